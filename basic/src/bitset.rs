@@ -0,0 +1,376 @@
+// 位集合.
+//
+// scalar() 里演示了 0b1111_0000 这样的位模式字面量, 以及 bool 上的 & | 运算.
+// BitSet 把一组非负整数存成一串 u64 的位: 第 i 个数在不在集合里, 就看第 i / 64 个 word 的第 i % 64 位.
+// 于是集合的交并差就变成了逐个 word 的 & | ^ 运算, 一次处理 64 个元素.
+//
+// 有两种模式:
+// BitSet::new() / BitSet::with_capacity(n) 是可增长的, insert 超出容量时自动扩容;
+// BitSet::fixed(n) 容量固定为 n 位, 越界访问会 panic, 就像数组越界一样.
+
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub, SubAssign};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Clone, Default)]
+pub struct BitSet {
+    words: Vec<u64>,
+    // Some(n) 表示容量固定为 n 位
+    fixed_len: Option<usize>,
+}
+
+fn word_count(bits: usize) -> usize {
+    bits.div_ceil(WORD_BITS)
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        BitSet {
+            words: Vec::new(),
+            fixed_len: None,
+        }
+    }
+
+    pub fn with_capacity(bits: usize) -> Self {
+        BitSet {
+            words: vec![0; word_count(bits)],
+            fixed_len: None,
+        }
+    }
+
+    pub fn fixed(bits: usize) -> Self {
+        BitSet {
+            words: vec![0; word_count(bits)],
+            fixed_len: Some(bits),
+        }
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.fixed_len.is_some()
+    }
+
+    // 不扩容的前提下能放下的位数
+    pub fn capacity(&self) -> usize {
+        self.fixed_len.unwrap_or(self.words.len() * WORD_BITS)
+    }
+
+    fn check_fixed(&self, i: usize) {
+        if let Some(len) = self.fixed_len {
+            assert!(
+                i < len,
+                "index out of bounds: the len is {} but the index is {}",
+                len,
+                i
+            );
+        }
+    }
+
+    // 返回 i 之前是否不在集合中 (与 HashSet::insert 语义一致)
+    pub fn insert(&mut self, i: usize) -> bool {
+        self.check_fixed(i);
+        let w = i / WORD_BITS;
+        if w >= self.words.len() {
+            self.words.resize(w + 1, 0);
+        }
+        let mask = 1 << (i % WORD_BITS);
+        let absent = self.words[w] & mask == 0;
+        self.words[w] |= mask;
+        absent
+    }
+
+    // 返回 i 之前是否在集合中
+    pub fn remove(&mut self, i: usize) -> bool {
+        self.check_fixed(i);
+        match self.words.get_mut(i / WORD_BITS) {
+            Some(word) => {
+                let mask = 1 << (i % WORD_BITS);
+                let present = *word & mask != 0;
+                *word &= !mask;
+                present
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.check_fixed(i);
+        match self.words.get(i / WORD_BITS) {
+            Some(word) => word & (1 << (i % WORD_BITS)) != 0,
+            None => false,
+        }
+    }
+
+    // set/clear/test 是位运算里的习惯叫法
+    pub fn set(&mut self, i: usize) {
+        self.insert(i);
+    }
+
+    pub fn clear(&mut self, i: usize) {
+        self.remove(i);
+    }
+
+    pub fn test(&self, i: usize) -> bool {
+        self.contains(i)
+    }
+
+    pub fn toggle(&mut self, i: usize) {
+        if !self.remove(i) {
+            self.insert(i);
+        }
+    }
+
+    pub fn clear_all(&mut self) {
+        self.words.iter_mut().for_each(|w| *w = 0);
+    }
+
+    // population count: 集合中元素的个数
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            index: 0,
+            current: self.words.first().copied().unwrap_or(0),
+        }
+    }
+
+    // rank(i): 集合中小于 i 的元素个数
+    pub fn rank(&self, i: usize) -> usize {
+        let w = i / WORD_BITS;
+        let full: usize = self
+            .words
+            .iter()
+            .take(w)
+            .map(|x| x.count_ones() as usize)
+            .sum();
+        let partial = match self.words.get(w) {
+            Some(word) => (word & ((1 << (i % WORD_BITS)) - 1)).count_ones() as usize,
+            None => 0,
+        };
+        full + partial
+    }
+
+    // select(k): 第 k 小的元素 (k 从 0 开始), 满足 rank(select(k)) == k
+    pub fn select(&self, mut k: usize) -> Option<usize> {
+        for (w, &word) in self.words.iter().enumerate() {
+            let ones = word.count_ones() as usize;
+            if k < ones {
+                // 清掉最低的 k 个 1, 剩下的最低位就是答案
+                let mut word = word;
+                for _ in 0..k {
+                    word &= word - 1;
+                }
+                return Some(w * WORD_BITS + word.trailing_zeros() as usize);
+            }
+            k -= ones;
+        }
+        None
+    }
+
+    pub fn min(&self) -> Option<usize> {
+        self.iter().next()
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        let (w, word) = self.words.iter().enumerate().rev().find(|(_, &w)| w != 0)?;
+        Some(w * WORD_BITS + (WORD_BITS - 1 - word.leading_zeros() as usize))
+    }
+
+    // 逐 word 合并. 并集/对称差时, 可增长的集合会扩容到能容纳 other 的所有元素,
+    // 固定容量的集合要求 other 的元素都在容量之内. 交集/差集不会产生新元素, 不需要扩容.
+    fn combine_with(&mut self, other: &BitSet, grow: bool, f: impl Fn(u64, u64) -> u64) {
+        if grow {
+            // fixed(10) 和 fixed(64) 都只有一个 word, 所以不能只在 word 数不够时检查
+            if self.fixed_len.is_some() {
+                if let Some(max) = other.max() {
+                    self.check_fixed(max);
+                }
+            }
+            if other.words.len() > self.words.len() {
+                self.words.resize(other.words.len(), 0);
+            }
+        }
+        for (i, word) in self.words.iter_mut().enumerate() {
+            *word = f(*word, other.words.get(i).copied().unwrap_or(0));
+        }
+    }
+
+    pub fn union_with(&mut self, other: &BitSet) {
+        self.combine_with(other, true, |a, b| a | b);
+    }
+
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        self.combine_with(other, false, |a, b| a & b);
+    }
+
+    pub fn difference_with(&mut self, other: &BitSet) {
+        self.combine_with(other, false, |a, b| a & !b);
+    }
+
+    pub fn symmetric_difference_with(&mut self, other: &BitSet) {
+        self.combine_with(other, true, |a, b| a ^ b);
+    }
+
+    pub fn union(&self, other: &BitSet) -> BitSet {
+        let mut r = self.clone();
+        r.union_with(other);
+        r
+    }
+
+    pub fn intersection(&self, other: &BitSet) -> BitSet {
+        let mut r = self.clone();
+        r.intersect_with(other);
+        r
+    }
+
+    pub fn difference(&self, other: &BitSet) -> BitSet {
+        let mut r = self.clone();
+        r.difference_with(other);
+        r
+    }
+
+    pub fn symmetric_difference(&self, other: &BitSet) -> BitSet {
+        let mut r = self.clone();
+        r.symmetric_difference_with(other);
+        r
+    }
+
+    pub fn is_subset(&self, other: &BitSet) -> bool {
+        self.words
+            .iter()
+            .enumerate()
+            .all(|(i, &w)| w & !other.words.get(i).copied().unwrap_or(0) == 0)
+    }
+
+    pub fn is_disjoint(&self, other: &BitSet) -> bool {
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .all(|(a, b)| a & b == 0)
+    }
+}
+
+// 末尾全 0 的 word 不影响集合的内容, 所以不能直接 derive PartialEq
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        let n = self.words.len().max(other.words.len());
+        (0..n).all(|i| {
+            self.words.get(i).copied().unwrap_or(0) == other.words.get(i).copied().unwrap_or(0)
+        })
+    }
+}
+
+impl Eq for BitSet {}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// 按位打印, 低位在右边, 与 0b 字面量的写法一致
+impl fmt::Binary for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let top = self.words.iter().rposition(|&w| w != 0);
+        match top {
+            None => write!(f, "0"),
+            Some(top) => {
+                write!(f, "{:b}", self.words[top])?;
+                for w in self.words[..top].iter().rev() {
+                    write!(f, "{:064b}", w)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    words: &'a [u64],
+    index: usize,
+    current: u64,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.index += 1;
+            self.current = *self.words.get(self.index)?;
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        // 清掉最低位的 1
+        self.current &= self.current - 1;
+        Some(self.index * WORD_BITS + bit)
+    }
+}
+
+impl<'a> IntoIterator for &'a BitSet {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<usize> for BitSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for i in iter {
+            self.insert(i);
+        }
+    }
+}
+
+// &a | &b, &a & &b, &a ^ &b, &a - &b, 与 HashSet 的运算符一致
+macro_rules! impl_op {
+    ($op:ident, $method:ident, $assign:ident, $assign_method:ident, $with:ident, $new:ident) => {
+        impl $op<&BitSet> for &BitSet {
+            type Output = BitSet;
+            fn $method(self, rhs: &BitSet) -> BitSet {
+                self.$new(rhs)
+            }
+        }
+
+        impl $assign<&BitSet> for BitSet {
+            fn $assign_method(&mut self, rhs: &BitSet) {
+                self.$with(rhs);
+            }
+        }
+    };
+}
+
+impl_op!(BitOr, bitor, BitOrAssign, bitor_assign, union_with, union);
+impl_op!(
+    BitAnd,
+    bitand,
+    BitAndAssign,
+    bitand_assign,
+    intersect_with,
+    intersection
+);
+impl_op!(
+    BitXor,
+    bitxor,
+    BitXorAssign,
+    bitxor_assign,
+    symmetric_difference_with,
+    symmetric_difference
+);
+impl_op!(Sub, sub, SubAssign, sub_assign, difference_with, difference);
@@ -0,0 +1,131 @@
+// 一个小型的 Rust 风格表达式语言的解释器.
+//
+// if_() 和 for_loop() 里说明了:
+// if 是表达式, 有值; loop 也是表达式, 值由 break value 给出; while 的值永远是 ().
+// 这里实现一门遵循同样规则的小语言, 分为四步:
+// lexer (源码 -> token) -> parser (token -> 语法树) -> check (静态类型检查) -> eval (求值)
+//
+// 支持: 整数 (i64), 浮点数 (f64), bool, (), let/let mut, 赋值和 += 等复合赋值,
+// 块表达式 { ...; tail }, if/else, loop/break value, while, continue, 算术/比较/逻辑运算.
+//
+// 与 rustc 一样, 类型错误在运行之前就会被报告, 并给出对应的错误码, 例如:
+//     5 + 1.0  =>  error[E0277]: cannot add `float` to `integer`
+
+mod check;
+mod eval;
+mod lexer;
+mod parser;
+
+use std::fmt;
+
+pub use eval::{Interpreter, Value};
+
+// 源码中的字节区间 [start, end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Lex,
+    Parse,
+    Type,
+    // 运行时错误, 对应 Rust 里的 panic, 例如整数溢出, 除以 0
+    Runtime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub phase: Phase,
+    // rustc 的错误码, 例如 E0277. 运行时错误和语法错误没有错误码.
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Error {
+    pub fn new(phase: Phase, message: impl Into<String>, span: Span) -> Self {
+        Error {
+            phase,
+            code: None,
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    // 把字节偏移换算成 1 开始的行号和列号 (列号按 char 计)
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.span.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, col)
+    }
+
+    // 类似 rustc 的输出格式:
+    // error[E0277]: cannot add `float` to `integer`
+    //  --> 1:3
+    //   |
+    // 1 | 5 + 1.0
+    //   |   ^
+    pub fn render(&self, source: &str) -> String {
+        let (line, col) = self.line_col(source);
+        let text = source.lines().nth(line - 1).unwrap_or("");
+        let width = self.span.end.saturating_sub(self.span.start).max(1);
+        let width = width.min(text.len() + 1 - col.min(text.len() + 1)).max(1);
+        let gutter = " ".repeat(line.to_string().len());
+        format!(
+            "{}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}",
+            self,
+            gutter,
+            line,
+            col,
+            gutter,
+            line,
+            text,
+            gutter,
+            " ".repeat(col - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.phase, self.code) {
+            (Phase::Runtime, _) => write!(f, "panicked: {}", self.message),
+            (_, Some(code)) => write!(f, "error[{}]: {}", code, self.message),
+            (_, None) => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// 词法分析, 语法分析, 类型检查, 求值一步到位
+pub fn run(source: &str) -> Result<Value, Error> {
+    Interpreter::new().run(source)
+}
+
+// 只做类型检查, 返回程序的类型名
+pub fn type_of(source: &str) -> Result<&'static str, Error> {
+    let tokens = lexer::tokenize(source)?;
+    let program = parser::parse(&tokens)?;
+    Ok(check::check(&program)?.name())
+}
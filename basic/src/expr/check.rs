@@ -0,0 +1,372 @@
+// 静态类型检查. 与 rustc 一样, 在运行之前就把类型错误找出来, 即使出错的分支不会被执行.
+
+use std::collections::HashMap;
+
+use super::parser::{BinOp, Block, Expr, ExprKind, Stmt, UnOp};
+use super::{Error, Phase, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    Unit,
+    // never 类型 !, 例如 break, continue, 没有 break 的 loop. 它可以转换成任何类型.
+    Never,
+}
+
+impl Type {
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Int => "integer",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::Unit => "()",
+            Type::Never => "!",
+        }
+    }
+
+    fn quoted(self) -> String {
+        format!("`{}`", self.name())
+    }
+}
+
+// 两个分支的类型合并, ! 可以与任何类型合并
+fn unify(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        (Type::Never, t) | (t, Type::Never) => Some(t),
+        (a, b) if a == b => Some(a),
+        _ => None,
+    }
+}
+
+fn type_error(code: &'static str, message: impl Into<String>, span: Span) -> Error {
+    Error::new(Phase::Type, message, span).with_code(code)
+}
+
+fn mismatched(expected: Type, found: Type, span: Span) -> Error {
+    type_error(
+        "E0308",
+        format!(
+            "mismatched types: expected {}, found {}",
+            expected.quoted(),
+            found.quoted()
+        ),
+        span,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoopKind {
+    Loop,
+    While,
+}
+
+struct LoopCtx {
+    kind: LoopKind,
+    // loop 中所有 break value 的类型
+    break_ty: Option<Type>,
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, (Type, bool)>>,
+    loops: Vec<LoopCtx>,
+}
+
+pub fn check(program: &Block) -> Result<Type, Error> {
+    let mut c = Checker {
+        scopes: Vec::new(),
+        loops: Vec::new(),
+    };
+    c.block(program)
+}
+
+impl Checker {
+    fn lookup(&self, name: &str) -> Option<(Type, bool)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    fn block(&mut self, block: &Block) -> Result<Type, Error> {
+        self.scopes.push(HashMap::new());
+        let result = self.block_inner(block);
+        self.scopes.pop();
+        result
+    }
+
+    fn block_inner(&mut self, block: &Block) -> Result<Type, Error> {
+        let mut diverges = false;
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Let {
+                    name,
+                    mutable,
+                    init,
+                    ..
+                } => {
+                    let ty = self.expr(init)?;
+                    diverges |= ty == Type::Never;
+                    self.scopes
+                        .last_mut()
+                        .unwrap()
+                        .insert(name.clone(), (ty, *mutable));
+                }
+                Stmt::Expr { expr, semi } => {
+                    let ty = self.expr(expr)?;
+                    diverges |= ty == Type::Never;
+                    // 省略分号的块状表达式语句必须是 ()
+                    if !semi && unify(ty, Type::Unit).is_none() {
+                        return Err(mismatched(Type::Unit, ty, expr.span));
+                    }
+                }
+            }
+        }
+        match &block.tail {
+            Some(tail) => {
+                let ty = self.expr(tail)?;
+                Ok(if diverges { Type::Never } else { ty })
+            }
+            None if diverges => Ok(Type::Never),
+            None => Ok(Type::Unit),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<Type, Error> {
+        match &expr.kind {
+            ExprKind::Unit => Ok(Type::Unit),
+            ExprKind::Int(_) => Ok(Type::Int),
+            ExprKind::Float(_) => Ok(Type::Float),
+            ExprKind::Bool(_) => Ok(Type::Bool),
+            ExprKind::Var(name) => match self.lookup(name) {
+                Some((ty, _)) => Ok(ty),
+                None => Err(type_error(
+                    "E0425",
+                    format!("cannot find value `{}` in this scope", name),
+                    expr.span,
+                )),
+            },
+            ExprKind::Unary(op, operand) => {
+                let ty = self.expr(operand)?;
+                match (op, ty) {
+                    (_, Type::Never) => Ok(Type::Never),
+                    (UnOp::Neg, Type::Int | Type::Float) => Ok(ty),
+                    // 整数上的 ! 是按位取反
+                    (UnOp::Not, Type::Int | Type::Bool) => Ok(ty),
+                    _ => {
+                        let symbol = if *op == UnOp::Neg { "-" } else { "!" };
+                        Err(type_error(
+                            "E0600",
+                            format!(
+                                "cannot apply unary operator `{}` to type {}",
+                                symbol,
+                                ty.quoted()
+                            ),
+                            expr.span,
+                        ))
+                    }
+                }
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let l = self.expr(lhs)?;
+                let r = self.expr(rhs)?;
+                binary_type(*op, l, r, expr.span, rhs.span)
+            }
+            ExprKind::Assign {
+                name,
+                name_span,
+                op,
+                value,
+            } => {
+                let (var_ty, mutable) = match self.lookup(name) {
+                    Some(v) => v,
+                    None => {
+                        return Err(type_error(
+                            "E0425",
+                            format!("cannot find value `{}` in this scope", name),
+                            *name_span,
+                        ))
+                    }
+                };
+                let value_ty = self.expr(value)?;
+                let result_ty = match op {
+                    Some(op) => binary_type(*op, var_ty, value_ty, expr.span, value.span)?,
+                    None => value_ty,
+                };
+                if unify(var_ty, result_ty).is_none() {
+                    return Err(mismatched(var_ty, result_ty, value.span));
+                }
+                if !mutable {
+                    return Err(type_error(
+                        "E0384",
+                        format!("cannot assign twice to immutable variable `{}`", name),
+                        expr.span,
+                    ));
+                }
+                Ok(Type::Unit)
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If { cond, then, els } => {
+                self.condition(cond)?;
+                let then_ty = self.block(then)?;
+                match els {
+                    Some(els) => {
+                        let else_ty = self.expr(els)?;
+                        unify(then_ty, else_ty).ok_or_else(|| {
+                            type_error(
+                                "E0308",
+                                format!(
+                                    "`if` and `else` have incompatible types: expected {}, found {}",
+                                    then_ty.quoted(),
+                                    else_ty.quoted()
+                                ),
+                                els.span,
+                            )
+                        })
+                    }
+                    None => {
+                        if unify(then_ty, Type::Unit).is_none() {
+                            return Err(type_error(
+                                "E0317",
+                                format!(
+                                    "`if` may be missing an `else` clause: expected `()`, found {}",
+                                    then_ty.quoted()
+                                ),
+                                expr.span,
+                            ));
+                        }
+                        Ok(Type::Unit)
+                    }
+                }
+            }
+            ExprKind::Loop(body) => {
+                let ctx = self.loop_body(LoopKind::Loop, body)?;
+                // 没有 break 的 loop 永远不会结束
+                Ok(ctx.break_ty.unwrap_or(Type::Never))
+            }
+            ExprKind::While { cond, body } => {
+                self.condition(cond)?;
+                self.loop_body(LoopKind::While, body)?;
+                Ok(Type::Unit)
+            }
+            ExprKind::Break(value) => {
+                let ty = match value {
+                    Some(v) => self.expr(v)?,
+                    None => Type::Unit,
+                };
+                let ctx = match self.loops.last_mut() {
+                    Some(ctx) => ctx,
+                    None => {
+                        return Err(type_error("E0268", "`break` outside of a loop", expr.span))
+                    }
+                };
+                if ctx.kind == LoopKind::While && value.is_some() {
+                    // for_loop() 里的注释: can only break with a value inside `loop`
+                    return Err(type_error(
+                        "E0571",
+                        "`break` with value from a `while` loop",
+                        expr.span,
+                    ));
+                }
+                ctx.break_ty = match ctx.break_ty {
+                    None => Some(ty),
+                    Some(prev) => {
+                        Some(unify(prev, ty).ok_or_else(|| mismatched(prev, ty, expr.span))?)
+                    }
+                };
+                Ok(Type::Never)
+            }
+            ExprKind::Continue => {
+                if self.loops.is_empty() {
+                    return Err(type_error(
+                        "E0268",
+                        "`continue` outside of a loop",
+                        expr.span,
+                    ));
+                }
+                Ok(Type::Never)
+            }
+        }
+    }
+
+    // if_() 里的注释: rust 不会把非 bool 值隐式转换成 bool 值 (mismatched types [E0308])
+    fn condition(&mut self, cond: &Expr) -> Result<(), Error> {
+        let ty = self.expr(cond)?;
+        if unify(ty, Type::Bool).is_none() {
+            return Err(mismatched(Type::Bool, ty, cond.span));
+        }
+        Ok(())
+    }
+
+    fn loop_body(&mut self, kind: LoopKind, body: &Block) -> Result<LoopCtx, Error> {
+        self.loops.push(LoopCtx {
+            kind,
+            break_ty: None,
+        });
+        let result = self.block(body);
+        let ctx = self.loops.pop().unwrap();
+        let ty = result?;
+        if unify(ty, Type::Unit).is_none() {
+            return Err(mismatched(Type::Unit, ty, body.span));
+        }
+        Ok(ctx)
+    }
+}
+
+fn binary_type(op: BinOp, l: Type, r: Type, span: Span, rhs_span: Span) -> Result<Type, Error> {
+    if l == Type::Never || r == Type::Never {
+        return Ok(Type::Never);
+    }
+    match op {
+        BinOp::And | BinOp::Or => {
+            for (ty, s) in [(l, span), (r, rhs_span)] {
+                if ty != Type::Bool {
+                    return Err(mismatched(Type::Bool, ty, s));
+                }
+            }
+            Ok(Type::Bool)
+        }
+        _ if op.is_comparison() => {
+            if l != r {
+                return Err(mismatched(l, r, rhs_span));
+            }
+            if l == Type::Unit && !matches!(op, BinOp::Eq | BinOp::Ne) {
+                return Err(unsupported(op, l, span));
+            }
+            Ok(Type::Bool)
+        }
+        _ => match (l, r) {
+            (Type::Int, Type::Int) => Ok(Type::Int),
+            (Type::Float, Type::Float) => Ok(Type::Float),
+            // 5 + 1.0: cannot add a float to an integer [E0277]
+            (Type::Int | Type::Float, Type::Int | Type::Float) => {
+                let message = match op {
+                    BinOp::Add => format!("cannot add {} to {}", r.quoted(), l.quoted()),
+                    BinOp::Sub => format!("cannot subtract {} from {}", r.quoted(), l.quoted()),
+                    BinOp::Mul => format!("cannot multiply {} by {}", l.quoted(), r.quoted()),
+                    BinOp::Div => format!("cannot divide {} by {}", l.quoted(), r.quoted()),
+                    _ => format!(
+                        "cannot calculate the remainder of {} divided by {}",
+                        l.quoted(),
+                        r.quoted()
+                    ),
+                };
+                Err(type_error("E0277", message, span))
+            }
+            (Type::Int | Type::Float, other) | (other, _) => Err(unsupported(op, other, span)),
+        },
+    }
+}
+
+fn unsupported(op: BinOp, ty: Type, span: Span) -> Error {
+    type_error(
+        "E0369",
+        format!(
+            "binary operation `{}` cannot be applied to type {}",
+            op.symbol(),
+            ty.quoted()
+        ),
+        span,
+    )
+}
@@ -0,0 +1,272 @@
+// 求值. 程序已经通过了类型检查, 所以这里遇到类型不符只可能是解释器自身的 bug;
+// 真正的运行时错误只有 Rust 里会 panic 的那几种: 整数溢出, 除以 0.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::parser::{BinOp, Block, Expr, ExprKind, Stmt, UnOp};
+use super::{check, lexer, parser, Error, Phase, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Unit,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{:?}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+// 控制流: 正常求值之外, break/continue 需要沿着调用栈向外传播到最近的循环
+enum Flow {
+    Break(Value),
+    Continue,
+    Error(Error),
+}
+
+impl From<Error> for Flow {
+    fn from(e: Error) -> Self {
+        Flow::Error(e)
+    }
+}
+
+type EvalResult = Result<Value, Flow>;
+
+pub struct Interpreter {
+    scopes: Vec<HashMap<String, Value>>,
+    // 最多执行多少步, 防止 loop {} 这样的死循环让程序卡住
+    max_steps: u64,
+    steps: u64,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn runtime_error(message: impl Into<String>, span: Span) -> Flow {
+    Flow::Error(Error::new(Phase::Runtime, message, span))
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            scopes: Vec::new(),
+            max_steps: 10_000_000,
+            steps: 0,
+        }
+    }
+
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn run(&mut self, source: &str) -> Result<Value, Error> {
+        let tokens = lexer::tokenize(source)?;
+        let program = parser::parse(&tokens)?;
+        check::check(&program)?;
+        self.scopes.clear();
+        self.steps = 0;
+        match self.block(&program) {
+            Ok(v) => Ok(v),
+            Err(Flow::Error(e)) => Err(e),
+            // 类型检查保证了 break/continue 都在循环内
+            Err(Flow::Break(_)) | Err(Flow::Continue) => unreachable!(),
+        }
+    }
+
+    fn tick(&mut self, span: Span) -> Result<(), Flow> {
+        self.steps += 1;
+        if self.steps > self.max_steps {
+            return Err(runtime_error(
+                format!("evaluation step limit of {} exceeded", self.max_steps),
+                span,
+            ));
+        }
+        Ok(())
+    }
+
+    fn lookup(&mut self, name: &str) -> &mut Value {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .expect("variable checked by type checker")
+    }
+
+    fn block(&mut self, block: &Block) -> EvalResult {
+        self.scopes.push(HashMap::new());
+        let result = self.block_inner(block);
+        self.scopes.pop();
+        result
+    }
+
+    fn block_inner(&mut self, block: &Block) -> EvalResult {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Let { name, init, .. } => {
+                    let v = self.expr(init)?;
+                    // 同名变量会遮蔽 (shadow) 之前的变量
+                    self.scopes.last_mut().unwrap().insert(name.clone(), v);
+                }
+                Stmt::Expr { expr, .. } => {
+                    self.expr(expr)?;
+                }
+            }
+        }
+        match &block.tail {
+            Some(tail) => self.expr(tail),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> EvalResult {
+        self.tick(expr.span)?;
+        match &expr.kind {
+            ExprKind::Unit => Ok(Value::Unit),
+            ExprKind::Int(v) => Ok(Value::Int(*v)),
+            ExprKind::Float(v) => Ok(Value::Float(*v)),
+            ExprKind::Bool(v) => Ok(Value::Bool(*v)),
+            ExprKind::Var(name) => Ok(*self.lookup(name)),
+            ExprKind::Unary(op, operand) => {
+                let v = self.expr(operand)?;
+                match (op, v) {
+                    (UnOp::Neg, Value::Int(i)) => i
+                        .checked_neg()
+                        .map(Value::Int)
+                        .ok_or_else(|| runtime_error("attempt to negate with overflow", expr.span)),
+                    (UnOp::Neg, Value::Float(f)) => Ok(Value::Float(-f)),
+                    (UnOp::Not, Value::Int(i)) => Ok(Value::Int(!i)),
+                    (UnOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    _ => unreachable!("checked by type checker"),
+                }
+            }
+            // && 和 || 是短路求值的
+            ExprKind::Binary(BinOp::And, lhs, rhs) => match self.expr(lhs)? {
+                Value::Bool(false) => Ok(Value::Bool(false)),
+                _ => self.expr(rhs),
+            },
+            ExprKind::Binary(BinOp::Or, lhs, rhs) => match self.expr(lhs)? {
+                Value::Bool(true) => Ok(Value::Bool(true)),
+                _ => self.expr(rhs),
+            },
+            ExprKind::Binary(op, lhs, rhs) => {
+                let l = self.expr(lhs)?;
+                let r = self.expr(rhs)?;
+                binary(*op, l, r, expr.span)
+            }
+            ExprKind::Assign {
+                name, op, value, ..
+            } => {
+                let v = self.expr(value)?;
+                let v = match op {
+                    Some(op) => binary(*op, *self.lookup(name), v, expr.span)?,
+                    None => v,
+                };
+                *self.lookup(name) = v;
+                Ok(Value::Unit)
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If { cond, then, els } => {
+                if self.expr(cond)? == Value::Bool(true) {
+                    self.block(then)
+                } else if let Some(els) = els {
+                    self.expr(els)
+                } else {
+                    Ok(Value::Unit)
+                }
+            }
+            ExprKind::Loop(body) => loop {
+                self.tick(expr.span)?;
+                match self.block(body) {
+                    Ok(_) | Err(Flow::Continue) => continue,
+                    Err(Flow::Break(v)) => break Ok(v),
+                    Err(e) => break Err(e),
+                }
+            },
+            ExprKind::While { cond, body } => {
+                while self.expr(cond)? == Value::Bool(true) {
+                    match self.block(body) {
+                        Ok(_) | Err(Flow::Continue) => {}
+                        Err(Flow::Break(_)) => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+                // while 的值永远是 ()
+                Ok(Value::Unit)
+            }
+            ExprKind::Break(value) => {
+                let v = match value {
+                    Some(v) => self.expr(v)?,
+                    None => Value::Unit,
+                };
+                Err(Flow::Break(v))
+            }
+            ExprKind::Continue => Err(Flow::Continue),
+        }
+    }
+}
+
+fn binary(op: BinOp, l: Value, r: Value, span: Span) -> EvalResult {
+    use Value::*;
+    let overflow = |what: &str| runtime_error(format!("attempt to {} with overflow", what), span);
+    Ok(match (l, r) {
+        (Int(a), Int(b)) => match op {
+            BinOp::Add => Int(a.checked_add(b).ok_or_else(|| overflow("add"))?),
+            BinOp::Sub => Int(a.checked_sub(b).ok_or_else(|| overflow("subtract"))?),
+            BinOp::Mul => Int(a.checked_mul(b).ok_or_else(|| overflow("multiply"))?),
+            BinOp::Div if b == 0 => return Err(runtime_error("attempt to divide by zero", span)),
+            BinOp::Div => Int(a.checked_div(b).ok_or_else(|| overflow("divide"))?),
+            BinOp::Rem if b == 0 => {
+                return Err(runtime_error(
+                    "attempt to calculate the remainder with a divisor of zero",
+                    span,
+                ))
+            }
+            BinOp::Rem => Int(a
+                .checked_rem(b)
+                .ok_or_else(|| overflow("calculate the remainder"))?),
+            _ => Bool(compare(op, a.cmp(&b))),
+        },
+        // 浮点数除以 0 得到 inf, 不会 panic, 与 scalar() 里的 1.0 / 0.0 一致
+        (Float(a), Float(b)) => match op {
+            BinOp::Add => Float(a + b),
+            BinOp::Sub => Float(a - b),
+            BinOp::Mul => Float(a * b),
+            BinOp::Div => Float(a / b),
+            BinOp::Rem => Float(a % b),
+            // NaN 与任何数比较都是 false (!= 除外)
+            _ => match a.partial_cmp(&b) {
+                Some(ord) => Bool(compare(op, ord)),
+                None => Bool(op == BinOp::Ne),
+            },
+        },
+        (Bool(a), Bool(b)) => Bool(compare(op, a.cmp(&b))),
+        (Unit, Unit) => Bool(op == BinOp::Eq),
+        _ => unreachable!("checked by type checker"),
+    })
+}
+
+fn compare(op: BinOp, ord: std::cmp::Ordering) -> bool {
+    use std::cmp::Ordering::*;
+    match op {
+        BinOp::Eq => ord == Equal,
+        BinOp::Ne => ord != Equal,
+        BinOp::Lt => ord == Less,
+        BinOp::Le => ord != Greater,
+        BinOp::Gt => ord == Greater,
+        BinOp::Ge => ord != Less,
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,252 @@
+use super::{Error, Phase, Span};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    Int(i64),
+    Float(f64),
+    Ident(String),
+    True,
+    False,
+    Let,
+    Mut,
+    If,
+    Else,
+    Loop,
+    While,
+    Break,
+    Continue,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Bang,
+    Eq,
+    EqEq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    AndAnd,
+    OrOr,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Semi,
+    Eof,
+}
+
+impl Tok {
+    pub fn describe(&self) -> String {
+        match self {
+            Tok::Int(v) => format!("`{}`", v),
+            Tok::Float(v) => format!("`{:?}`", v),
+            Tok::Ident(name) => format!("`{}`", name),
+            Tok::Eof => String::from("end of input"),
+            other => format!("`{}`", other.text()),
+        }
+    }
+
+    fn text(&self) -> &'static str {
+        match self {
+            Tok::True => "true",
+            Tok::False => "false",
+            Tok::Let => "let",
+            Tok::Mut => "mut",
+            Tok::If => "if",
+            Tok::Else => "else",
+            Tok::Loop => "loop",
+            Tok::While => "while",
+            Tok::Break => "break",
+            Tok::Continue => "continue",
+            Tok::Plus => "+",
+            Tok::Minus => "-",
+            Tok::Star => "*",
+            Tok::Slash => "/",
+            Tok::Percent => "%",
+            Tok::Bang => "!",
+            Tok::Eq => "=",
+            Tok::EqEq => "==",
+            Tok::Ne => "!=",
+            Tok::Lt => "<",
+            Tok::Le => "<=",
+            Tok::Gt => ">",
+            Tok::Ge => ">=",
+            Tok::AndAnd => "&&",
+            Tok::OrOr => "||",
+            Tok::PlusEq => "+=",
+            Tok::MinusEq => "-=",
+            Tok::StarEq => "*=",
+            Tok::SlashEq => "/=",
+            Tok::PercentEq => "%=",
+            Tok::LParen => "(",
+            Tok::RParen => ")",
+            Tok::LBrace => "{",
+            Tok::RBrace => "}",
+            Tok::Semi => ";",
+            Tok::Int(_) | Tok::Float(_) | Tok::Ident(_) | Tok::Eof => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub tok: Tok,
+    pub span: Span,
+}
+
+fn lex_error(message: impl Into<String>, start: usize, end: usize) -> Error {
+    Error::new(Phase::Lex, message, Span::new(start, end))
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        // 行注释
+        if source[i..].starts_with("//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let tok = if c.is_ascii_digit() {
+            let (tok, end) = lex_number(source, start)?;
+            i = end;
+            tok
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            match &source[start..i] {
+                "true" => Tok::True,
+                "false" => Tok::False,
+                "let" => Tok::Let,
+                "mut" => Tok::Mut,
+                "if" => Tok::If,
+                "else" => Tok::Else,
+                "loop" => Tok::Loop,
+                "while" => Tok::While,
+                "break" => Tok::Break,
+                "continue" => Tok::Continue,
+                name => Tok::Ident(name.to_string()),
+            }
+        } else {
+            let two = source.get(i..i + 2).unwrap_or("");
+            let double = match two {
+                "==" => Some(Tok::EqEq),
+                "!=" => Some(Tok::Ne),
+                "<=" => Some(Tok::Le),
+                ">=" => Some(Tok::Ge),
+                "&&" => Some(Tok::AndAnd),
+                "||" => Some(Tok::OrOr),
+                "+=" => Some(Tok::PlusEq),
+                "-=" => Some(Tok::MinusEq),
+                "*=" => Some(Tok::StarEq),
+                "/=" => Some(Tok::SlashEq),
+                "%=" => Some(Tok::PercentEq),
+                _ => None,
+            };
+            if let Some(tok) = double {
+                i += 2;
+                tok
+            } else {
+                i += 1;
+                match c {
+                    b'+' => Tok::Plus,
+                    b'-' => Tok::Minus,
+                    b'*' => Tok::Star,
+                    b'/' => Tok::Slash,
+                    b'%' => Tok::Percent,
+                    b'!' => Tok::Bang,
+                    b'=' => Tok::Eq,
+                    b'<' => Tok::Lt,
+                    b'>' => Tok::Gt,
+                    b'(' => Tok::LParen,
+                    b')' => Tok::RParen,
+                    b'{' => Tok::LBrace,
+                    b'}' => Tok::RBrace,
+                    b';' => Tok::Semi,
+                    _ => {
+                        let ch = source[start..].chars().next().unwrap();
+                        return Err(lex_error(
+                            format!("unknown start of token: {}", ch.escape_default()),
+                            start,
+                            start + ch.len_utf8(),
+                        ));
+                    }
+                }
+            }
+        };
+        tokens.push(Token {
+            tok,
+            span: Span::new(start, i),
+        });
+    }
+    tokens.push(Token {
+        tok: Tok::Eof,
+        span: Span::new(bytes.len(), bytes.len()),
+    });
+    Ok(tokens)
+}
+
+// 十进制整数/浮点数, 允许 _ 分隔. 1.0 和 2.5e3 是浮点数, 1.max 这种不是.
+fn lex_number(source: &str, start: usize) -> Result<(Tok, usize), Error> {
+    let bytes = source.as_bytes();
+    let digits = |mut i: usize| {
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+            i += 1;
+        }
+        i
+    };
+    let mut i = digits(start);
+    let mut is_float = false;
+    if i + 1 < bytes.len() && bytes[i] == b'.' && bytes[i + 1].is_ascii_digit() {
+        is_float = true;
+        i = digits(i + 1);
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        let mut j = i + 1;
+        if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+            j += 1;
+        }
+        if j < bytes.len() && bytes[j].is_ascii_digit() {
+            is_float = true;
+            i = digits(j);
+        }
+    }
+    if i < bytes.len() && (bytes[i].is_ascii_alphabetic() || bytes[i] == b'_') {
+        let mut end = i;
+        while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+            end += 1;
+        }
+        return Err(lex_error(
+            format!("invalid suffix `{}` for number literal", &source[i..end]),
+            i,
+            end,
+        ));
+    }
+    let text: String = source[start..i].chars().filter(|&c| c != '_').collect();
+    if is_float {
+        Ok((Tok::Float(text.parse().unwrap()), i))
+    } else {
+        match text.parse() {
+            Ok(v) => Ok((Tok::Int(v), i)),
+            Err(_) => Err(lex_error("integer literal is too large", start, i)),
+        }
+    }
+}
@@ -0,0 +1,543 @@
+use super::lexer::{Tok, Token};
+use super::{Error, Phase, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge
+        )
+    }
+
+    // 优先级, 越大结合越紧
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 3,
+            BinOp::Add | BinOp::Sub => 4,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 5,
+        }
+    }
+
+    fn from_tok(tok: &Tok) -> Option<BinOp> {
+        Some(match tok {
+            Tok::Plus => BinOp::Add,
+            Tok::Minus => BinOp::Sub,
+            Tok::Star => BinOp::Mul,
+            Tok::Slash => BinOp::Div,
+            Tok::Percent => BinOp::Rem,
+            Tok::EqEq => BinOp::Eq,
+            Tok::Ne => BinOp::Ne,
+            Tok::Lt => BinOp::Lt,
+            Tok::Le => BinOp::Le,
+            Tok::Gt => BinOp::Gt,
+            Tok::Ge => BinOp::Ge,
+            Tok::AndAnd => BinOp::And,
+            Tok::OrOr => BinOp::Or,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Unit,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Var(String),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    // x = v 或 x += v (op 为 Some(Add))
+    Assign {
+        name: String,
+        name_span: Span,
+        op: Option<BinOp>,
+        value: Box<Expr>,
+    },
+    Block(Block),
+    If {
+        cond: Box<Expr>,
+        then: Block,
+        // else 分支是一个块, 或者是 else if 的另一个 If 表达式
+        els: Option<Box<Expr>>,
+    },
+    Loop(Block),
+    While {
+        cond: Box<Expr>,
+        body: Block,
+    },
+    Break(Option<Box<Expr>>),
+    Continue,
+}
+
+impl Expr {
+    // 块状表达式作为语句时可以省略分号, 与 Rust 相同
+    fn is_block_like(&self) -> bool {
+        matches!(
+            self.kind,
+            ExprKind::Block(_) | ExprKind::If { .. } | ExprKind::Loop(_) | ExprKind::While { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    // 最后一个不带分号的表达式, 是块的值
+    pub tail: Option<Box<Expr>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Let {
+        name: String,
+        mutable: bool,
+        init: Expr,
+        span: Span,
+    },
+    // semi 为 false 的只可能是省略了分号的块状表达式
+    Expr {
+        expr: Expr,
+        semi: bool,
+    },
+}
+
+// 递归下降的嵌套层数上限. 检查和求值同样是递归的, 不加限制的话
+// 十万个 { 或 ( 就能把栈撑爆.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    depth: usize,
+}
+
+fn parse_error(message: impl Into<String>, span: Span) -> Error {
+    Error::new(Phase::Parse, message, span)
+}
+
+// 整个程序被当成一个没有花括号的块来解析, 它的值就是程序的值
+pub fn parse(tokens: &[Token]) -> Result<Block, Error> {
+    let mut p = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let block = p.block_body(0, &Tok::Eof)?;
+    Ok(block)
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, n: usize) -> &Tok {
+        let i = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[i].tok
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].span
+    }
+
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    fn bump(&mut self) -> &Token {
+        let t = &self.tokens[self.pos];
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        t
+    }
+
+    // 在嵌套一层的状态下运行 f, 超过 MAX_DEPTH 时报语法错误
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(parse_error(
+                format!("expression nested more than {} levels deep", MAX_DEPTH),
+                self.span(),
+            ));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn eat(&mut self, tok: &Tok) -> bool {
+        if self.peek() == tok {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, tok: &Tok) -> Result<Span, Error> {
+        if self.peek() == tok {
+            Ok(self.bump().span)
+        } else {
+            Err(parse_error(
+                format!(
+                    "expected {}, found {}",
+                    tok.describe(),
+                    self.peek().describe()
+                ),
+                self.span(),
+            ))
+        }
+    }
+
+    // 解析块内的语句, 直到遇到 end (不消耗 end)
+    fn block_body(&mut self, start: usize, end: &Tok) -> Result<Block, Error> {
+        let mut stmts = Vec::new();
+        let mut tail = None;
+        while self.peek() != end {
+            if self.eat(&Tok::Semi) {
+                continue;
+            }
+            if self.peek() == &Tok::Eof {
+                return Err(parse_error(
+                    format!("expected {}, found end of input", end.describe()),
+                    self.span(),
+                ));
+            }
+            if *self.peek() == Tok::Let {
+                stmts.push(self.let_stmt()?);
+                continue;
+            }
+            // 语句开头的块状表达式在 } 处结束, 后面不再接二元运算符
+            let expr = if self.starts_block_like() {
+                self.block_like()?
+            } else {
+                self.expr()?
+            };
+            if self.eat(&Tok::Semi) {
+                stmts.push(Stmt::Expr { expr, semi: true });
+            } else if self.peek() == end {
+                tail = Some(Box::new(expr));
+            } else if expr.is_block_like() {
+                stmts.push(Stmt::Expr { expr, semi: false });
+            } else {
+                return Err(parse_error(
+                    format!("expected `;`, found {}", self.peek().describe()),
+                    self.span(),
+                ));
+            }
+        }
+        let end_pos = self.span().start;
+        Ok(Block {
+            stmts,
+            tail,
+            span: Span::new(start, end_pos),
+        })
+    }
+
+    fn let_stmt(&mut self) -> Result<Stmt, Error> {
+        let start = self.expect(&Tok::Let)?;
+        let mutable = self.eat(&Tok::Mut);
+        let name = match self.peek().clone() {
+            Tok::Ident(name) => {
+                self.bump();
+                name
+            }
+            other => {
+                return Err(parse_error(
+                    format!("expected identifier, found {}", other.describe()),
+                    self.span(),
+                ))
+            }
+        };
+        self.expect(&Tok::Eq)?;
+        let init = self.expr()?;
+        let end = self.expect(&Tok::Semi)?;
+        Ok(Stmt::Let {
+            name,
+            mutable,
+            init,
+            span: start.to(end),
+        })
+    }
+
+    fn starts_block_like(&self) -> bool {
+        matches!(self.peek(), Tok::LBrace | Tok::If | Tok::Loop | Tok::While)
+    }
+
+    fn block_like(&mut self) -> Result<Expr, Error> {
+        let start = self.span();
+        match self.peek() {
+            Tok::LBrace => {
+                let block = self.block()?;
+                Ok(Expr {
+                    span: block.span,
+                    kind: ExprKind::Block(block),
+                })
+            }
+            Tok::If => self.if_expr(),
+            Tok::Loop => {
+                self.bump();
+                let body = self.block()?;
+                Ok(Expr {
+                    span: start.to(body.span),
+                    kind: ExprKind::Loop(body),
+                })
+            }
+            Tok::While => {
+                self.bump();
+                let cond = self.expr()?;
+                let body = self.block()?;
+                Ok(Expr {
+                    span: start.to(body.span),
+                    kind: ExprKind::While {
+                        cond: Box::new(cond),
+                        body,
+                    },
+                })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // { stmts; tail }
+    fn block(&mut self) -> Result<Block, Error> {
+        self.nested(Self::block_inner)
+    }
+
+    fn block_inner(&mut self) -> Result<Block, Error> {
+        let open = self.expect(&Tok::LBrace)?;
+        let mut block = self.block_body(open.start, &Tok::RBrace)?;
+        let close = self.expect(&Tok::RBrace)?;
+        block.span = open.to(close);
+        Ok(block)
+    }
+
+    fn if_expr(&mut self) -> Result<Expr, Error> {
+        let start = self.expect(&Tok::If)?;
+        let cond = self.expr()?;
+        let then = self.block()?;
+        let mut end = then.span;
+        let els = if self.eat(&Tok::Else) {
+            let e = if *self.peek() == Tok::If {
+                self.nested(Self::if_expr)?
+            } else {
+                let block = self.block()?;
+                Expr {
+                    span: block.span,
+                    kind: ExprKind::Block(block),
+                }
+            };
+            end = e.span;
+            Some(Box::new(e))
+        } else {
+            None
+        };
+        Ok(Expr {
+            span: start.to(end),
+            kind: ExprKind::If {
+                cond: Box::new(cond),
+                then,
+                els,
+            },
+        })
+    }
+
+    pub fn expr(&mut self) -> Result<Expr, Error> {
+        self.nested(Self::expr_inner)
+    }
+
+    fn expr_inner(&mut self) -> Result<Expr, Error> {
+        // x = v, x += v. 赋值表达式本身的值是 ().
+        if let Tok::Ident(name) = self.peek().clone() {
+            let op = match self.peek_at(1) {
+                Tok::Eq => Some(None),
+                Tok::PlusEq => Some(Some(BinOp::Add)),
+                Tok::MinusEq => Some(Some(BinOp::Sub)),
+                Tok::StarEq => Some(Some(BinOp::Mul)),
+                Tok::SlashEq => Some(Some(BinOp::Div)),
+                Tok::PercentEq => Some(Some(BinOp::Rem)),
+                _ => None,
+            };
+            if let Some(op) = op {
+                let name_span = self.bump().span;
+                self.bump();
+                let value = self.expr()?;
+                return Ok(Expr {
+                    span: name_span.to(value.span),
+                    kind: ExprKind::Assign {
+                        name,
+                        name_span,
+                        op,
+                        value: Box::new(value),
+                    },
+                });
+            }
+        }
+        self.binary(0)
+    }
+
+    // precedence climbing
+    fn binary(&mut self, min_prec: u8) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = BinOp::from_tok(self.peek()) {
+            let prec = op.precedence();
+            if prec <= min_prec {
+                break;
+            }
+            let op_span = self.bump().span;
+            let rhs = self.binary(prec)?;
+            // a < b < c 在 Rust 里是语法错误
+            if op.is_comparison() {
+                if let Some(next) = BinOp::from_tok(self.peek()) {
+                    if next.is_comparison() {
+                        return Err(parse_error(
+                            "comparison operators cannot be chained",
+                            op_span.to(self.span()),
+                        ));
+                    }
+                }
+            }
+            lhs = Expr {
+                span: lhs.span.to(rhs.span),
+                kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        let op = match self.peek() {
+            Tok::Minus => UnOp::Neg,
+            Tok::Bang => UnOp::Not,
+            _ => return self.primary(),
+        };
+        let start = self.bump().span;
+        let operand = self.nested(Self::unary)?;
+        Ok(Expr {
+            span: start.to(operand.span),
+            kind: ExprKind::Unary(op, Box::new(operand)),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let span = self.span();
+        let kind = match self.peek().clone() {
+            Tok::Int(v) => {
+                self.bump();
+                ExprKind::Int(v)
+            }
+            Tok::Float(v) => {
+                self.bump();
+                ExprKind::Float(v)
+            }
+            Tok::True => {
+                self.bump();
+                ExprKind::Bool(true)
+            }
+            Tok::False => {
+                self.bump();
+                ExprKind::Bool(false)
+            }
+            Tok::Ident(name) => {
+                self.bump();
+                ExprKind::Var(name)
+            }
+            Tok::LParen => {
+                self.bump();
+                if self.eat(&Tok::RParen) {
+                    ExprKind::Unit
+                } else {
+                    let inner = self.expr()?;
+                    let close = self.expect(&Tok::RParen)?;
+                    return Ok(Expr {
+                        span: span.to(close),
+                        kind: inner.kind,
+                    });
+                }
+            }
+            Tok::LBrace | Tok::If | Tok::Loop | Tok::While => return self.block_like(),
+            Tok::Break => {
+                self.bump();
+                // break 后面紧跟 ; } ) 或文件结尾时不带值
+                if matches!(
+                    self.peek(),
+                    Tok::Semi | Tok::RBrace | Tok::RParen | Tok::Eof
+                ) {
+                    ExprKind::Break(None)
+                } else {
+                    let value = self.expr()?;
+                    return Ok(Expr {
+                        span: span.to(value.span),
+                        kind: ExprKind::Break(Some(Box::new(value))),
+                    });
+                }
+            }
+            Tok::Continue => {
+                self.bump();
+                ExprKind::Continue
+            }
+            other => {
+                return Err(parse_error(
+                    format!("expected expression, found {}", other.describe()),
+                    span,
+                ))
+            }
+        };
+        Ok(Expr {
+            span: span.to(self.prev_span()),
+            kind,
+        })
+    }
+}
@@ -0,0 +1,170 @@
+// 浮点数比较工具.
+//
+// scalar() 里演示了 -0.0 == 0.0, 1.0 / 0.0 = inf, 以及用 f32::EPSILON 做比较.
+// 但 EPSILON 只是 1.0 附近相邻两个浮点数的间隔, 数值越大间隔越大, 直接拿它做容差并不可靠.
+// 这里提供:
+// 1. ULP(unit in the last place) 距离
+// 2. 绝对容差 + 相对容差的 approx_eq
+// 3. 基于 total_cmp 的 TotalF64, 实现了 Ord 和 Hash, 可以排序, 也可以作为 HashMap/BTreeMap 的 key
+// 4. 对 NaN 有明确语义的 min/max/sort
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+// 把 f64 的位模式映射成一个单调递增的 i64:
+// 正数的位模式本身就是单调递增的, 负数的位模式是单调递减的, 翻转一下即可.
+// 这样 -0.0 和 0.0 映射到 -1 和 0 的相邻位置上 (与 total_cmp 的顺序一致).
+fn ordered_bits(x: f64) -> i64 {
+    let bits = x.to_bits() as i64;
+    if bits < 0 {
+        bits ^ i64::MAX
+    } else {
+        bits
+    }
+}
+
+// 两个浮点数之间相隔多少个可表示的浮点数.
+// 任一参数是 NaN 时没有意义, 返回 None.
+// -0.0 和 0.0 的距离被视为 0, 因为它们 == 相等.
+pub fn ulp_distance(a: f64, b: f64) -> Option<u64> {
+    if a.is_nan() || b.is_nan() {
+        return None;
+    }
+    if a == b {
+        return Some(0);
+    }
+    let (a, b) = (ordered_bits(a), ordered_bits(b));
+    // 跨过 0 时, -0.0(-1) 和 0.0(0) 之间多算了一格, 扣掉.
+    let d = (a as i128 - b as i128).unsigned_abs();
+    let d = if (a < 0) != (b < 0) { d - 1 } else { d };
+    Some(d as u64)
+}
+
+pub fn ulp_eq(a: f64, b: f64, max_ulps: u64) -> bool {
+    matches!(ulp_distance(a, b), Some(d) if d <= max_ulps)
+}
+
+// |a - b| <= max(abs_tol, rel_tol * max(|a|, |b|))
+//
+// 只用相对容差时, 接近 0 的数永远比较不相等 (比如 0.1 * 3.0 - 0.3 和 0.0),
+// 所以同时给一个绝对容差兜底. 这和 Python 的 math.isclose 是同一个语义.
+pub fn approx_eq(a: f64, b: f64, abs_tol: f64, rel_tol: f64) -> bool {
+    if a == b {
+        // 包括两个同号的 inf
+        return true;
+    }
+    if !a.is_finite() || !b.is_finite() {
+        // NaN 与任何数都不相等, inf 只和自己相等
+        return false;
+    }
+    let diff = (a - b).abs();
+    diff <= abs_tol || diff <= rel_tol * a.abs().max(b.abs())
+}
+
+// f64 只实现了 PartialOrd 和 PartialEq, 因为 NaN != NaN.
+// 所以 Vec<f64> 不能 sort(), f64 也不能作为 HashMap 的 key.
+//
+// f64::total_cmp 按 IEEE 754 的 totalOrder 给出了一个全序:
+// -NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN
+// 在它之上包一层就可以实现 Eq/Ord/Hash.
+// 注意在这个全序下 -0.0 != 0.0, 且 NaN == NaN (位模式相同时).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TotalF64(pub f64);
+
+impl TotalF64 {
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl PartialEq for TotalF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalF64 {}
+
+impl PartialOrd for TotalF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// total_cmp 相等 <=> 位模式相等, 所以按位模式 hash 与 Eq 一致.
+impl Hash for TotalF64 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl From<f64> for TotalF64 {
+    fn from(value: f64) -> Self {
+        TotalF64(value)
+    }
+}
+
+impl From<TotalF64> for f64 {
+    fn from(value: TotalF64) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for TotalF64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// 忽略 NaN 的最小值/最大值. 没有非 NaN 元素时返回 None.
+// (f64::min/f64::max 对单个 NaN 也是忽略的, 这里推广到迭代器.)
+pub fn min_ignore_nan<I: IntoIterator<Item = f64>>(iter: I) -> Option<f64> {
+    iter.into_iter().filter(|x| !x.is_nan()).reduce(f64::min)
+}
+
+pub fn max_ignore_nan<I: IntoIterator<Item = f64>>(iter: I) -> Option<f64> {
+    iter.into_iter().filter(|x| !x.is_nan()).reduce(f64::max)
+}
+
+// 只要出现 NaN 就返回 NaN 的最小值/最大值 (类似 SQL 里 NULL 的传播).
+pub fn min_propagate_nan<I: IntoIterator<Item = f64>>(iter: I) -> Option<f64> {
+    iter.into_iter().reduce(|a, b| {
+        if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else {
+            a.min(b)
+        }
+    })
+}
+
+pub fn max_propagate_nan<I: IntoIterator<Item = f64>>(iter: I) -> Option<f64> {
+    iter.into_iter().reduce(|a, b| {
+        if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else {
+            a.max(b)
+        }
+    })
+}
+
+// 按 total_cmp 排序. 注意符号位为 1 的 NaN 会排在最前面.
+pub fn sort_total(v: &mut [f64]) {
+    v.sort_by(f64::total_cmp);
+}
+
+// 升序排序, 不管 NaN 的符号位如何都放到最后; 其余元素按 total_cmp 排序 (-0.0 在 0.0 前面).
+pub fn sort_nan_last(v: &mut [f64]) {
+    v.sort_by(|a, b| match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.total_cmp(b),
+    });
+}
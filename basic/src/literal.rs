@@ -0,0 +1,516 @@
+// Rust 字面量解析器.
+//
+// scalar() 里列出了各种整型/浮点/字符字面量:
+// 98_222, 0xff, 0o77, 0b1111_0000, b'A', 2.0, 3.6_f32
+// 这里把这些字面量文本解析成带类型的值, 规则尽量与 rustc 一致:
+// 1. 没有后缀时整型默认是 i32, 浮点默认是 f64
+// 2. 类型后缀: i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64, 前面可以有 _
+// 3. 0x 字面量里 f 是合法数字, 所以 0xff_f32 是整数 0xfff32 而不是 f32
+// 4. 0b/0o/0x 不能是浮点数
+// 5. 超出类型范围时报错 (rustc 里是 deny-by-default 的 overflowing_literals lint)
+//
+// 为了方便, 数字字面量前面允许有一个 '-', 这样 -128i8 这种写法可以被判定为在范围内.
+// (严格来说 Rust 的字面量没有符号, - 是一元运算符.)
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    Isize(isize),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    Usize(usize),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    // b'A' 的类型是 u8, 这里单独区分出来只是为了 Display 时能还原成 b'A' 的样子
+    Byte(u8),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::I8(_) => "i8",
+            Value::I16(_) => "i16",
+            Value::I32(_) => "i32",
+            Value::I64(_) => "i64",
+            Value::I128(_) => "i128",
+            Value::Isize(_) => "isize",
+            Value::U8(_) | Value::Byte(_) => "u8",
+            Value::U16(_) => "u16",
+            Value::U32(_) => "u32",
+            Value::U64(_) => "u64",
+            Value::U128(_) => "u128",
+            Value::Usize(_) => "usize",
+            Value::F32(_) => "f32",
+            Value::F64(_) => "f64",
+            Value::Char(_) => "char",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I8(v) => write!(f, "{}", v),
+            Value::I16(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::I128(v) => write!(f, "{}", v),
+            Value::Isize(v) => write!(f, "{}", v),
+            Value::U8(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::U128(v) => write!(f, "{}", v),
+            Value::Usize(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{:?}", v),
+            Value::F64(v) => write!(f, "{:?}", v),
+            Value::Char(c) => write!(f, "{:?}", c),
+            Value::Byte(b) => write!(f, "b'{}'", std::ascii::escape_default(*b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralErrorKind {
+    Empty,
+    // 0x, 0b_ 这种没有任何数字的
+    NoDigits,
+    InvalidDigit { digit: char, radix: u32 },
+    InvalidSuffix(String),
+    // 0b1.0, 0o7f32
+    FloatWithRadix { radix: u32 },
+    OutOfRange { ty: &'static str },
+    // -'a', -b'a', -1u8
+    NegativeNotAllowed { ty: &'static str },
+    MissingExponentDigits,
+    UnterminatedQuote,
+    EmptyChar,
+    TooManyChars,
+    InvalidEscape(String),
+    // \x80 在 char 字面量里不允许
+    OutOfRangeHexEscape,
+    InvalidUnicodeEscape(String),
+    // b'中'
+    NonAsciiInByte(char),
+    // '\u{..}' 不能出现在 byte 字面量里
+    UnicodeEscapeInByte,
+    // ''' 和 '\t' 的原文 tab: 必须转义
+    MustBeEscaped(char),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiteralError {
+    // 出错位置, 是输入文本中的字节偏移
+    pub pos: usize,
+    pub kind: LiteralErrorKind,
+}
+
+impl fmt::Display for LiteralError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at byte {}: ", self.pos)?;
+        match &self.kind {
+            LiteralErrorKind::Empty => write!(f, "empty literal"),
+            LiteralErrorKind::NoDigits => write!(f, "no valid digits found for number"),
+            LiteralErrorKind::InvalidDigit { digit, radix } => {
+                write!(f, "invalid digit `{}` for a base {} literal", digit, radix)
+            }
+            LiteralErrorKind::InvalidSuffix(s) => write!(f, "invalid suffix `{}`", s),
+            LiteralErrorKind::FloatWithRadix { radix } => {
+                let name = match radix {
+                    2 => "binary",
+                    8 => "octal",
+                    _ => "hexadecimal",
+                };
+                write!(f, "{} float literal is not supported", name)
+            }
+            LiteralErrorKind::OutOfRange { ty } => write!(f, "literal out of range for `{}`", ty),
+            LiteralErrorKind::NegativeNotAllowed { ty } => {
+                write!(f, "cannot apply unary operator `-` to type `{}`", ty)
+            }
+            LiteralErrorKind::MissingExponentDigits => {
+                write!(f, "expected at least one digit in exponent")
+            }
+            LiteralErrorKind::UnterminatedQuote => write!(f, "unterminated character literal"),
+            LiteralErrorKind::EmptyChar => write!(f, "empty character literal"),
+            LiteralErrorKind::TooManyChars => {
+                write!(f, "character literal may only contain one codepoint")
+            }
+            LiteralErrorKind::InvalidEscape(s) => write!(f, "unknown character escape: `{}`", s),
+            LiteralErrorKind::OutOfRangeHexEscape => {
+                write!(
+                    f,
+                    "out of range hex escape, must be a character in the range [\\x00-\\x7f]"
+                )
+            }
+            LiteralErrorKind::InvalidUnicodeEscape(s) => {
+                write!(f, "invalid unicode character escape: {}", s)
+            }
+            LiteralErrorKind::NonAsciiInByte(c) => {
+                write!(f, "non-ASCII character `{}` in byte literal", c)
+            }
+            LiteralErrorKind::UnicodeEscapeInByte => write!(f, "unicode escape in byte literal"),
+            LiteralErrorKind::MustBeEscaped(c) => {
+                write!(f, "character `{}` must be escaped", c.escape_default())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LiteralError {}
+
+const SUFFIXES: [&str; 14] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64",
+];
+
+fn err<T>(pos: usize, kind: LiteralErrorKind) -> Result<T, LiteralError> {
+    Err(LiteralError { pos, kind })
+}
+
+pub fn parse(text: &str) -> Result<Value, LiteralError> {
+    if text.is_empty() {
+        return err(0, LiteralErrorKind::Empty);
+    }
+    let (negative, body, offset) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest, 1),
+        None => (false, text, 0),
+    };
+    let value = if body.starts_with("b'") {
+        Value::Byte(parse_quoted(body, offset + 2, true)? as u8)
+    } else if body.starts_with('\'') {
+        Value::Char(parse_quoted(body, offset + 1, false)?)
+    } else if body.is_empty() {
+        return err(offset, LiteralErrorKind::Empty);
+    } else {
+        return parse_number(body, offset, negative);
+    };
+    if negative {
+        return err(
+            0,
+            LiteralErrorKind::NegativeNotAllowed {
+                ty: value.type_name(),
+            },
+        );
+    }
+    Ok(value)
+}
+
+// 解析 'x' 或 b'x' 的引号部分. start 指向开头引号之后的第一个字节.
+fn parse_quoted(body: &str, start: usize, is_byte: bool) -> Result<char, LiteralError> {
+    let quote_len = if is_byte { 2 } else { 1 };
+    let inner = &body[quote_len..];
+    let end = match inner.rfind('\'') {
+        Some(end) if !inner[..end].is_empty() || inner.len() == 1 => end,
+        // 只有 ' 或 b' 一个引号
+        _ => return err(start + inner.len(), LiteralErrorKind::UnterminatedQuote),
+    };
+    if end + 1 != inner.len() {
+        // 'a'b 这种引号后面还有东西的
+        return err(
+            start + end + 1,
+            LiteralErrorKind::InvalidSuffix(inner[end + 1..].to_string()),
+        );
+    }
+    let inner = &inner[..end];
+    if inner.is_empty() {
+        return err(start, LiteralErrorKind::EmptyChar);
+    }
+    let (c, len) = if let Some(esc) = inner.strip_prefix('\\') {
+        let (c, len) = parse_escape(esc, start + 1, is_byte)?;
+        (c, len + 1)
+    } else {
+        let c = inner.chars().next().unwrap();
+        match c {
+            '\'' | '\n' | '\r' | '\t' => return err(start, LiteralErrorKind::MustBeEscaped(c)),
+            _ if is_byte && !c.is_ascii() => {
+                return err(start, LiteralErrorKind::NonAsciiInByte(c))
+            }
+            _ => (c, c.len_utf8()),
+        }
+    };
+    if len != inner.len() {
+        return err(start + len, LiteralErrorKind::TooManyChars);
+    }
+    Ok(c)
+}
+
+// 解析 \ 之后的转义序列, 返回字符和转义序列 (不含 \) 的字节长度.
+fn parse_escape(esc: &str, pos: usize, is_byte: bool) -> Result<(char, usize), LiteralError> {
+    let first = match esc.chars().next() {
+        Some(c) => c,
+        None => return err(pos, LiteralErrorKind::InvalidEscape(String::from("\\"))),
+    };
+    let simple = match first {
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        '\\' => Some('\\'),
+        '0' => Some('\0'),
+        '\'' => Some('\''),
+        '"' => Some('"'),
+        _ => None,
+    };
+    if let Some(c) = simple {
+        return Ok((c, 1));
+    }
+    match first {
+        'x' => {
+            let digits = esc.get(1..3).unwrap_or("");
+            let value = match u8::from_str_radix(digits, 16) {
+                Ok(v) if digits.len() == 2 && digits.bytes().all(|b| b.is_ascii_hexdigit()) => v,
+                _ => {
+                    return err(
+                        pos,
+                        LiteralErrorKind::InvalidEscape(format!("\\x{}", digits)),
+                    )
+                }
+            };
+            if !is_byte && value > 0x7f {
+                return err(pos, LiteralErrorKind::OutOfRangeHexEscape);
+            }
+            Ok((value as char, 3))
+        }
+        'u' => {
+            if is_byte {
+                return err(pos, LiteralErrorKind::UnicodeEscapeInByte);
+            }
+            let rest = &esc[1..];
+            let close = match (rest.starts_with('{'), rest.find('}')) {
+                (true, Some(close)) => close,
+                _ => {
+                    return err(
+                        pos,
+                        LiteralErrorKind::InvalidUnicodeEscape(String::from("expected `\\u{...}`")),
+                    )
+                }
+            };
+            let hex = &rest[1..close];
+            if hex.starts_with('_') {
+                return err(
+                    pos,
+                    LiteralErrorKind::InvalidUnicodeEscape(String::from(
+                        "invalid start of unicode escape: `_`",
+                    )),
+                );
+            }
+            let digits: String = hex.chars().filter(|&c| c != '_').collect();
+            if digits.is_empty() {
+                return err(
+                    pos,
+                    LiteralErrorKind::InvalidUnicodeEscape(String::from("empty unicode escape")),
+                );
+            }
+            if digits.len() > 6 {
+                return err(
+                    pos,
+                    LiteralErrorKind::InvalidUnicodeEscape(String::from(
+                        "must have at most 6 hex digits",
+                    )),
+                );
+            }
+            let code = match u32::from_str_radix(&digits, 16) {
+                Ok(code) => code,
+                Err(_) => {
+                    return err(
+                        pos,
+                        LiteralErrorKind::InvalidUnicodeEscape(format!(
+                            "invalid character in `{}`",
+                            hex
+                        )),
+                    )
+                }
+            };
+            match char::from_u32(code) {
+                Some(c) => Ok((c, close + 2)),
+                None if (0xD800..=0xDFFF).contains(&code) => err(
+                    pos,
+                    LiteralErrorKind::InvalidUnicodeEscape(String::from("must not be a surrogate")),
+                ),
+                None => err(
+                    pos,
+                    LiteralErrorKind::InvalidUnicodeEscape(String::from("must be at most 10FFFF")),
+                ),
+            }
+        }
+        _ => err(pos, LiteralErrorKind::InvalidEscape(format!("\\{}", first))),
+    }
+}
+
+fn parse_number(body: &str, offset: usize, negative: bool) -> Result<Value, LiteralError> {
+    let bytes = body.as_bytes();
+    let (radix, mut i) = match body.get(..2) {
+        Some("0x") => (16, 2),
+        Some("0o") => (8, 2),
+        Some("0b") => (2, 2),
+        _ => (10, 0),
+    };
+    if !bytes[0].is_ascii_digit() {
+        return err(
+            offset,
+            LiteralErrorKind::InvalidDigit {
+                digit: body.chars().next().unwrap(),
+                radix,
+            },
+        );
+    }
+
+    // 整数部分. 对于 0b/0o, 遇到 0-9 但超出进制的数字报错, 遇到字母则认为后缀开始了.
+    let int_start = i;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'_' || (b as char).is_digit(radix) {
+            i += 1;
+        } else if b.is_ascii_digit() {
+            return err(
+                offset + i,
+                LiteralErrorKind::InvalidDigit {
+                    digit: b as char,
+                    radix,
+                },
+            );
+        } else {
+            break;
+        }
+    }
+    let int_digits: String = body[int_start..i].chars().filter(|&c| c != '_').collect();
+    if int_digits.is_empty() {
+        return err(offset + int_start, LiteralErrorKind::NoDigits);
+    }
+
+    // 小数部分和指数部分只对十进制有效.
+    // 2. 是合法的浮点字面量, 但 2.f32 和 2.foo 是方法调用/字段访问, 不是字面量.
+    let mut is_float = false;
+    let mut float_end = i;
+    if i < bytes.len() && bytes[i] == b'.' {
+        let next = bytes.get(i + 1).copied();
+        if radix != 10 {
+            return err(offset + i, LiteralErrorKind::FloatWithRadix { radix });
+        }
+        if next.is_none_or(|b| b.is_ascii_digit()) {
+            is_float = true;
+            i += 1;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+                i += 1;
+            }
+            float_end = i;
+        }
+    }
+    if radix == 10 && i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        is_float = true;
+        i += 1;
+        if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+            i += 1;
+        }
+        let exp_start = i;
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+            i += 1;
+        }
+        if !body[exp_start..i].bytes().any(|b| b.is_ascii_digit()) {
+            return err(offset + exp_start, LiteralErrorKind::MissingExponentDigits);
+        }
+        float_end = i;
+    }
+
+    let suffix = &body[i..];
+    let ty = match suffix {
+        "" => None,
+        _ => match SUFFIXES.iter().find(|&&s| s == suffix) {
+            Some(&ty) => Some(ty),
+            None => {
+                return err(
+                    offset + i,
+                    LiteralErrorKind::InvalidSuffix(suffix.to_string()),
+                )
+            }
+        },
+    };
+
+    let float_ty = match ty {
+        Some("f32") => Some("f32"),
+        Some("f64") => Some("f64"),
+        None if is_float => Some("f64"),
+        Some(int_ty) if is_float => {
+            return err(
+                offset + i,
+                LiteralErrorKind::InvalidSuffix(int_ty.to_string()),
+            )
+        }
+        _ => None,
+    };
+    if let Some(float_ty) = float_ty {
+        if radix != 10 {
+            return err(offset + i, LiteralErrorKind::FloatWithRadix { radix });
+        }
+        let end = if is_float { float_end } else { i };
+        let mut digits: String = body[..end].chars().filter(|&c| c != '_').collect();
+        if negative {
+            digits.insert(0, '-');
+        }
+        return if float_ty == "f32" {
+            let v: f32 = digits.parse().unwrap();
+            if v.is_infinite() {
+                return err(offset, LiteralErrorKind::OutOfRange { ty: "f32" });
+            }
+            Ok(Value::F32(v))
+        } else {
+            let v: f64 = digits.parse().unwrap();
+            if v.is_infinite() {
+                return err(offset, LiteralErrorKind::OutOfRange { ty: "f64" });
+            }
+            Ok(Value::F64(v))
+        };
+    }
+
+    let ty = ty.unwrap_or("i32");
+    let out_of_range = || LiteralError {
+        pos: offset,
+        kind: LiteralErrorKind::OutOfRange { ty },
+    };
+    let magnitude = match u128::from_str_radix(&int_digits, radix) {
+        Ok(m) => m,
+        Err(_) => return Err(out_of_range()),
+    };
+    if negative && ty.starts_with('u') {
+        return err(0, LiteralErrorKind::NegativeNotAllowed { ty });
+    }
+    // 与 rustc 一样, 0xff_i8 这种按位模式解释的写法也算越界
+    let signed = |bits: u32| -> Result<i128, LiteralError> {
+        let limit = 1u128 << (bits - 1);
+        if (negative && magnitude > limit) || (!negative && magnitude >= limit) {
+            return Err(out_of_range());
+        }
+        let m = magnitude as i128;
+        Ok(if negative { m.wrapping_neg() } else { m })
+    };
+    let unsigned = |bits: u32| -> Result<u128, LiteralError> {
+        if bits < 128 && magnitude >> bits != 0 {
+            return Err(out_of_range());
+        }
+        Ok(magnitude)
+    };
+    Ok(match ty {
+        "i8" => Value::I8(signed(8)? as i8),
+        "i16" => Value::I16(signed(16)? as i16),
+        "i32" => Value::I32(signed(32)? as i32),
+        "i64" => Value::I64(signed(64)? as i64),
+        "i128" => Value::I128(signed(128)?),
+        "isize" => Value::Isize(signed(isize::BITS)? as isize),
+        "u8" => Value::U8(unsigned(8)? as u8),
+        "u16" => Value::U16(unsigned(16)? as u16),
+        "u32" => Value::U32(unsigned(32)? as u32),
+        "u64" => Value::U64(unsigned(64)? as u64),
+        "u128" => Value::U128(unsigned(128)?),
+        "usize" => Value::Usize(unsigned(usize::BITS)? as usize),
+        _ => unreachable!(),
+    })
+}
@@ -1,3 +1,11 @@
+mod bitset;
+mod expr;
+mod float;
+mod literal;
+mod radix;
+mod ranged;
+mod unicode;
+
 //https://stackoverflow.com/questions/21747136/how-do-i-print-in-rust-the-type-of-a-variable
 fn get_type<T>(_: &T) -> &'static str {
     std::any::type_name::<T>()
@@ -135,6 +143,535 @@ fn scalar() {
     println!("v: {:?}", v);
 }
 
+fn use_float() {
+    use float::{approx_eq, sort_nan_last, ulp_distance, TotalF64};
+    use std::collections::{BTreeSet, HashMap};
+
+    // 0.1 * 3.0 与 0.3 只差 1 个 ULP, 但 == 判断不相等
+    let a = 0.1 * 3.0;
+    assert!(a != 0.3);
+    assert_eq!(ulp_distance(a, 0.3), Some(1));
+    assert!(approx_eq(a, 0.3, 0.0, 1e-12));
+
+    // f32::EPSILON 式的固定容差在大数上失效, 相对容差不会
+    assert!(approx_eq(1e10 + 1e-3, 1e10, f64::EPSILON, 1e-12));
+    // 接近 0 时相对容差没有意义, 靠绝对容差
+    assert!(approx_eq(a - 0.3, 0.0, 1e-12, 1e-12));
+
+    // -0.0 == 0.0, 它们的 ULP 距离是 0; 但在 total_cmp 下 -0.0 < 0.0
+    assert_eq!(ulp_distance(-0.0, 0.0), Some(0));
+    assert!(TotalF64(-0.0) < TotalF64(0.0));
+    assert_eq!(ulp_distance(f64::NAN, 0.0), None);
+    assert!(!approx_eq(f64::NAN, f64::NAN, 1.0, 1.0));
+    assert!(approx_eq(1.0 / 0.0, f64::INFINITY, 0.0, 0.0));
+
+    // //the trait bound `f64: Ord` is not satisfied [E0277]
+    // let mut v = vec![1.0, 0.5];
+    // v.sort();
+    let mut v: Vec<TotalF64> = [2.5, f64::NAN, -1.0, 0.0, -0.0]
+        .into_iter()
+        .map(TotalF64)
+        .collect();
+    v.sort();
+    println!("sorted: {:?}", v);
+    assert!(v.last().unwrap().get().is_nan());
+
+    let mut v = vec![2.5, -f64::NAN, -1.0, f64::NAN, 0.0];
+    sort_nan_last(&mut v);
+    assert_eq!(&v[..3], &[-1.0, 0.0, 2.5]);
+    assert!(v[3].is_nan() && v[4].is_nan());
+
+    // 浮点数作为 map 的 key
+    let mut counts: HashMap<TotalF64, u32> = HashMap::new();
+    for x in [0.5, 0.5, 1.0 / 0.0, f64::NAN, f64::NAN] {
+        *counts.entry(TotalF64(x)).or_insert(0) += 1;
+    }
+    assert_eq!(counts[&TotalF64(0.5)], 2);
+    assert_eq!(counts[&TotalF64(f64::NAN)], 2);
+    let set: BTreeSet<TotalF64> = [3.0, 1.0, 2.0, 1.0].into_iter().map(TotalF64).collect();
+    assert_eq!(set.len(), 3);
+
+    assert_eq!(float::min_ignore_nan([3.0, f64::NAN, 1.0]), Some(1.0));
+    assert_eq!(float::max_ignore_nan([3.0, f64::NAN, 1.0]), Some(3.0));
+    assert!(float::min_propagate_nan([3.0, f64::NAN, 1.0])
+        .unwrap()
+        .is_nan());
+    assert!(float::max_propagate_nan([3.0, f64::NAN, 1.0])
+        .unwrap()
+        .is_nan());
+    assert_eq!(float::min_ignore_nan([f64::NAN]), None);
+
+    let mut v = vec![1.0, -f64::NAN, -2.0];
+    float::sort_total(&mut v);
+    assert!(v[0].is_nan());
+    assert!(float::ulp_eq(a, 0.3, 4));
+    assert_eq!(f64::from(TotalF64::from(1.5)), 1.5);
+    println!("TotalF64: {}", TotalF64(1.5));
+}
+
+fn use_literal() {
+    use literal::{parse, LiteralErrorKind, Value};
+
+    // scalar() 里的字面量, 没有后缀时整型是 i32, 浮点是 f64
+    assert_eq!(parse("98_222"), Ok(Value::I32(98222)));
+    assert_eq!(parse("0xff"), Ok(Value::I32(255)));
+    assert_eq!(parse("0o77"), Ok(Value::I32(63)));
+    assert_eq!(parse("0b1111_0000"), Ok(Value::I32(240)));
+    assert_eq!(parse("b'A'"), Ok(Value::Byte(65)));
+    assert_eq!(parse("2.0"), Ok(Value::F64(2.0)));
+    assert_eq!(parse("3.6_f32"), Ok(Value::F32(3.6)));
+
+    assert_eq!(parse("2."), Ok(Value::F64(2.0)));
+    assert_eq!(parse("1e3"), Ok(Value::F64(1000.0)));
+    assert_eq!(parse("2f32"), Ok(Value::F32(2.0)));
+    assert_eq!(parse("255u8"), Ok(Value::U8(255)));
+    assert_eq!(parse("-128i8"), Ok(Value::I8(-128)));
+    assert_eq!(parse("1_u64"), Ok(Value::U64(1)));
+    // f 是十六进制数字, 所以这不是 f32
+    assert_eq!(parse("0x1f32"), Ok(Value::I32(0x1f32)));
+    assert_eq!(
+        parse("0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffffu128"),
+        Ok(Value::U128(u128::MAX))
+    );
+
+    assert_eq!(parse("'中'"), Ok(Value::Char('中')));
+    assert_eq!(parse("'\\n'"), Ok(Value::Char('\n')));
+    assert_eq!(parse("'\\''"), Ok(Value::Char('\'')));
+    assert_eq!(parse("'\\u{1F63B}'"), Ok(Value::Char('😻')));
+    assert_eq!(parse("'\\u{fe0f}'"), Ok(Value::Char('\u{fe0f}')));
+    assert_eq!(parse("b'\\xff'"), Ok(Value::Byte(0xff)));
+
+    let cases = [
+        "256u8",
+        "2147483648",
+        "-129i8",
+        "-1u32",
+        "1e40f32",
+        "0b102",
+        "0o8",
+        "0x",
+        "0b1.0",
+        "0o7f32",
+        "1.5i32",
+        "12abc",
+        "1e",
+        "''",
+        "'ab'",
+        "'\\x80'",
+        "'\\q'",
+        "'\\u{D800}'",
+        "'\\u{110000}'",
+        "b'中'",
+        "b'\\u{41}'",
+        "'a",
+        "-'a'",
+    ];
+    for case in cases {
+        let e = parse(case).unwrap_err();
+        println!("{:<20} error {}", case, e);
+    }
+    assert_eq!(
+        parse("256u8").unwrap_err().kind,
+        LiteralErrorKind::OutOfRange { ty: "u8" }
+    );
+    assert_eq!(
+        parse("0b102").unwrap_err().kind,
+        LiteralErrorKind::InvalidDigit {
+            digit: '2',
+            radix: 2
+        }
+    );
+    assert_eq!(parse("0b102").unwrap_err().pos, 4);
+
+    for text in ["42", "0xffu8", "b'A'", "3.6_f32", "'😻'"] {
+        let v = parse(text).unwrap();
+        println!("{:<10} => {} : {}", text, v, v.type_name());
+    }
+}
+
+fn use_unicode() {
+    // s.len(): 12, v.len(): 8, 但只有 7 个字素簇: ❤ 和 \u{fe0f} 合起来才是一个 "字符"
+    let a = unicode::analyze("love: ❤️");
+    assert_eq!(a.byte_len, 12);
+    assert_eq!(a.char_count, 8);
+    assert_eq!(a.grapheme_count(), 7);
+    assert_eq!(a.graphemes[6], "❤️");
+    let vs: Vec<char> = a.invisible_chars().map(|c| c.ch).collect();
+    assert_eq!(vs, ['\u{fe0f}']);
+    print!("{}", a);
+
+    // e + 组合重音符 = é, 2 个 char, 1 个字素簇, 显示宽度 1
+    let a = unicode::analyze("e\u{301}");
+    assert_eq!(
+        (a.char_count, a.grapheme_count(), a.display_width),
+        (2, 1, 1)
+    );
+    assert_eq!(a.combining_chars().count(), 1);
+
+    // 中日韩文字占两列
+    let a = unicode::analyze("中国");
+    assert_eq!((a.byte_len, a.char_count, a.display_width), (6, 2, 4));
+    assert_eq!(a.chars[1].utf8(), vec![0xE5, 0x9B, 0xBD]);
+    assert_eq!(a.chars[1].code_point(), 0x56FD);
+
+    // 👨‍👩‍👧 由 3 个 emoji 和 2 个零宽连接符(ZWJ)组成
+    let a = unicode::analyze("👨\u{200d}👩\u{200d}👧");
+    assert_eq!((a.char_count, a.grapheme_count()), (5, 1));
+    assert_eq!(a.invisible_chars().count(), 2);
+    print!("{}", a);
+}
+
+fn use_radix() {
+    use radix::{format, format_twos_complement, from_radix, parse, to_radix, Format};
+    use rand::Rng;
+
+    assert_eq!(to_radix(0b1111_0000, 2).unwrap(), "11110000");
+    assert_eq!(
+        format(0b1111_0000, &Format::new(2).group(4)).unwrap(),
+        "1111_0000"
+    );
+    assert_eq!(to_radix(255u8, 16).unwrap(), "ff");
+    assert_eq!(to_radix(-255i32, 16).unwrap(), "-ff");
+    assert_eq!(to_radix(35, 36).unwrap(), "z");
+    assert_eq!(
+        format(1_000_000, &Format::new(10).group(3).separator(',')).unwrap(),
+        "1,000,000"
+    );
+    assert_eq!(
+        format(
+            u128::MAX,
+            &Format::new(16).group(8).uppercase(true).prefix(true)
+        )
+        .unwrap(),
+        "0xFFFFFFFF_FFFFFFFF_FFFFFFFF_FFFFFFFF"
+    );
+    assert_eq!(
+        to_radix(i128::MIN, 36).unwrap(),
+        "-7ksyyizzkutudzbv8aqztecjk"
+    );
+    assert!(to_radix(1, 37).is_err());
+
+    assert_eq!(parse::<i32>("1111_0000", 2), Ok(240));
+    assert_eq!(parse::<u8>("0xff", 16), Ok(255));
+    assert_eq!(parse::<i8>("-80", 16), Ok(-128));
+    assert!(parse::<i8>("80", 16).is_err());
+    assert!(parse::<u8>("-1", 10).is_err());
+    assert!(parse::<u32>("12a", 10).is_err());
+    assert_eq!(from_radix::<i64>("zz", 36), Ok(1295));
+
+    // 与 format!("{:b}", -1i8) 一样的补码视图, 但可以选择位宽和进制
+    let fmt = Format::new(2).group(4);
+    assert_eq!(format!("{:b}", -1i8), "11111111");
+    assert_eq!(format_twos_complement(-1i8, 8, &fmt).unwrap(), "1111_1111");
+    assert_eq!(
+        format_twos_complement(-42, 12, &fmt).unwrap(),
+        "1111_1101_0110"
+    );
+    assert_eq!(format_twos_complement(5u8, 8, &fmt).unwrap(), "0000_0101");
+    assert_eq!(
+        format_twos_complement(-1i64, 16, &Format::new(16)).unwrap(),
+        "ffff"
+    );
+    assert!(format_twos_complement(128, 8, &fmt).is_err());
+    assert!(format_twos_complement(-129, 8, &fmt).is_err());
+    assert_eq!(radix::twos_complement(-128i16, 8), Ok(0x80));
+    assert_eq!(radix::from_twos_complement(0x80, 8), Ok(-128));
+    assert_eq!(radix::from_twos_complement(0xd6, 8), Ok(-42));
+
+    // round-trip 性质测试: 任意类型, 任意进制, 任意分组, format 之后再 parse 得到原值
+    let mut rng = rand::thread_rng();
+    macro_rules! round_trip {
+        ($($t:ty),*) => {$(
+            for _ in 0..2000 {
+                // 一半的样本取很小的值, 另一半覆盖整个取值范围
+                let raw: u128 = rng.gen();
+                let v = if rng.gen() { raw as $t } else { (raw % 100) as $t };
+                let radix = rng.gen_range(2..=36);
+                let fmt = Format::new(radix)
+                    .group(rng.gen_range(0..5))
+                    .uppercase(rng.gen())
+                    .prefix(rng.gen());
+                let s = format(v, &fmt).unwrap();
+                assert_eq!(parse::<$t>(&s, radix), Ok(v), "{} in base {}", s, radix);
+
+                let bits = <$t>::BITS;
+                let raw = radix::twos_complement(v, bits).unwrap();
+                assert_eq!(raw, v as u128 & (u128::MAX >> (128 - bits)));
+                if <$t as radix::Integer>::SIGNED {
+                    assert_eq!(radix::from_twos_complement(raw, bits), Ok(v as i128));
+                }
+            }
+        )*};
+    }
+    round_trip!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+    println!("radix round trip ok");
+}
+
+fn use_bitset() {
+    use bitset::BitSet;
+
+    // 0b1111_0000 的第 4..8 位是 1
+    let a: BitSet = (4..8).collect();
+    assert_eq!(format!("{:b}", a), format!("{:b}", 0b1111_0000));
+    let b: BitSet = [0, 2, 4, 6, 200].into_iter().collect();
+    println!("a: {:?}, b: {:?}", a, b);
+
+    assert_eq!(&a | &b, [0, 2, 4, 5, 6, 7, 200].into_iter().collect());
+    assert_eq!(&a & &b, [4, 6].into_iter().collect());
+    assert_eq!(&a - &b, [5, 7].into_iter().collect());
+    assert_eq!(&a ^ &b, [0, 2, 5, 7, 200].into_iter().collect());
+    assert!((&a & &b).is_subset(&a));
+    assert!((&a - &b).is_disjoint(&b));
+
+    let mut c = BitSet::new();
+    assert!(c.insert(1000));
+    assert!(!c.insert(1000));
+    assert!(c.test(1000) && !c.test(999) && !c.test(100_000));
+    c.toggle(3);
+    c.clear(1000);
+    assert_eq!(c.iter().collect::<Vec<_>>(), vec![3]);
+    c.clear_all();
+    assert!(c.is_empty());
+
+    // rank/select
+    let d: BitSet = [1, 3, 64, 65, 130].into_iter().collect();
+    assert_eq!(d.len(), 5);
+    assert_eq!(d.rank(64), 2);
+    assert_eq!(d.rank(131), 5);
+    assert_eq!(d.select(0), Some(1));
+    assert_eq!(d.select(3), Some(65));
+    assert_eq!(d.select(5), None);
+    for k in 0..d.len() {
+        assert_eq!(d.rank(d.select(k).unwrap()), k);
+    }
+    assert_eq!((d.min(), d.max()), (Some(1), Some(130)));
+
+    // 固定容量
+    let mut e = BitSet::fixed(10);
+    e.set(9);
+    e |= &[0, 1].into_iter().collect();
+    e &= &d;
+    assert_eq!(e.iter().collect::<Vec<_>>(), vec![1]);
+    assert!(e.is_fixed() && e.capacity() == 10);
+
+    // 两个固定容量的集合 word 数相同, 容量不同: other 的元素也必须在 e 的容量之内
+    let mut wide = BitSet::fixed(64);
+    wide.set(5);
+    e |= &wide;
+    assert_eq!(e.iter().collect::<Vec<_>>(), vec![1, 5]);
+    wide.set(40);
+    let overflow = std::panic::catch_unwind(move || {
+        e ^= &wide;
+        e
+    });
+    assert!(overflow.is_err());
+
+    // //thread 'main' panicked at 'index out of bounds: the len is 10 but the index is 10'
+    // e.set(10);
+}
+
+// $ cargo run --release
+fn bench_bitset() {
+    use bitset::BitSet;
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    const N: usize = 1_000_000;
+    let evens = (0..N).step_by(2);
+    let threes = (0..N).step_by(3);
+
+    let start = Instant::now();
+    let a: BitSet = evens.clone().collect();
+    let b: BitSet = threes.clone().collect();
+    let hits = (0..N).filter(|&i| a.contains(i)).count();
+    let both = (&a & &b).len();
+    println!(
+        "BitSet:         {:?} (hits: {}, both: {})",
+        start.elapsed(),
+        hits,
+        both
+    );
+
+    let start = Instant::now();
+    let a: HashSet<usize> = evens.collect();
+    let b: HashSet<usize> = threes.collect();
+    let hits2 = (0..N).filter(|i| a.contains(i)).count();
+    let both2 = a.intersection(&b).count();
+    println!(
+        "HashSet<usize>: {:?} (hits: {}, both: {})",
+        start.elapsed(),
+        hits2,
+        both2
+    );
+
+    assert_eq!((hits, both), (hits2, both2));
+}
+
+fn use_ranged() {
+    use ranged::{RangeError, Ranged};
+
+    // guessing_game 里的 1..101
+    type Guess = Ranged<1, 100>;
+    let guess: Guess = " 42\n".parse().unwrap();
+    assert_eq!(guess.get(), 42);
+    for input in ["0", "101", "abc", "99999999999999999999"] {
+        println!("{:?}: {}", input, input.parse::<Guess>().unwrap_err());
+    }
+    assert_eq!(
+        "101".parse::<Guess>(),
+        Err(RangeError::TooLarge {
+            value: 101,
+            min: 1,
+            max: 100
+        })
+    );
+
+    // use_never() 里的 0..=3 => i, _ => panic!
+    type Small = Ranged<0, 3>;
+    assert!(Small::new(2).is_ok());
+    assert!(Small::try_from(4).is_err());
+    assert_eq!(
+        Small::all().map(i64::from).collect::<Vec<_>>(),
+        [0, 1, 2, 3]
+    );
+    assert_eq!(Small::count(), 4);
+
+    // 运算结果不会离开范围
+    let g = Guess::new(99).unwrap();
+    assert_eq!(g.checked_add(1), Some(Guess::MAX));
+    assert_eq!(g.checked_add(2), None);
+    assert_eq!(g.saturating_add(1000), Guess::MAX);
+    assert_eq!(g.saturating_sub(1000), Guess::MIN);
+    assert_eq!(g.saturating_mul(-1), Guess::MIN);
+    assert_eq!(Guess::MAX.succ(), None);
+    assert_eq!(Guess::MIN.pred(), None);
+    assert_eq!(Guess::clamped(-5), Guess::MIN);
+    assert_eq!(Guess::new(50).unwrap().checked_div(0), None);
+
+    type Percent = Ranged<-100, 100>;
+    let p = Percent::default();
+    assert_eq!(p, Percent::MIN);
+    println!("percent: {}, {}", p, p.checked_mul(-1).unwrap());
+    assert_eq!(Ranged::<{ i64::MIN }, { i64::MAX }>::count(), 1 << 64);
+
+    // //evaluation panicked: Ranged requires MIN <= MAX [E0080]
+    // let bad = Ranged::<5, 1>::new(3);
+}
+
+fn use_expr() {
+    use expr::{run, type_of, Value};
+
+    // if 是表达式
+    let source = "
+        let condition = true;
+        let number = if condition { 5 } else { 6 };
+        number
+    ";
+    assert_eq!(run(source), Ok(Value::Int(5)));
+
+    // loop 的值由 break 给出, while 的值是 ()
+    let source = "
+        let mut var = 0;
+        let value = loop {
+            var += 1;
+            if var == 100 {
+                break var * 2;
+            }
+        };
+        value
+    ";
+    assert_eq!(run(source), Ok(Value::Int(200)));
+    assert_eq!(
+        run("let mut var = 0; while var != 100 { var += 1; }"),
+        Ok(Value::Unit)
+    );
+    assert_eq!(type_of("let mut v = 0; while v < 3 { v += 1; }"), Ok("()"));
+    assert_eq!(type_of("loop {}"), Ok("!"));
+
+    // 块也是表达式
+    assert_eq!(
+        run("let y = { let x = 3; x + 1 }; y * 2"),
+        Ok(Value::Int(8))
+    );
+    assert_eq!(run("10.0 / 3.0"), Ok(Value::Float(10.0 / 3.0)));
+    assert_eq!(run("5 / 2 + 5 % 2"), Ok(Value::Int(3)));
+    assert_eq!(run("1.0 / 0.0"), Ok(Value::Float(f64::INFINITY)));
+    assert_eq!(run("false && 1 / 0 == 0"), Ok(Value::Bool(false)));
+
+    let source = "
+        // 求 1..=10 中偶数的和
+        let mut sum = 0;
+        let mut i = 0;
+        while true {
+            i += 1;
+            if i > 10 { break; }
+            if i % 2 == 1 { continue; }
+            sum += i;
+        }
+        sum
+    ";
+    assert_eq!(run(source), Ok(Value::Int(30)));
+
+    // 类型错误在运行前报告, 即使出错的分支不会被执行
+    let programs = [
+        "let c = 5 + 1.0;",
+        "let number = 3; if number { 1 } else { 2 }",
+        "if false { 1 } else { 2.0 }",
+        "let x = if true { 1 };",
+        "let mut var = 0; while true { break \"done\" == 1; }",
+        "let v = 1; while true { break v; }",
+        "let x = 1; x = 2;",
+        "y + 1",
+        "break;",
+        "loop { if true { break 1; } else { break false; } }",
+        "true + true",
+        "-false",
+        "1 < 2 < 3",
+        "let c = 5 +;",
+        "i64::MAX",
+        "9223372036854775807 + 1",
+        "1 / 0",
+        "loop {}",
+    ];
+    for source in programs {
+        let e = run(source).unwrap_err();
+        println!("{}\n", e.render(source));
+    }
+    let e = run("let c = 5 + 1.0;").unwrap_err();
+    assert_eq!(e.code, Some("E0277"));
+    assert_eq!(e.message, "cannot add `float` to `integer`");
+    assert_eq!(e.line_col("let c = 5 + 1.0;"), (1, 9));
+    assert_eq!(run("let x = 1; x = 2;").unwrap_err().code, Some("E0384"));
+    assert_eq!(
+        run("let v = 1; while true { break v; }").unwrap_err().code,
+        Some("E0571")
+    );
+    assert_eq!(
+        run("9223372036854775807 + 1").unwrap_err().message,
+        "attempt to add with overflow"
+    );
+    assert_eq!(
+        expr::Interpreter::new()
+            .max_steps(1000)
+            .run("loop {}")
+            .unwrap_err()
+            .phase,
+        expr::Phase::Runtime
+    );
+    // 嵌套层数有上限, 过深的输入是语法错误而不是栈溢出
+    for deep in [
+        "{".repeat(100_000),
+        "(".repeat(100_000),
+        "-".repeat(100_000),
+    ] {
+        assert_eq!(run(&deep).unwrap_err().phase, expr::Phase::Parse);
+    }
+    assert_eq!(
+        run(&format!("{}1{}", "(".repeat(100), ")".repeat(100))),
+        Ok(expr::Value::Int(1))
+    );
+}
+
 fn tuple() {
     let a = (42, 42., true); //(i32, f64, bool)
     let b: (i32, f64, bool) = (42, 3.14, false);
@@ -464,5 +1001,39 @@ fn reference() {
     assert!(!ptr::eq(five_ref, other_five_ref));
 }
 fn main() {
-    optional_semicolon();
+    // $ cargo run -- unicode "love: ❤️"
+    // $ cargo run -- demo use_bitset
+    // $ cargo run --release -- demo bench_bitset
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("unicode") => {
+            if let Err(e) = unicode::cli(&args[1..]) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+        Some("radix") => {
+            if let Err(e) = radix::cli(&args[1..]) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+        Some("demo") => match args.get(1).map(|s| s.as_str()) {
+            Some("optional_semicolon") => optional_semicolon(),
+            Some("use_float") => use_float(),
+            Some("use_literal") => use_literal(),
+            Some("use_unicode") => use_unicode(),
+            Some("use_radix") => use_radix(),
+            Some("use_bitset") => use_bitset(),
+            Some("bench_bitset") => bench_bitset(),
+            Some("use_ranged") => use_ranged(),
+            Some("use_expr") => use_expr(),
+            Some(other) => {
+                eprintln!("unknown demo: {}", other);
+                std::process::exit(2);
+            }
+            None => optional_semicolon(),
+        },
+        _ => optional_semicolon(),
+    }
 }
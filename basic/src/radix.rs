@@ -0,0 +1,410 @@
+// 任意进制 (2-36) 的整数格式化与解析.
+//
+// scalar() 里只演示了 0x/0o/0b 三种字面量, 标准库的 {:x}/{:o}/{:b} 也只支持这三种进制,
+// 而且对负数输出的是补码 (format!("{:b}", -1i8) == "11111111").
+// 这里支持所有整数类型 (包括 i128/u128) 在 2 到 36 进制之间转换, 可选的数字分组,
+// 以及指定位宽下负数的补码视图.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RadixError {
+    InvalidRadix(u32),
+    Empty,
+    InvalidDigit { digit: char, pos: usize },
+    // 负号出现在无符号类型上
+    Negative,
+    Overflow,
+    InvalidBitWidth(u32),
+    // 值放不进给定的位宽
+    DoesNotFit { bits: u32 },
+}
+
+impl fmt::Display for RadixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RadixError::InvalidRadix(r) => write!(f, "radix must be in 2..=36, got {}", r),
+            RadixError::Empty => write!(f, "cannot parse integer from empty string"),
+            RadixError::InvalidDigit { digit, pos } => {
+                write!(f, "invalid digit {:?} at position {}", digit, pos)
+            }
+            RadixError::Negative => write!(f, "negative value for unsigned type"),
+            RadixError::Overflow => write!(f, "number too large to fit in target type"),
+            RadixError::InvalidBitWidth(bits) => {
+                write!(f, "bit width must be in 1..=128, got {}", bits)
+            }
+            RadixError::DoesNotFit { bits } => write!(f, "value does not fit in {} bits", bits),
+        }
+    }
+}
+
+impl std::error::Error for RadixError {}
+
+// 把各种宽度的整数统一成 (是否为负, 绝对值) 来处理, u128 的绝对值放得下所有类型.
+pub trait Integer: Copy {
+    const SIGNED: bool;
+    fn to_parts(self) -> (bool, u128);
+    fn from_parts(negative: bool, magnitude: u128) -> Option<Self>;
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            const SIGNED: bool = false;
+            fn to_parts(self) -> (bool, u128) {
+                (false, self as u128)
+            }
+            fn from_parts(negative: bool, magnitude: u128) -> Option<Self> {
+                if negative && magnitude != 0 {
+                    return None;
+                }
+                <$t>::try_from(magnitude).ok()
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            const SIGNED: bool = true;
+            fn to_parts(self) -> (bool, u128) {
+                (self < 0, (self as i128).unsigned_abs())
+            }
+            fn from_parts(negative: bool, magnitude: u128) -> Option<Self> {
+                if negative {
+                    // MIN 的绝对值比 MAX 大 1
+                    if magnitude > <$t>::MIN.unsigned_abs() as u128 {
+                        return None;
+                    }
+                    Some((magnitude as i128).wrapping_neg() as $t)
+                } else {
+                    <$t>::try_from(magnitude).ok()
+                }
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+impl_signed!(i8, i16, i32, i64, i128, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Format {
+    pub radix: u32,
+    // 每多少位数字插入一个分隔符, 从低位开始数. 例如 Some(4) 得到 1111_0000
+    pub group: Option<usize>,
+    pub separator: char,
+    pub uppercase: bool,
+    // 给 2/8/16 进制加上 0b/0o/0x 前缀
+    pub prefix: bool,
+}
+
+impl Format {
+    pub fn new(radix: u32) -> Self {
+        Format {
+            radix,
+            group: None,
+            separator: '_',
+            uppercase: false,
+            prefix: false,
+        }
+    }
+
+    pub fn group(mut self, size: usize) -> Self {
+        self.group = if size == 0 { None } else { Some(size) };
+        self
+    }
+
+    pub fn separator(mut self, separator: char) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    pub fn uppercase(mut self, uppercase: bool) -> Self {
+        self.uppercase = uppercase;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+}
+
+fn check_radix(radix: u32) -> Result<(), RadixError> {
+    if (2..=36).contains(&radix) {
+        Ok(())
+    } else {
+        Err(RadixError::InvalidRadix(radix))
+    }
+}
+
+fn prefix_of(radix: u32) -> &'static str {
+    match radix {
+        2 => "0b",
+        8 => "0o",
+        16 => "0x",
+        _ => "",
+    }
+}
+
+// 绝对值的各位数字, 高位在前, 至少 min_digits 位 (高位补 0)
+fn digits(mut magnitude: u128, fmt: &Format, min_digits: usize) -> String {
+    let mut out = Vec::new();
+    while magnitude != 0 || out.is_empty() {
+        let d = (magnitude % fmt.radix as u128) as u32;
+        let c = char::from_digit(d, fmt.radix).unwrap();
+        out.push(if fmt.uppercase {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        });
+        magnitude /= fmt.radix as u128;
+    }
+    while out.len() < min_digits {
+        out.push('0');
+    }
+    let mut s = String::new();
+    for (i, c) in out.iter().enumerate().rev() {
+        s.push(*c);
+        if let Some(size) = fmt.group {
+            if i != 0 && i % size == 0 {
+                s.push(fmt.separator);
+            }
+        }
+    }
+    s
+}
+
+// 带符号的格式化: 负数输出 -绝对值, 而不是补码.
+pub fn format<T: Integer>(value: T, fmt: &Format) -> Result<String, RadixError> {
+    check_radix(fmt.radix)?;
+    let (negative, magnitude) = value.to_parts();
+    let mut s = String::new();
+    if negative {
+        s.push('-');
+    }
+    if fmt.prefix {
+        s.push_str(prefix_of(fmt.radix));
+    }
+    s.push_str(&digits(magnitude, fmt, 0));
+    Ok(s)
+}
+
+pub fn to_radix<T: Integer>(value: T, radix: u32) -> Result<String, RadixError> {
+    format(value, &Format::new(radix))
+}
+
+// 解析带可选符号, 可选前缀 (需与 radix 匹配) 和可选 _ 分隔符的字符串.
+pub fn parse<T: Integer>(s: &str, radix: u32) -> Result<T, RadixError> {
+    check_radix(radix)?;
+    let (negative, body, mut pos) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..], 1),
+        Some(b'+') => (false, &s[1..], 1),
+        _ => (false, s, 0),
+    };
+    let body = match body.get(..2) {
+        Some(p) if !prefix_of(radix).is_empty() && p.eq_ignore_ascii_case(prefix_of(radix)) => {
+            pos += 2;
+            &body[2..]
+        }
+        _ => body,
+    };
+    if negative && !T::SIGNED {
+        return Err(RadixError::Negative);
+    }
+    let mut magnitude: u128 = 0;
+    let mut seen_digit = false;
+    for c in body.chars() {
+        if c == '_' && seen_digit {
+            pos += 1;
+            continue;
+        }
+        let d = c
+            .to_digit(radix)
+            .ok_or(RadixError::InvalidDigit { digit: c, pos })?;
+        magnitude = magnitude
+            .checked_mul(radix as u128)
+            .and_then(|m| m.checked_add(d as u128))
+            .ok_or(RadixError::Overflow)?;
+        seen_digit = true;
+        pos += c.len_utf8();
+    }
+    if !seen_digit {
+        return Err(RadixError::Empty);
+    }
+    T::from_parts(negative, magnitude).ok_or(RadixError::Overflow)
+}
+
+pub fn from_radix<T: Integer>(s: &str, radix: u32) -> Result<T, RadixError> {
+    parse(s, radix)
+}
+
+fn mask(bits: u32) -> u128 {
+    if bits == 128 {
+        u128::MAX
+    } else {
+        (1u128 << bits) - 1
+    }
+}
+
+// value 在 bits 位宽下的补码表示. 无符号数要求 < 2^bits, 有符号数要求在 [-2^(bits-1), 2^(bits-1)) 内.
+pub fn twos_complement<T: Integer>(value: T, bits: u32) -> Result<u128, RadixError> {
+    if !(1..=128).contains(&bits) {
+        return Err(RadixError::InvalidBitWidth(bits));
+    }
+    let (negative, magnitude) = value.to_parts();
+    let fits = if negative {
+        magnitude <= 1u128 << (bits - 1)
+    } else if T::SIGNED {
+        magnitude < 1u128 << (bits - 1)
+    } else {
+        magnitude <= mask(bits)
+    };
+    if !fits {
+        return Err(RadixError::DoesNotFit { bits });
+    }
+    let raw = if negative {
+        magnitude.wrapping_neg()
+    } else {
+        magnitude
+    };
+    Ok(raw & mask(bits))
+}
+
+// 把 bits 位宽的补码位模式还原成有符号值
+pub fn from_twos_complement(raw: u128, bits: u32) -> Result<i128, RadixError> {
+    if !(1..=128).contains(&bits) {
+        return Err(RadixError::InvalidBitWidth(bits));
+    }
+    if raw & !mask(bits) != 0 {
+        return Err(RadixError::DoesNotFit { bits });
+    }
+    // 左移到最高位再算术右移回来, 完成符号扩展
+    let shift = 128 - bits;
+    Ok(((raw << shift) as i128) >> shift)
+}
+
+// 补码视图. 2/8/16 进制时高位补 0 到位宽对应的位数, 例如 -1 在 8 位下是 1111_1111.
+pub fn format_twos_complement<T: Integer>(
+    value: T,
+    bits: u32,
+    fmt: &Format,
+) -> Result<String, RadixError> {
+    check_radix(fmt.radix)?;
+    let raw = twos_complement(value, bits)?;
+    let width = match fmt.radix {
+        2 => bits as usize,
+        8 => bits.div_ceil(3) as usize,
+        16 => bits.div_ceil(4) as usize,
+        _ => 0,
+    };
+    let mut s = String::new();
+    if fmt.prefix {
+        s.push_str(prefix_of(fmt.radix));
+    }
+    s.push_str(&digits(raw, fmt, width));
+    Ok(s)
+}
+
+// $ cargo run -- radix -42 --to 2 --bits 8 --group 4
+// $ cargo run -- radix ff --from 16 --to 36
+// 值按 i128 解析, 解析失败 (超出 i128) 时再按 u128 解析.
+pub fn cli(args: &[String]) -> Result<(), String> {
+    let usage =
+        "usage: radix <value> [--from N] [--to N] [--group N] [--bits N] [--upper] [--prefix]";
+    let mut value = None;
+    let mut from = 10;
+    let mut to = None;
+    let mut group = 0;
+    let mut bits = None;
+    let mut upper = false;
+    let mut prefix = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut number = |name: &str| -> Result<u32, String> {
+            iter.next()
+                .and_then(|v| v.parse().ok())
+                .ok_or(format!("{} expects a number\n{}", name, usage))
+        };
+        match arg.as_str() {
+            "--from" => from = number("--from")?,
+            "--to" => to = Some(number("--to")?),
+            "--group" => group = number("--group")? as usize,
+            "--bits" => bits = Some(number("--bits")?),
+            "--upper" => upper = true,
+            "--prefix" => prefix = true,
+            _ if value.is_none() => value = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {:?}\n{}", arg, usage)),
+        }
+    }
+    let value = value.ok_or(usage.to_string())?;
+    let targets = match to {
+        Some(to) => vec![to],
+        None => vec![2, 8, 10, 16, 36],
+    };
+    let print = |radix: u32, result: Result<String, RadixError>| match result {
+        Ok(s) => println!("base {:>2}: {}", radix, s),
+        Err(e) => println!("base {:>2}: error: {}", radix, e),
+    };
+    for radix in targets {
+        let fmt = Format::new(radix)
+            .group(group)
+            .uppercase(upper)
+            .prefix(prefix);
+        let result = match (parse::<i128>(&value, from), bits) {
+            (Ok(v), Some(bits)) => format_twos_complement(v, bits, &fmt),
+            (Ok(v), None) => format(v, &fmt),
+            (Err(_), Some(bits)) => {
+                parse::<u128>(&value, from).and_then(|v| format_twos_complement(v, bits, &fmt))
+            }
+            (Err(_), None) => parse::<u128>(&value, from).and_then(|v| format(v, &fmt)),
+        };
+        print(radix, result);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 简单的 xorshift, 避免引入 rand 依赖
+    fn samples() -> Vec<u128> {
+        let mut x: u128 = 0x2545_f491_4f6c_dd1d;
+        let mut out = vec![0, 1, 2, u128::MAX, u128::MAX - 1];
+        for _ in 0..200 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            out.push(x);
+            // 也覆盖较短的数字
+            out.push(x >> (x % 128));
+        }
+        out
+    }
+
+    macro_rules! round_trip {
+        ($($t:ty),*) => {$(
+            let mut values = vec![<$t>::MIN, <$t>::MAX];
+            values.extend(samples().into_iter().map(|v| v as $t));
+            for value in values {
+                for radix in 2..=36 {
+                    let s = to_radix(value, radix).unwrap();
+                    assert_eq!(from_radix::<$t>(&s, radix), Ok(value), "{} in base {}", s, radix);
+
+                    let fmt = Format::new(radix).group(3).prefix(true).uppercase(radix % 2 == 0);
+                    let s = format(value, &fmt).unwrap();
+                    assert_eq!(parse::<$t>(&s, radix), Ok(value), "{} in base {}", s, radix);
+                }
+                assert_eq!(to_radix(value, 10).unwrap(), value.to_string());
+            }
+        )*};
+    }
+
+    #[test]
+    fn round_trip_all_types_and_radixes() {
+        round_trip!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+    }
+}
@@ -0,0 +1,197 @@
+// 取值范围受约束的整数.
+//
+// guessing_game 里 1..101 的范围, 以及 use_never() 里的
+//     match i { 0..=3 => i, _ => panic!(...) }
+// 都是手工检查范围. Ranged<MIN, MAX> 把范围写进类型里:
+// 一旦构造成功, 值就一定在 MIN..=MAX 之内, 之后的算术运算也不会让它离开这个范围.
+
+use std::fmt;
+use std::num::{IntErrorKind, ParseIntError};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ranged<const MIN: i64, const MAX: i64>(i64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeError {
+    TooSmall { value: i64, min: i64, max: i64 },
+    TooLarge { value: i64, min: i64, max: i64 },
+    // 超出了 i64 本身的范围, 没法给出具体的值
+    Overflow { negative: bool, min: i64, max: i64 },
+    Invalid(ParseIntError),
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::TooSmall { value, min, max } => {
+                write!(
+                    f,
+                    "{} is less than {} (expected {}..={})",
+                    value, min, min, max
+                )
+            }
+            RangeError::TooLarge { value, min, max } => {
+                write!(
+                    f,
+                    "{} is greater than {} (expected {}..={})",
+                    value, max, min, max
+                )
+            }
+            RangeError::Overflow { negative, min, max } => {
+                let which = if *negative { "small" } else { "large" };
+                write!(f, "number too {} (expected {}..={})", which, min, max)
+            }
+            RangeError::Invalid(e) => write!(f, "not a number: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+impl<const MIN: i64, const MAX: i64> Ranged<MIN, MAX> {
+    // 在编译期检查 MIN <= MAX, Ranged<5, 1> 在被使用时会编译失败
+    const VALID: () = assert!(MIN <= MAX, "Ranged requires MIN <= MAX");
+
+    pub const MIN: Self = {
+        let () = Self::VALID;
+        Ranged(MIN)
+    };
+    pub const MAX: Self = {
+        let () = Self::VALID;
+        Ranged(MAX)
+    };
+
+    pub fn new(value: i64) -> Result<Self, RangeError> {
+        let () = Self::VALID;
+        if value < MIN {
+            Err(RangeError::TooSmall {
+                value,
+                min: MIN,
+                max: MAX,
+            })
+        } else if value > MAX {
+            Err(RangeError::TooLarge {
+                value,
+                min: MIN,
+                max: MAX,
+            })
+        } else {
+            Ok(Ranged(value))
+        }
+    }
+
+    // 超出范围时取最近的边界值
+    pub fn clamped(value: i64) -> Self {
+        let () = Self::VALID;
+        Ranged(value.clamp(MIN, MAX))
+    }
+
+    pub fn get(self) -> i64 {
+        self.0
+    }
+
+    pub fn contains(value: i64) -> bool {
+        (MIN..=MAX).contains(&value)
+    }
+
+    // 范围内所有值的个数. Ranged<{i64::MIN}, {i64::MAX}> 有 2^64 个值, 所以返回 u128.
+    pub fn count() -> u128 {
+        let () = Self::VALID;
+        (MAX as i128 - MIN as i128 + 1) as u128
+    }
+
+    // 从 MIN 到 MAX 依次迭代所有值
+    pub fn all() -> impl DoubleEndedIterator<Item = Self> {
+        let () = Self::VALID;
+        (MIN..=MAX).map(Ranged)
+    }
+
+    // checked_*: 结果溢出 i64 或者离开范围时返回 None
+    pub fn checked_add(self, rhs: i64) -> Option<Self> {
+        self.0.checked_add(rhs).and_then(|v| Self::new(v).ok())
+    }
+
+    pub fn checked_sub(self, rhs: i64) -> Option<Self> {
+        self.0.checked_sub(rhs).and_then(|v| Self::new(v).ok())
+    }
+
+    pub fn checked_mul(self, rhs: i64) -> Option<Self> {
+        self.0.checked_mul(rhs).and_then(|v| Self::new(v).ok())
+    }
+
+    pub fn checked_div(self, rhs: i64) -> Option<Self> {
+        self.0.checked_div(rhs).and_then(|v| Self::new(v).ok())
+    }
+
+    // saturating_*: 结果停在 MIN 或 MAX 上
+    pub fn saturating_add(self, rhs: i64) -> Self {
+        Self::clamped(self.0.saturating_add(rhs))
+    }
+
+    pub fn saturating_sub(self, rhs: i64) -> Self {
+        Self::clamped(self.0.saturating_sub(rhs))
+    }
+
+    pub fn saturating_mul(self, rhs: i64) -> Self {
+        Self::clamped(self.0.saturating_mul(rhs))
+    }
+
+    // 下一个/上一个值, 到边界时返回 None
+    pub fn succ(self) -> Option<Self> {
+        self.checked_add(1)
+    }
+
+    pub fn pred(self) -> Option<Self> {
+        self.checked_sub(1)
+    }
+}
+
+impl<const MIN: i64, const MAX: i64> Default for Ranged<MIN, MAX> {
+    fn default() -> Self {
+        let () = Self::VALID;
+        Self::MIN
+    }
+}
+
+impl<const MIN: i64, const MAX: i64> fmt::Display for Ranged<MIN, MAX> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<const MIN: i64, const MAX: i64> TryFrom<i64> for Ranged<MIN, MAX> {
+    type Error = RangeError;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<const MIN: i64, const MAX: i64> From<Ranged<MIN, MAX>> for i64 {
+    fn from(value: Ranged<MIN, MAX>) -> Self {
+        value.0
+    }
+}
+
+impl<const MIN: i64, const MAX: i64> FromStr for Ranged<MIN, MAX> {
+    type Err = RangeError;
+
+    // 与 guessing_game 一样先 trim, 这样可以直接解析 read_line 读到的内容
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.parse::<i64>() {
+            Ok(v) => Self::new(v),
+            Err(e) => match e.kind() {
+                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                    Err(RangeError::Overflow {
+                        negative: *e.kind() == IntErrorKind::NegOverflow,
+                        min: MIN,
+                        max: MAX,
+                    })
+                }
+                _ => Err(RangeError::Invalid(e)),
+            },
+        }
+    }
+}
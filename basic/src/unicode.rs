@@ -0,0 +1,180 @@
+// Unicode 字符串分析.
+//
+// scalar() 里 "love: ❤️" 的 len() 是 12, chars() 却只有 8 个,
+// 因为 ❤️ 实际上是 U+2764 加上一个不可见的变体选择符 U+FE0F.
+// 而人眼看到的 "字符" 是 extended grapheme cluster (字素簇), 这一点 chars() 也反映不出来.
+//
+// 这里对一个字符串同时给出:
+// 字节长度 / char 个数 / 字素簇 / 终端显示宽度
+// 以及每个 char 的码点, UTF-8 编码, Unicode general category, 是否不可见, 是否是组合字符.
+
+use std::fmt;
+use std::io::{self, Read};
+
+use unicode_general_category::{get_general_category, GeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+#[derive(Debug, Clone)]
+pub struct CharInfo {
+    pub ch: char,
+    // 在原字符串中的字节偏移
+    pub byte_offset: usize,
+    // 所在字素簇的下标
+    pub grapheme_index: usize,
+    pub category: GeneralCategory,
+    // 控制字符没有显示宽度, 是 None
+    pub width: Option<usize>,
+    pub invisible: bool,
+    pub combining: bool,
+}
+
+impl CharInfo {
+    pub fn code_point(&self) -> u32 {
+        self.ch as u32
+    }
+
+    pub fn utf8(&self) -> Vec<u8> {
+        let mut buf = [0; 4];
+        self.ch.encode_utf8(&mut buf).as_bytes().to_vec()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis<'a> {
+    pub text: &'a str,
+    pub byte_len: usize,
+    pub char_count: usize,
+    pub graphemes: Vec<&'a str>,
+    pub display_width: usize,
+    pub chars: Vec<CharInfo>,
+}
+
+impl Analysis<'_> {
+    pub fn grapheme_count(&self) -> usize {
+        self.graphemes.len()
+    }
+
+    pub fn invisible_chars(&self) -> impl Iterator<Item = &CharInfo> {
+        self.chars.iter().filter(|c| c.invisible)
+    }
+
+    pub fn combining_chars(&self) -> impl Iterator<Item = &CharInfo> {
+        self.chars.iter().filter(|c| c.combining)
+    }
+}
+
+// 变体选择符 VS1..VS16 和 VS17..VS256, 用来选择前一个字符的显示形式 (如 emoji 样式)
+fn is_variation_selector(c: char) -> bool {
+    matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+// 不会被画出来的字符: 控制字符, 格式字符 (零宽空格, ZWJ, BOM, 双向控制符等),
+// 行/段分隔符, 变体选择符, 以及几个渲染成空白的 Hangul filler.
+pub fn is_invisible(c: char) -> bool {
+    use GeneralCategory::*;
+    matches!(
+        get_general_category(c),
+        Control | Format | LineSeparator | ParagraphSeparator
+    ) || is_variation_selector(c)
+        || matches!(c, '\u{115F}' | '\u{1160}' | '\u{3164}' | '\u{FFA0}')
+}
+
+// 组合字符 (Mn/Mc/Me): 附着在前一个字符上显示, 例如 e + U+0301 = é
+pub fn is_combining(c: char) -> bool {
+    use GeneralCategory::*;
+    matches!(
+        get_general_category(c),
+        NonspacingMark | SpacingMark | EnclosingMark
+    )
+}
+
+pub fn analyze(text: &str) -> Analysis<'_> {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let mut chars = Vec::new();
+    let mut byte_offset = 0;
+    for (grapheme_index, g) in graphemes.iter().enumerate() {
+        for ch in g.chars() {
+            chars.push(CharInfo {
+                ch,
+                byte_offset,
+                grapheme_index,
+                category: get_general_category(ch),
+                width: ch.width(),
+                invisible: is_invisible(ch),
+                combining: is_combining(ch),
+            });
+            byte_offset += ch.len_utf8();
+        }
+    }
+    Analysis {
+        text,
+        byte_len: text.len(),
+        char_count: chars.len(),
+        graphemes,
+        display_width: text.width(),
+        chars,
+    }
+}
+
+impl fmt::Display for Analysis<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "text:          {:?}", self.text)?;
+        writeln!(f, "bytes:         {}", self.byte_len)?;
+        writeln!(f, "chars:         {}", self.char_count)?;
+        writeln!(f, "graphemes:     {}", self.grapheme_count())?;
+        writeln!(f, "display width: {}", self.display_width)?;
+        writeln!(
+            f,
+            "{:>6} {:>5} {:<10} {:<12} {:<3} {:>5}  flags",
+            "offset", "gc", "code point", "utf-8", "cat", "width"
+        )?;
+        for c in &self.chars {
+            let utf8: Vec<String> = c.utf8().iter().map(|b| format!("{:02X}", b)).collect();
+            let mut flags = Vec::new();
+            if c.invisible {
+                flags.push("invisible");
+            }
+            if c.combining {
+                flags.push("combining");
+            }
+            // 不可见字符直接打印会看不出来, 打印转义形式
+            let shown = if c.invisible || c.combining {
+                c.ch.escape_unicode().to_string()
+            } else {
+                c.ch.to_string()
+            };
+            let line = format!(
+                "{:>6} {:>5} U+{:<8X} {:<12} {:<3} {:>5}  {} {}",
+                c.byte_offset,
+                c.grapheme_index,
+                c.code_point(),
+                utf8.join(" "),
+                c.category.abbreviation(),
+                c.width.map_or(String::from("-"), |w| w.to_string()),
+                shown,
+                flags.join(",")
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+// $ cargo run -- unicode "love: ❤️" "e\u{301}"
+// 没有给出文本参数时从 stdin 读取.
+pub fn cli(args: &[String]) -> io::Result<()> {
+    if args.is_empty() {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        print!("{}", analyze(input.trim_end_matches('\n')));
+        return Ok(());
+    }
+    for (i, text) in args.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print!("{}", analyze(text));
+    }
+    Ok(())
+}
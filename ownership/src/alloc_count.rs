@@ -0,0 +1,179 @@
+// 统计堆内存分配的全局分配器.
+//
+// shallow_transfer_vs_deep_clone() 里说: move 只复制栈上数据, clone 还会复制堆上数据.
+// 用这个分配器可以实际量出来:
+//
+//     let s1 = String::from("s1");
+//     let stats = measure(|| { let s2 = s1; });         // stats.allocations == 0
+//     let stats = measure(|| { let s2 = s1.clone(); }); // stats.bytes_allocated == 2
+//
+// 需要在 main.rs 里注册才会生效 (opt-in). main.rs 只在打开 alloc-count feature 或者跑测试时注册:
+//
+//     #[cfg(any(test, feature = "alloc-count"))]
+//     #[global_allocator]
+//     static GLOBAL: alloc_count::Counting = alloc_count::Counting;
+//
+// 计数是按线程的, 只统计调用 measure 的线程在闭包执行期间的分配, 其他线程的分配不会混进来.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub struct Counting;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    // alloc 和 realloc 的次数
+    pub allocations: usize,
+    pub deallocations: usize,
+    pub bytes_allocated: usize,
+    pub bytes_deallocated: usize,
+    // 闭包执行期间, 相对于开始时, 同一时刻占用的最大字节数
+    pub peak: usize,
+}
+
+impl Stats {
+    // 闭包结束后仍未释放的字节数, 可能为负 (释放了闭包之前分配的内存)
+    pub fn net(&self) -> isize {
+        self.bytes_allocated as isize - self.bytes_deallocated as isize
+    }
+}
+
+thread_local! {
+    // None 表示当前线程没有在 measure 中
+    static CURRENT: Cell<Option<Stats>> = const { Cell::new(None) };
+}
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+// 分配器里不能分配内存, 也不能 panic, 所以用 Cell 和 try_with
+fn record(alloc: usize, dealloc: usize) {
+    let _ = CURRENT.try_with(|current| {
+        if let Some(mut s) = current.get() {
+            if alloc > 0 {
+                s.allocations += 1;
+                s.bytes_allocated += alloc;
+            }
+            if dealloc > 0 {
+                s.deallocations += 1;
+                s.bytes_deallocated += dealloc;
+            }
+            s.peak = s.peak.max(s.net().max(0) as usize);
+            current.set(Some(s));
+        }
+    });
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record(0, layout.size());
+    }
+
+    // realloc 算作一次新的分配加一次释放
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size, layout.size());
+        }
+        new_ptr
+    }
+}
+
+// Counting 是否已经注册为全局分配器 (至少经手过一次分配)
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+// measure_with 期间存活, drop 时把内层的统计并入外层并恢复外层.
+// 即使 f panic, 之后的分配也不会被计入已经结束的内层统计
+struct Frame {
+    outer: Option<Stats>,
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let inner = CURRENT.with(|c| c.get()).unwrap_or_default();
+        let merged = self.outer.map(|o| Stats {
+            allocations: o.allocations + inner.allocations,
+            deallocations: o.deallocations + inner.deallocations,
+            bytes_allocated: o.bytes_allocated + inner.bytes_allocated,
+            bytes_deallocated: o.bytes_deallocated + inner.bytes_deallocated,
+            peak: o.peak.max((o.net().max(0) as usize) + inner.peak),
+        });
+        CURRENT.with(|c| c.set(merged));
+    }
+}
+
+// 执行 f, 返回它的结果和执行期间当前线程的分配统计.
+// 可以嵌套, 内层的分配也会计入外层.
+pub fn measure_with<R>(f: impl FnOnce() -> R) -> (R, Stats) {
+    assert!(
+        is_installed(),
+        "alloc_count::Counting is not registered as #[global_allocator]"
+    );
+    let frame = Frame {
+        outer: CURRENT.with(|c| c.replace(Some(Stats::default()))),
+    };
+    let result = f();
+    let inner = CURRENT.with(|c| c.get()).unwrap();
+    drop(frame);
+    (result, inner)
+}
+
+pub fn measure(f: impl FnOnce()) -> Stats {
+    measure_with(f).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // 要测的正是 let s2 = s1 这一句
+    #[allow(clippy::let_and_return)]
+    fn move_does_not_allocate() {
+        let s1 = String::from("s1");
+        let (s2, stats) = measure_with(|| {
+            let s2 = s1;
+            s2
+        });
+        assert_eq!(stats.allocations, 0);
+        assert_eq!(stats.bytes_allocated, 0);
+
+        let (_s3, stats) = measure_with(|| s2.clone());
+        assert_eq!(stats.allocations, 1);
+        assert_eq!(stats.bytes_allocated, 2);
+    }
+
+    #[test]
+    fn nested_measure_counts_in_outer() {
+        let outer = measure(|| {
+            let _a = String::from("abc");
+            let inner = measure(|| {
+                let _b = String::from("defg");
+            });
+            assert_eq!(inner.bytes_allocated, 4);
+        });
+        assert_eq!(outer.bytes_allocated, 7);
+        assert_eq!(outer.net(), 0);
+    }
+}
@@ -0,0 +1,193 @@
+// 一个玩具借用检查器.
+//
+// main.rs 里 move, 两个 &mut, & 与 &mut 冲突, partial move 这些错误都只能以注释的形式出现,
+// 因为真的写出来就编译不过. 这里对一门很小的类 Rust 语言做同样的检查, 并给出 rustc 的错误码:
+//
+//     let s1 = String::from("s1");
+//     let s2 = s1;
+//     println!("s1: {}", s1);   // error[E0382]: borrow of moved value: `s1`
+//
+// 语言支持:
+// - let / let mut / let x; (先声明后初始化), 赋值 x = ...; a.f = ...;
+// - 值: 整数 (Copy), "字符串字面量" (&'static str, Copy), String::from("..") / String::new() / "..".to_string(),
+//   结构体字面量 Droppable { a: 0, b: String::new() }, &x, &mut x, x.clone(), 字段 a.b.c
+// - println!("{} {x:?}", y) 和 use(x) 读取 (不可变借用) 参数
+// - drop(x) 以及任意函数调用 f(x, y) 都会 move 参数
+// - { ... } 块, 块结束时其中的变量被 drop
+//
+// 和 NLL (non-lexical lifetimes) 一样, 借用从创建开始, 到持有它的变量最后一次被使用为止,
+// 而不是到变量离开作用域为止.
+//
+// 报告的错误:
+// E0381 使用未初始化的变量, E0382 use/borrow of (partially) moved value, E0384 给不可变变量赋值两次,
+// E0499 两个 &mut, E0502 & 与 &mut 冲突, E0503 被 &mut 借用时使用, E0505 被借用时 move,
+// E0506 被借用时赋值, E0594 给不可变变量的字段赋值, E0596 对不可变变量取 &mut, E0597 活得不够久,
+// E0425 找不到变量.
+
+mod check;
+mod lexer;
+mod parser;
+
+use std::fmt;
+
+// 源码中的字节区间 [start, end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+// 次要标注, 例如 "value moved here"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    // 语法错误没有错误码
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Span,
+    pub label: String,
+    pub secondary: Vec<Label>,
+    pub note: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: Option<&'static str>, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            code,
+            message: message.into(),
+            span,
+            label: String::new(),
+            secondary: Vec::new(),
+            note: None,
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    // 把字节偏移换算成 1 开始的行号和列号 (列号按 char 计)
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        line_col(source, self.span.start)
+    }
+
+    // 类似 rustc 的输出格式:
+    // error[E0382]: borrow of moved value: `s1`
+    //  --> 3:20
+    //   |
+    // 2 | let s2 = s1;
+    //   |          -- value moved here
+    // 3 | println!("s1: {}", s1);
+    //   |                    ^^ value borrowed here after move
+    pub fn render(&self, source: &str) -> String {
+        let mut labels: Vec<(Span, &str, char)> = vec![(self.span, &self.label, '^')];
+        labels.extend(
+            self.secondary
+                .iter()
+                .map(|l| (l.span, l.message.as_str(), '-')),
+        );
+        labels.sort_by_key(|(span, _, _)| span.start);
+        let (line, col) = self.line_col(source);
+        let last_line = labels
+            .iter()
+            .map(|(span, _, _)| line_col(source, span.start).0)
+            .max()
+            .unwrap();
+        let gutter = " ".repeat(last_line.to_string().len());
+        let mut out = format!("{}\n{}--> {}:{}\n{} |", self, gutter, line, col, gutter);
+        let mut prev_line = 0;
+        for (span, message, mark) in labels {
+            let (line, col) = line_col(source, span.start);
+            let text = source.lines().nth(line - 1).unwrap_or("");
+            if line != prev_line {
+                out += &format!("\n{:>w$} | {}", line, text, w = gutter.len());
+                prev_line = line;
+            }
+            // 跨行的区间只标到行尾
+            let rest = text.chars().count() + 1 - col.min(text.chars().count() + 1);
+            let width = source[span.start..span.end.max(span.start)]
+                .chars()
+                .count()
+                .min(rest)
+                .max(1);
+            out += &format!(
+                "\n{} | {}{} {}",
+                gutter,
+                " ".repeat(col - 1),
+                mark.to_string().repeat(width),
+                message
+            );
+        }
+        if let Some(note) = &self.note {
+            out += &format!("\n{} |\n{} = note: {}", gutter, gutter, note);
+        }
+        out
+    }
+}
+
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, col)
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "error[{}]: {}", code, self.message),
+            None => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+// 检查整个程序, 返回所有的错误. 语法错误只会有一个.
+pub fn check(source: &str) -> Result<(), Vec<Diagnostic>> {
+    let tokens = lexer::tokenize(source).map_err(|e| vec![*e])?;
+    let program = parser::parse(&tokens).map_err(|e| vec![*e])?;
+    let diagnostics = check::check(&program);
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+// 只返回错误码, 方便断言
+pub fn error_codes(source: &str) -> Vec<&'static str> {
+    match check(source) {
+        Ok(()) => Vec::new(),
+        Err(diagnostics) => diagnostics.iter().filter_map(|d| d.code).collect(),
+    }
+}
@@ -0,0 +1,864 @@
+// 检查分两步:
+// 1. 解析变量名 (处理作用域和遮蔽), 把嵌套的块展开成一串线性的操作 Op, 块结束处插入 ScopeEnd.
+// 2. 从后往前算出每个操作之后还会被用到的变量 (活跃变量), 再从前往后模拟执行:
+//    记录每个变量的类型, 哪些 place 被 move 了, 哪些借用 (loan) 被哪些变量持有.
+//    一个借用只要还有持有它的变量是活跃的, 它就是活的 (NLL).
+
+use std::collections::{BTreeSet, HashSet};
+
+use super::parser::{Expr, ExprKind, Place, Stmt};
+use super::{Diagnostic, Span};
+
+type VarId = usize;
+type LoanId = usize;
+
+#[derive(Debug, Clone)]
+struct RPlace {
+    var: VarId,
+    fields: Vec<String>,
+    span: Span,
+    // 源码里的写法, 用于错误信息
+    text: String,
+}
+
+#[derive(Debug, Clone)]
+enum RExpr {
+    Int,
+    Str,
+    Place(RPlace),
+    Borrow {
+        mutable: bool,
+        place: RPlace,
+        span: Span,
+    },
+    Clone(RPlace),
+    Method {
+        mutable: bool,
+        place: RPlace,
+        args: Vec<RExpr>,
+        span: Span,
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, RExpr)>,
+    },
+    Call(Vec<RExpr>),
+}
+
+#[derive(Debug)]
+enum Op {
+    Let { var: VarId, init: Option<RExpr> },
+    Assign { place: RPlace, value: RExpr },
+    Read(Vec<RExpr>),
+    Expr(RExpr),
+    ScopeEnd { vars: Vec<VarId>, span: Span },
+}
+
+struct Var {
+    name: String,
+    mutable: bool,
+    decl: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Int,
+    Str,
+    String,
+    Ref {
+        mutable: bool,
+        target: Box<Ty>,
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, Ty)>,
+    },
+    // 未初始化, 或者字段不存在
+    Unknown,
+}
+
+impl Ty {
+    // 共享引用是 Copy 的, 可变引用不是. 结构体没有 #[derive(Clone, Copy)], 所以不是 Copy
+    fn is_copy(&self) -> bool {
+        matches!(self, Ty::Int | Ty::Str | Ty::Ref { mutable: false, .. })
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Ty::Int => String::from("i32"),
+            Ty::Str => String::from("&str"),
+            Ty::String => String::from("String"),
+            Ty::Ref { mutable, target } => {
+                format!("&{}{}", if *mutable { "mut " } else { "" }, target.name())
+            }
+            Ty::Struct { name, .. } => name.clone(),
+            Ty::Unknown => String::from("_"),
+        }
+    }
+}
+
+// ---------- 第 1 步: 解析变量名 ----------
+
+struct Resolver {
+    vars: Vec<Var>,
+    scopes: Vec<Vec<(String, VarId)>>,
+    ops: Vec<Op>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    fn lookup(&self, name: &str) -> Option<VarId> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|&(_, id)| id)
+    }
+
+    fn place(&mut self, place: &Place) -> Option<RPlace> {
+        match self.lookup(&place.var) {
+            Some(var) => Some(RPlace {
+                var,
+                fields: place.fields.clone(),
+                span: place.span,
+                text: place.describe(),
+            }),
+            None => {
+                self.diagnostics.push(
+                    Diagnostic::new(
+                        Some("E0425"),
+                        format!("cannot find value `{}` in this scope", place.var),
+                        place.span,
+                    )
+                    .with_label("not found in this scope"),
+                );
+                None
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Option<RExpr> {
+        Some(match &expr.kind {
+            ExprKind::Int => RExpr::Int,
+            ExprKind::Str => RExpr::Str,
+            ExprKind::Place(p) => RExpr::Place(self.place(p)?),
+            ExprKind::Borrow { mutable, place } => RExpr::Borrow {
+                mutable: *mutable,
+                place: self.place(place)?,
+                span: expr.span,
+            },
+            ExprKind::Clone(p) => RExpr::Clone(self.place(p)?),
+            ExprKind::Method {
+                mutable,
+                place,
+                args,
+            } => {
+                let place = self.place(place)?;
+                let mut resolved = Vec::new();
+                for e in args {
+                    resolved.push(self.expr(e)?);
+                }
+                RExpr::Method {
+                    mutable: *mutable,
+                    place,
+                    args: resolved,
+                    span: expr.span,
+                }
+            }
+            ExprKind::Struct { name, fields } => {
+                let mut resolved = Vec::new();
+                for (field, e) in fields {
+                    resolved.push((field.clone(), self.expr(e)?));
+                }
+                RExpr::Struct {
+                    name: name.clone(),
+                    fields: resolved,
+                }
+            }
+            ExprKind::Call(args) => {
+                let mut resolved = Vec::new();
+                for e in args {
+                    resolved.push(self.expr(e)?);
+                }
+                RExpr::Call(resolved)
+            }
+        })
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Let {
+                    name,
+                    name_span,
+                    mutable,
+                    init,
+                } => {
+                    // 先解析初始值, let x = x; 里右边的 x 是外面的 x
+                    let init = match init {
+                        Some(e) => match self.expr(e) {
+                            Some(e) => Some(e),
+                            None => continue,
+                        },
+                        None => None,
+                    };
+                    let var = self.vars.len();
+                    self.vars.push(Var {
+                        name: name.clone(),
+                        mutable: *mutable,
+                        decl: *name_span,
+                    });
+                    self.scopes.last_mut().unwrap().push((name.clone(), var));
+                    self.ops.push(Op::Let { var, init });
+                }
+                Stmt::Assign { place, value } => {
+                    if let (Some(place), Some(value)) = (self.place(place), self.expr(value)) {
+                        self.ops.push(Op::Assign { place, value });
+                    }
+                }
+                Stmt::Read(args) => {
+                    let args: Option<Vec<_>> = args.iter().map(|e| self.expr(e)).collect();
+                    if let Some(args) = args {
+                        self.ops.push(Op::Read(args));
+                    }
+                }
+                Stmt::Expr(e) => {
+                    if let Some(e) = self.expr(e) {
+                        self.ops.push(Op::Expr(e));
+                    }
+                }
+                Stmt::Block { stmts, end } => {
+                    self.scopes.push(Vec::new());
+                    self.stmts(stmts);
+                    let scope = self.scopes.pop().unwrap();
+                    // 与 rustc 一样, 后声明的变量先被 drop
+                    let vars = scope.iter().rev().map(|&(_, id)| id).collect();
+                    self.ops.push(Op::ScopeEnd { vars, span: *end });
+                }
+            }
+        }
+    }
+}
+
+// ---------- 第 2 步: 活跃变量分析 ----------
+
+fn expr_uses(expr: &RExpr, uses: &mut Vec<(VarId, Span)>) {
+    match expr {
+        RExpr::Int | RExpr::Str => {}
+        RExpr::Place(p) | RExpr::Clone(p) => uses.push((p.var, p.span)),
+        RExpr::Borrow { place, .. } => uses.push((place.var, place.span)),
+        RExpr::Method { place, args, .. } => {
+            uses.push((place.var, place.span));
+            args.iter().for_each(|e| expr_uses(e, uses));
+        }
+        RExpr::Struct { fields, .. } => fields.iter().for_each(|(_, e)| expr_uses(e, uses)),
+        RExpr::Call(args) => args.iter().for_each(|e| expr_uses(e, uses)),
+    }
+}
+
+// 每个操作用到 (读取) 的变量和被整体赋值 (定义) 的变量
+fn uses_and_defs(op: &Op) -> (Vec<(VarId, Span)>, Option<VarId>) {
+    let mut uses = Vec::new();
+    let mut def = None;
+    match op {
+        Op::Let { var, init } => {
+            if let Some(e) = init {
+                expr_uses(e, &mut uses);
+            }
+            def = Some(*var);
+        }
+        Op::Assign { place, value } => {
+            expr_uses(value, &mut uses);
+            if place.fields.is_empty() {
+                def = Some(place.var);
+            } else {
+                uses.push((place.var, place.span));
+            }
+        }
+        Op::Read(args) => args.iter().for_each(|e| expr_uses(e, &mut uses)),
+        Op::Expr(e) => expr_uses(e, &mut uses),
+        Op::ScopeEnd { .. } => {}
+    }
+    (uses, def)
+}
+
+// ---------- 第 3 步: 模拟执行 ----------
+
+#[derive(Debug, Clone)]
+struct Moved {
+    var: VarId,
+    fields: Vec<String>,
+    span: Span,
+    text: String,
+    ty: String,
+    // let x; 声明了但没有初始化
+    uninit: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Loan {
+    var: VarId,
+    fields: Vec<String>,
+    text: String,
+    mutable: bool,
+    span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    // 读取 Copy 类型的值
+    Copy,
+    Move,
+    // 不可变借用, 包括 println! 和 x.clone()
+    Read,
+    MutBorrow,
+    Assign,
+}
+
+struct Value {
+    ty: Ty,
+    loans: BTreeSet<LoanId>,
+}
+
+impl Value {
+    fn new(ty: Ty) -> Self {
+        Value {
+            ty,
+            loans: BTreeSet::new(),
+        }
+    }
+}
+
+struct Checker<'a> {
+    vars: &'a [Var],
+    types: Vec<Ty>,
+    uses: Vec<Vec<(VarId, Span)>>,
+    live_after: Vec<HashSet<VarId>>,
+    // 当前操作的下标
+    pos: usize,
+    moved: Vec<Moved>,
+    loans: Vec<Loan>,
+    // 每个变量的值里持有的借用
+    holds: Vec<BTreeSet<LoanId>>,
+    // 当前语句里临时创建的借用, 例如 f(&mut x, &x) 里的 &mut x
+    temp_loans: Vec<LoanId>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// a 是 b 的前缀 (包括相等)
+fn is_prefix(a: (VarId, &[String]), b: (VarId, &[String])) -> bool {
+    a.0 == b.0 && b.1.starts_with(a.1)
+}
+
+fn overlaps(a: (VarId, &[String]), b: (VarId, &[String])) -> bool {
+    is_prefix(a, b) || is_prefix(b, a)
+}
+
+pub fn check(program: &[Stmt]) -> Vec<Diagnostic> {
+    let mut resolver = Resolver {
+        vars: Vec::new(),
+        scopes: vec![Vec::new()],
+        ops: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    resolver.stmts(program);
+    if !resolver.diagnostics.is_empty() {
+        return resolver.diagnostics;
+    }
+    let ops = resolver.ops;
+
+    let (uses, defs): (Vec<_>, Vec<_>) = ops.iter().map(uses_and_defs).unzip();
+    let mut live_after = vec![HashSet::new(); ops.len()];
+    let mut live = HashSet::new();
+    for i in (0..ops.len()).rev() {
+        live_after[i] = live.clone();
+        if let Some(def) = defs[i] {
+            live.remove(&def);
+        }
+        live.extend(uses[i].iter().map(|&(v, _)| v));
+    }
+
+    let n = resolver.vars.len();
+    let mut checker = Checker {
+        vars: &resolver.vars,
+        types: vec![Ty::Unknown; n],
+        uses,
+        live_after,
+        pos: 0,
+        moved: Vec::new(),
+        loans: Vec::new(),
+        holds: vec![BTreeSet::new(); n],
+        temp_loans: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for (i, op) in ops.iter().enumerate() {
+        checker.pos = i;
+        checker.temp_loans.clear();
+        checker.op(op);
+    }
+    checker.diagnostics
+}
+
+impl Checker<'_> {
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn place_ty(&mut self, place: &RPlace) -> Ty {
+        let mut ty = self.types[place.var].clone();
+        for field in &place.fields {
+            ty = match &ty {
+                Ty::Struct { fields, .. } => match fields.iter().find(|(f, _)| f == field) {
+                    Some((_, t)) => t.clone(),
+                    None => {
+                        let name = ty.name();
+                        self.error(
+                            Diagnostic::new(
+                                Some("E0609"),
+                                format!("no field `{}` on type `{}`", field, name),
+                                place.span,
+                            )
+                            .with_label("unknown field"),
+                        );
+                        return Ty::Unknown;
+                    }
+                },
+                // 字段的字段, 或者通过引用访问的字段: 不再深究
+                _ => return Ty::Unknown,
+            };
+        }
+        ty
+    }
+
+    // 当前仍然活着的借用: 被活跃变量持有的, 以及当前语句里临时创建的
+    fn live_loans(&self) -> Vec<(LoanId, Option<VarId>)> {
+        let mut live: Vec<(LoanId, Option<VarId>)> =
+            self.temp_loans.iter().map(|&l| (l, None)).collect();
+        let current: HashSet<VarId> = self.uses[self.pos].iter().map(|&(v, _)| v).collect();
+        let mut vars: Vec<VarId> = self.live_after[self.pos].union(&current).copied().collect();
+        vars.sort();
+        for v in vars {
+            live.extend(self.holds[v].iter().map(|&l| (l, Some(v))));
+        }
+        live
+    }
+
+    // 持有借用的变量从当前操作开始的下一次使用
+    fn later_use(&self, holder: VarId) -> Option<Span> {
+        let current = self.uses[self.pos]
+            .iter()
+            .find(|&&(v, _)| v == holder)
+            .map(|&(_, span)| span);
+        current.or_else(|| {
+            self.uses[self.pos + 1..]
+                .iter()
+                .flatten()
+                .find(|&&(v, _)| v == holder)
+                .map(|&(_, span)| span)
+        })
+    }
+
+    // 检查 place 是否 (部分) 被 move 或者未初始化
+    fn check_moved(&mut self, place: &RPlace, access: Access) -> bool {
+        let path = (place.var, place.fields.as_slice());
+        let Some(m) = self
+            .moved
+            .iter()
+            .find(|m| overlaps((m.var, &m.fields), path))
+            .cloned()
+        else {
+            return true;
+        };
+        let borrow = matches!(access, Access::Read | Access::MutBorrow);
+        let (verb, used) = match access {
+            Access::Assign => (
+                "assign to part of",
+                "value partially assigned here after move",
+            ),
+            _ if borrow => ("borrow of", "value borrowed here after move"),
+            _ => ("use of", "value used here after move"),
+        };
+        let diagnostic = if m.uninit {
+            Diagnostic::new(
+                Some("E0381"),
+                format!("used binding `{}` isn't initialized", m.text),
+                place.span,
+            )
+            .with_label(format!("`{}` used here but it isn't initialized", m.text))
+            .with_secondary(m.span, "binding declared here but left uninitialized")
+        } else if is_prefix((m.var, &m.fields), path) {
+            Diagnostic::new(
+                Some("E0382"),
+                format!("{} moved value: `{}`", verb, place.text),
+                place.span,
+            )
+            .with_label(used)
+            .with_secondary(m.span, "value moved here")
+            .with_note(format!(
+                "move occurs because `{}` has type `{}`, which does not implement the `Copy` trait",
+                m.text, m.ty
+            ))
+        } else {
+            Diagnostic::new(
+                Some("E0382"),
+                format!("{} partially moved value: `{}`", verb, place.text),
+                place.span,
+            )
+            .with_label(used.replace("after move", "after partial move"))
+            .with_secondary(m.span, "value partially moved here")
+            .with_note(format!(
+                "partial move occurs because `{}` has type `{}`, which does not implement the `Copy` trait",
+                m.text, m.ty
+            ))
+        };
+        self.error(diagnostic);
+        false
+    }
+
+    // 检查 access 是否与活着的借用冲突
+    fn check_loans(&mut self, place: &RPlace, access: Access, span: Span) -> bool {
+        let path = (place.var, place.fields.as_slice());
+        for (id, holder) in self.live_loans() {
+            let loan = self.loans[id].clone();
+            if !overlaps((loan.var, &loan.fields), path) {
+                continue;
+            }
+            let p = &place.text;
+            let (code, message, primary, secondary) = match (access, loan.mutable) {
+                (Access::Read, false) | (Access::Copy, false) => continue,
+                (Access::Read, true) => (
+                    "E0502",
+                    format!(
+                        "cannot borrow `{}` as immutable because it is also borrowed as mutable",
+                        p
+                    ),
+                    String::from("immutable borrow occurs here"),
+                    String::from("mutable borrow occurs here"),
+                ),
+                (Access::Copy, true) => (
+                    "E0503",
+                    format!("cannot use `{}` because it was mutably borrowed", p),
+                    format!("use of borrowed `{}`", loan.text),
+                    format!("`{}` is borrowed here", loan.text),
+                ),
+                (Access::MutBorrow, true) => (
+                    "E0499",
+                    format!("cannot borrow `{}` as mutable more than once at a time", p),
+                    String::from("second mutable borrow occurs here"),
+                    String::from("first mutable borrow occurs here"),
+                ),
+                (Access::MutBorrow, false) => (
+                    "E0502",
+                    format!(
+                        "cannot borrow `{}` as mutable because it is also borrowed as immutable",
+                        p
+                    ),
+                    String::from("mutable borrow occurs here"),
+                    String::from("immutable borrow occurs here"),
+                ),
+                (Access::Move, _) => (
+                    "E0505",
+                    format!("cannot move out of `{}` because it is borrowed", p),
+                    format!("move out of `{}` occurs here", p),
+                    format!("borrow of `{}` occurs here", loan.text),
+                ),
+                (Access::Assign, _) => (
+                    "E0506",
+                    format!("cannot assign to `{}` because it is borrowed", p),
+                    format!("`{}` is assigned to here but it was already borrowed", p),
+                    format!("`{}` is borrowed here", loan.text),
+                ),
+            };
+            let mut diagnostic = Diagnostic::new(Some(code), message, span)
+                .with_label(primary)
+                .with_secondary(loan.span, secondary);
+            if let Some(later) = holder.and_then(|v| self.later_use(v)) {
+                diagnostic = diagnostic.with_secondary(later, "borrow later used here");
+            }
+            self.error(diagnostic);
+            return false;
+        }
+        true
+    }
+
+    fn check_mutable(&mut self, place: &RPlace, span: Span) -> bool {
+        let var = &self.vars[place.var];
+        // 通过 &mut 引用修改它指向的数据不需要变量本身是 mut 的
+        let through_ref = matches!(self.types[place.var], Ty::Ref { mutable: true, .. });
+        if var.mutable || through_ref {
+            return true;
+        }
+        let diagnostic = Diagnostic::new(
+            Some("E0596"),
+            format!(
+                "cannot borrow `{}` as mutable, as it is not declared as mutable",
+                place.text
+            ),
+            span,
+        )
+        .with_label("cannot borrow as mutable")
+        .with_secondary(
+            var.decl,
+            format!(
+                "help: consider changing this to be mutable: `mut {}`",
+                var.name
+            ),
+        );
+        self.error(diagnostic);
+        false
+    }
+
+    fn borrow(&mut self, mutable: bool, place: &RPlace, span: Span) -> Option<LoanId> {
+        let access = if mutable {
+            Access::MutBorrow
+        } else {
+            Access::Read
+        };
+        if !self.check_moved(place, access)
+            || (mutable && !self.check_mutable(place, span))
+            || !self.check_loans(place, access, span)
+        {
+            return None;
+        }
+        let id = self.loans.len();
+        self.loans.push(Loan {
+            var: place.var,
+            fields: place.fields.clone(),
+            text: place.text.clone(),
+            mutable,
+            span,
+        });
+        self.temp_loans.push(id);
+        Some(id)
+    }
+
+    fn eval(&mut self, expr: &RExpr) -> Value {
+        match expr {
+            RExpr::Int => Value::new(Ty::Int),
+            RExpr::Str => Value::new(Ty::Str),
+            RExpr::Place(place) => {
+                let ty = self.place_ty(place);
+                // 引用 (以及包含引用的值) 被复制或 move 时, 它持有的借用也一起转移
+                let loans = self.holds[place.var].clone();
+                let access = if ty.is_copy() {
+                    Access::Copy
+                } else {
+                    Access::Move
+                };
+                if self.check_moved(place, access)
+                    && self.check_loans(place, access, place.span)
+                    && access == Access::Move
+                {
+                    self.moved.push(Moved {
+                        var: place.var,
+                        fields: place.fields.clone(),
+                        span: place.span,
+                        text: place.text.clone(),
+                        ty: ty.name(),
+                        uninit: false,
+                    });
+                }
+                Value { ty, loans }
+            }
+            RExpr::Borrow {
+                mutable,
+                place,
+                span,
+            } => {
+                let target = self.place_ty(place);
+                let mut loans = self.holds[place.var].clone();
+                loans.extend(self.borrow(*mutable, place, *span));
+                Value {
+                    ty: Ty::Ref {
+                        mutable: *mutable,
+                        target: Box::new(target),
+                    },
+                    loans,
+                }
+            }
+            RExpr::Clone(place) => {
+                let ty = self.place_ty(place);
+                let loans = match ty {
+                    Ty::Ref { .. } => self.holds[place.var].clone(),
+                    _ => BTreeSet::new(),
+                };
+                if self.check_moved(place, Access::Read) {
+                    self.check_loans(place, Access::Read, place.span);
+                }
+                Value { ty, loans }
+            }
+            // 先求值参数再借用接收者, 所以 v.push(v.len()) 没有问题 (two-phase borrow)
+            RExpr::Method {
+                mutable,
+                place,
+                args,
+                span,
+            } => {
+                for e in args {
+                    self.eval(e);
+                }
+                self.borrow(*mutable, place, *span);
+                Value::new(Ty::Int)
+            }
+            RExpr::Struct { name, fields } => {
+                let mut loans = BTreeSet::new();
+                let mut tys = Vec::new();
+                for (field, e) in fields {
+                    let v = self.eval(e);
+                    loans.extend(v.loans);
+                    tys.push((field.clone(), v.ty));
+                }
+                Value {
+                    ty: Ty::Struct {
+                        name: name.clone(),
+                        fields: tys,
+                    },
+                    loans,
+                }
+            }
+            RExpr::Call(args) => {
+                for e in args {
+                    self.eval(e);
+                }
+                Value::new(Ty::String)
+            }
+        }
+    }
+
+    fn op(&mut self, op: &Op) {
+        match op {
+            Op::Let { var, init } => match init {
+                Some(e) => {
+                    let v = self.eval(e);
+                    self.types[*var] = v.ty;
+                    self.holds[*var] = v.loans;
+                }
+                None => self.moved.push(Moved {
+                    var: *var,
+                    fields: Vec::new(),
+                    span: self.vars[*var].decl,
+                    text: self.vars[*var].name.clone(),
+                    ty: String::new(),
+                    uninit: true,
+                }),
+            },
+            Op::Assign { place, value } => {
+                let v = self.eval(value);
+                // 右边求值完成后, 临时借用就结束了
+                self.temp_loans.clear();
+                self.assign(place, v);
+            }
+            Op::Read(args) => {
+                for e in args {
+                    match e {
+                        RExpr::Place(place) => {
+                            self.place_ty(place);
+                            if self.check_moved(place, Access::Read) {
+                                self.check_loans(place, Access::Read, place.span);
+                            }
+                        }
+                        e => {
+                            self.eval(e);
+                        }
+                    }
+                }
+            }
+            Op::Expr(e) => {
+                self.eval(e);
+            }
+            Op::ScopeEnd { vars, span } => {
+                for &var in vars {
+                    self.scope_end(var, *span);
+                }
+            }
+        }
+    }
+
+    fn assign(&mut self, place: &RPlace, v: Value) {
+        let var = &self.vars[place.var];
+        let initialized = !self.moved.iter().any(|m| m.uninit && m.var == place.var);
+        if place.fields.is_empty() {
+            if !var.mutable && initialized {
+                let diagnostic = Diagnostic::new(
+                    Some("E0384"),
+                    format!("cannot assign twice to immutable variable `{}`", var.name),
+                    place.span,
+                )
+                .with_label("cannot assign twice to immutable variable")
+                .with_secondary(var.decl, format!("first assignment to `{}`", var.name));
+                self.error(diagnostic);
+                return;
+            }
+        } else {
+            if !var.mutable {
+                let diagnostic = Diagnostic::new(
+                    Some("E0594"),
+                    format!(
+                        "cannot assign to `{}`, as `{}` is not declared as mutable",
+                        place.text, var.name
+                    ),
+                    place.span,
+                )
+                .with_label("cannot assign");
+                self.error(diagnostic);
+                return;
+            }
+            // 整个变量都被 move 了, 不能只给其中一个字段赋值
+            let whole = RPlace {
+                fields: Vec::new(),
+                text: var.name.clone(),
+                ..place.clone()
+            };
+            if self
+                .moved
+                .iter()
+                .any(|m| m.var == place.var && m.fields.is_empty())
+                && !self.check_moved(&whole, Access::Assign)
+            {
+                return;
+            }
+        }
+        if !self.check_loans(place, Access::Assign, place.span) {
+            return;
+        }
+        // 重新初始化: place 和它的字段都不再是 moved 的
+        let path = (place.var, place.fields.as_slice());
+        self.moved.retain(|m| !is_prefix(path, (m.var, &m.fields)));
+        if place.fields.is_empty() {
+            self.types[place.var] = v.ty;
+            self.holds[place.var] = v.loans;
+        } else {
+            self.holds[place.var].extend(v.loans);
+        }
+    }
+
+    // 变量离开作用域时, 如果借用了它的引用之后还会被用到, 就是 E0597
+    fn scope_end(&mut self, var: VarId, span: Span) {
+        let holders: Vec<VarId> = self.live_after[self.pos].iter().copied().collect();
+        for holder in holders {
+            let loan = self.holds[holder]
+                .iter()
+                .map(|&l| &self.loans[l])
+                .find(|loan| loan.var == var);
+            if let Some(loan) = loan {
+                let name = &self.vars[var].name;
+                let mut diagnostic = Diagnostic::new(
+                    Some("E0597"),
+                    format!("`{}` does not live long enough", name),
+                    loan.span,
+                )
+                .with_label("borrowed value does not live long enough")
+                .with_secondary(
+                    span,
+                    format!("`{}` dropped here while still borrowed", name),
+                );
+                if let Some(later) = self.later_use(holder) {
+                    diagnostic = diagnostic.with_secondary(later, "borrow later used here");
+                }
+                self.error(diagnostic);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use super::{Diagnostic, Span};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    Int,
+    // 字符串字面量的内容, 不处理转义
+    Str(String),
+    Ident(String),
+    Let,
+    Mut,
+    Eq,
+    Semi,
+    Comma,
+    Dot,
+    Colon,
+    PathSep,
+    Amp,
+    Bang,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    // 类型标注里可能出现的其他符号, 例如 <>, 解析时跳过
+    Other(char),
+    Eof,
+}
+
+impl Tok {
+    pub fn describe(&self) -> String {
+        match self {
+            Tok::Int => String::from("integer literal"),
+            Tok::Str(s) => format!("{:?}", s),
+            Tok::Ident(name) => format!("`{}`", name),
+            Tok::Let => String::from("`let`"),
+            Tok::Mut => String::from("`mut`"),
+            Tok::Eq => String::from("`=`"),
+            Tok::Semi => String::from("`;`"),
+            Tok::Comma => String::from("`,`"),
+            Tok::Dot => String::from("`.`"),
+            Tok::Colon => String::from("`:`"),
+            Tok::PathSep => String::from("`::`"),
+            Tok::Amp => String::from("`&`"),
+            Tok::Bang => String::from("`!`"),
+            Tok::LParen => String::from("`(`"),
+            Tok::RParen => String::from("`)`"),
+            Tok::LBrace => String::from("`{`"),
+            Tok::RBrace => String::from("`}`"),
+            Tok::Other(c) => format!("`{}`", c),
+            Tok::Eof => String::from("end of input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub tok: Tok,
+    pub span: Span,
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Box<Diagnostic>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        // 行注释
+        if source[i..].starts_with("//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        // #[derive(Debug)] 之类的属性直接跳过
+        if source[i..].starts_with("#[") {
+            while i < bytes.len() && bytes[i] != b']' {
+                i += 1;
+            }
+            i += 1;
+            continue;
+        }
+        let tok = if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            Tok::Int
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            match &source[start..i] {
+                "let" => Tok::Let,
+                "mut" => Tok::Mut,
+                name => Tok::Ident(name.to_string()),
+            }
+        } else if c == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            if i >= bytes.len() {
+                return Err(Box::new(Diagnostic::new(
+                    Some("E0765"),
+                    "unterminated double quote string",
+                    Span::new(start, bytes.len()),
+                )));
+            }
+            i += 1;
+            Tok::Str(source[start + 1..i - 1].to_string())
+        } else if source[i..].starts_with("::") {
+            i += 2;
+            Tok::PathSep
+        } else {
+            let ch = source[i..].chars().next().unwrap();
+            i += ch.len_utf8();
+            match ch {
+                '=' => Tok::Eq,
+                ';' => Tok::Semi,
+                ',' => Tok::Comma,
+                '.' => Tok::Dot,
+                ':' => Tok::Colon,
+                '&' => Tok::Amp,
+                '!' => Tok::Bang,
+                '(' => Tok::LParen,
+                ')' => Tok::RParen,
+                '{' => Tok::LBrace,
+                '}' => Tok::RBrace,
+                '<' | '>' | '[' | ']' | '\'' => Tok::Other(ch),
+                _ => {
+                    return Err(Box::new(Diagnostic::new(
+                        None,
+                        format!("unknown start of token: {}", ch.escape_default()),
+                        Span::new(start, i),
+                    )))
+                }
+            }
+        };
+        tokens.push(Token {
+            tok,
+            span: Span::new(start, i),
+        });
+    }
+    tokens.push(Token {
+        tok: Tok::Eof,
+        span: Span::new(bytes.len(), bytes.len()),
+    });
+    Ok(tokens)
+}
@@ -0,0 +1,467 @@
+use super::lexer::{Tok, Token};
+use super::{Diagnostic, Span};
+
+// 变量或者变量的字段, 例如 a, a.b.c
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub var: String,
+    pub fields: Vec<String>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    // 整数, Copy
+    Int,
+    // 字符串字面量 &'static str, Copy
+    Str,
+    // 使用一个 place: 类型是 Copy 的就复制, 否则 move
+    Place(Place),
+    Borrow {
+        mutable: bool,
+        place: Place,
+    },
+    // x.clone(), x.to_string(): 不可变借用 x, 得到一个新的值
+    Clone(Place),
+    // x.len(), x.push(..): 调用方法, 按 mutable 借用 x, 参数被 move, 得到一个整数
+    Method {
+        mutable: bool,
+        place: Place,
+        args: Vec<Expr>,
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, Expr)>,
+    },
+    // 函数调用, 参数都被 move, 得到一个新的 String (String::from, drop, vec! 也都算作函数调用)
+    Call(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Let {
+        name: String,
+        name_span: Span,
+        mutable: bool,
+        init: Option<Expr>,
+    },
+    Assign {
+        place: Place,
+        value: Expr,
+    },
+    // println!(..) 和 use(..): 不可变借用参数
+    Read(Vec<Expr>),
+    // 表达式语句, 值马上被丢弃
+    Expr(Expr),
+    // end 是右花括号, 块里的变量在这里被 drop
+    Block {
+        stmts: Vec<Stmt>,
+        end: Span,
+    },
+}
+
+// 会修改接收者的方法, 调用时需要 &mut
+const MUTATING_METHODS: [&str; 8] = [
+    "push", "push_str", "pop", "clear", "insert", "remove", "truncate", "sort",
+];
+
+// 块和表达式的嵌套层数上限, 避免十万个 { 这样的输入把栈撑爆
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    depth: usize,
+}
+
+// Diagnostic 比较大, 解析过程中装箱传递
+fn parse_error(message: impl Into<String>, span: Span) -> Box<Diagnostic> {
+    Box::new(Diagnostic::new(None, message, span))
+}
+
+pub fn parse(tokens: &[Token]) -> Result<Vec<Stmt>, Box<Diagnostic>> {
+    let mut p = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let mut stmts = Vec::new();
+    while *p.peek() != Tok::Eof {
+        if let Some(stmt) = p.stmt()? {
+            stmts.push(stmt);
+        }
+    }
+    Ok(stmts)
+}
+
+impl Place {
+    pub fn describe(&self) -> String {
+        let mut s = self.var.clone();
+        for field in &self.fields {
+            s.push('.');
+            s.push_str(field);
+        }
+        s
+    }
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, n: usize) -> &Tok {
+        let i = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[i].tok
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].span
+    }
+
+    fn prev_span(&self) -> Span {
+        self.tokens[self.pos.saturating_sub(1)].span
+    }
+
+    fn bump(&mut self) -> &Token {
+        let t = &self.tokens[self.pos];
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn eat(&mut self, tok: &Tok) -> bool {
+        if self.peek() == tok {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, tok: &Tok) -> Result<Span, Box<Diagnostic>> {
+        if self.peek() == tok {
+            Ok(self.bump().span)
+        } else {
+            Err(self.unexpected(&tok.describe()))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Box<Diagnostic> {
+        parse_error(
+            format!("expected {}, found {}", expected, self.peek().describe()),
+            self.span(),
+        )
+    }
+
+    fn ident(&mut self) -> Result<(String, Span), Box<Diagnostic>> {
+        match self.peek().clone() {
+            Tok::Ident(name) => Ok((name, self.bump().span)),
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    // 在嵌套一层的状态下运行 f, 超过 MAX_DEPTH 时报语法错误
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Box<Diagnostic>>,
+    ) -> Result<T, Box<Diagnostic>> {
+        if self.depth == MAX_DEPTH {
+            return Err(parse_error(
+                format!("nesting deeper than {} levels", MAX_DEPTH),
+                self.span(),
+            ));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(n) if n == name)
+    }
+
+    // 跳过一对括号 (包括嵌套的), 用于 struct 定义
+    fn skip_balanced(&mut self, open: &Tok, close: &Tok) -> Result<(), Box<Diagnostic>> {
+        self.expect(open)?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump().tok.clone() {
+                Tok::Eof => return Err(self.unexpected(&close.describe())),
+                t if t == *open => depth += 1,
+                t if t == *close => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // struct 定义没有需要检查的东西, 返回 None
+    fn stmt(&mut self) -> Result<Option<Stmt>, Box<Diagnostic>> {
+        self.nested(Self::stmt_inner)
+    }
+
+    fn stmt_inner(&mut self) -> Result<Option<Stmt>, Box<Diagnostic>> {
+        if self.eat(&Tok::Semi) {
+            return Ok(None);
+        }
+        if *self.peek() == Tok::Let {
+            return self.let_stmt().map(Some);
+        }
+        if *self.peek() == Tok::LBrace {
+            self.bump();
+            let mut stmts = Vec::new();
+            while *self.peek() != Tok::RBrace {
+                if *self.peek() == Tok::Eof {
+                    return Err(self.unexpected("`}`"));
+                }
+                if let Some(stmt) = self.stmt()? {
+                    stmts.push(stmt);
+                }
+            }
+            let end = self.bump().span;
+            return Ok(Some(Stmt::Block { stmts, end }));
+        }
+        if self.is_ident("struct") {
+            self.bump();
+            self.ident()?;
+            self.skip_balanced(&Tok::LBrace, &Tok::RBrace)?;
+            return Ok(None);
+        }
+        // println!(..), print!(..), use(..)
+        let is_print =
+            (self.is_ident("println") || self.is_ident("print")) && *self.peek_at(1) == Tok::Bang;
+        if is_print || (self.is_ident("use") && *self.peek_at(1) == Tok::LParen) {
+            self.bump();
+            self.eat(&Tok::Bang);
+            self.expect(&Tok::LParen)?;
+            let mut args = Vec::new();
+            if let Tok::Str(format) = self.peek().clone() {
+                let span = self.bump().span;
+                args.extend(inline_args(&format, span.start + 1));
+                self.eat(&Tok::Comma);
+            }
+            args.extend(self.args(&Tok::RParen)?);
+            self.expect(&Tok::Semi)?;
+            return Ok(Some(Stmt::Read(args)));
+        }
+        let expr = self.expr()?;
+        if let (ExprKind::Place(place), Tok::Eq) = (&expr.kind, self.peek()) {
+            let place = place.clone();
+            self.bump();
+            let value = self.expr()?;
+            self.expect(&Tok::Semi)?;
+            return Ok(Some(Stmt::Assign { place, value }));
+        }
+        self.expect(&Tok::Semi)?;
+        Ok(Some(Stmt::Expr(expr)))
+    }
+
+    fn let_stmt(&mut self) -> Result<Stmt, Box<Diagnostic>> {
+        self.expect(&Tok::Let)?;
+        let mutable = self.eat(&Tok::Mut);
+        let (name, name_span) = self.ident()?;
+        // 类型标注不影响检查, 跳过
+        if self.eat(&Tok::Colon) {
+            while !matches!(self.peek(), Tok::Eq | Tok::Semi | Tok::Eof) {
+                self.bump();
+            }
+        }
+        let init = if self.eat(&Tok::Eq) {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        self.expect(&Tok::Semi)?;
+        Ok(Stmt::Let {
+            name,
+            name_span,
+            mutable,
+            init,
+        })
+    }
+
+    // 逗号分隔的表达式, 直到 close (消耗 close)
+    fn args(&mut self, close: &Tok) -> Result<Vec<Expr>, Box<Diagnostic>> {
+        let mut args = Vec::new();
+        while !self.eat(close) {
+            args.push(self.expr()?);
+            if !self.eat(&Tok::Comma) {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(args)
+    }
+
+    fn place(&mut self) -> Result<Place, Box<Diagnostic>> {
+        let (var, span) = self.ident()?;
+        let mut place = Place {
+            var,
+            fields: Vec::new(),
+            span,
+        };
+        // a.b.c, 但 a.clone() 的 clone 不是字段
+        while *self.peek() == Tok::Dot
+            && matches!(self.peek_at(1), Tok::Ident(_) | Tok::Int)
+            && *self.peek_at(2) != Tok::LParen
+        {
+            self.bump();
+            let t = self.bump();
+            let (field, span) = match &t.tok {
+                Tok::Ident(name) => (name.clone(), t.span),
+                _ => (String::from("0"), t.span),
+            };
+            place.fields.push(field);
+            place.span = place.span.to(span);
+        }
+        Ok(place)
+    }
+
+    fn expr(&mut self) -> Result<Expr, Box<Diagnostic>> {
+        self.nested(Self::expr_inner)
+    }
+
+    fn expr_inner(&mut self) -> Result<Expr, Box<Diagnostic>> {
+        let start = self.span();
+        let kind = match self.peek().clone() {
+            Tok::Int => {
+                self.bump();
+                ExprKind::Int
+            }
+            Tok::Str(_) => {
+                self.bump();
+                // "..".to_string() 得到 String
+                if *self.peek() == Tok::Dot && *self.peek_at(2) == Tok::LParen {
+                    self.bump();
+                    self.ident()?;
+                    self.expect(&Tok::LParen)?;
+                    self.expect(&Tok::RParen)?;
+                    ExprKind::Call(Vec::new())
+                } else {
+                    ExprKind::Str
+                }
+            }
+            Tok::Amp => {
+                self.bump();
+                let mutable = self.eat(&Tok::Mut);
+                ExprKind::Borrow {
+                    mutable,
+                    place: self.place()?,
+                }
+            }
+            Tok::Ident(name) => match self.peek_at(1) {
+                // String::from(..), Vec::new()
+                Tok::PathSep => {
+                    while self.eat(&Tok::PathSep) || matches!(self.peek(), Tok::Ident(_)) {
+                        self.bump();
+                    }
+                    self.expect(&Tok::LParen)?;
+                    ExprKind::Call(self.args(&Tok::RParen)?)
+                }
+                Tok::LParen => {
+                    self.bump();
+                    self.bump();
+                    ExprKind::Call(self.args(&Tok::RParen)?)
+                }
+                // vec![..]
+                Tok::Bang => {
+                    self.bump();
+                    self.bump();
+                    let close = match self.bump().tok {
+                        Tok::LParen => Tok::RParen,
+                        Tok::Other('[') => Tok::Other(']'),
+                        _ => return Err(parse_error("expected `(` or `[`", self.prev_span())),
+                    };
+                    ExprKind::Call(self.args(&close)?)
+                }
+                Tok::LBrace if name.starts_with(char::is_uppercase) => {
+                    self.bump();
+                    self.bump();
+                    let mut fields = Vec::new();
+                    while !self.eat(&Tok::RBrace) {
+                        let (field, _) = self.ident()?;
+                        self.expect(&Tok::Colon)?;
+                        fields.push((field, self.expr()?));
+                        if !self.eat(&Tok::Comma) {
+                            self.expect(&Tok::RBrace)?;
+                            break;
+                        }
+                    }
+                    ExprKind::Struct { name, fields }
+                }
+                _ => {
+                    let place = self.place()?;
+                    if *self.peek() == Tok::Dot {
+                        self.bump();
+                        let (method, _) = self.ident()?;
+                        self.expect(&Tok::LParen)?;
+                        let args = self.args(&Tok::RParen)?;
+                        if args.is_empty()
+                            && matches!(method.as_str(), "clone" | "to_string" | "to_owned")
+                        {
+                            ExprKind::Clone(place)
+                        } else {
+                            ExprKind::Method {
+                                mutable: MUTATING_METHODS.contains(&method.as_str()),
+                                place,
+                                args,
+                            }
+                        }
+                    } else {
+                        ExprKind::Place(place)
+                    }
+                }
+            },
+            _ => return Err(self.unexpected("expression")),
+        };
+        Ok(Expr {
+            kind,
+            span: start.to(self.prev_span()),
+        })
+    }
+}
+
+// 格式字符串里内联的参数, 例如 "{a:?}" 中的 a. offset 是格式字符串内容在源码中的起始位置
+fn inline_args(format: &str, offset: usize) -> Vec<Expr> {
+    let mut args = Vec::new();
+    let bytes = format.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if format[i..].starts_with("{{") {
+            i += 2;
+            continue;
+        }
+        if bytes[i] == b'{' {
+            let start = i + 1;
+            let mut end = start;
+            while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+                end += 1;
+            }
+            let is_name = end > start && !bytes[start].is_ascii_digit();
+            if is_name && end < bytes.len() && matches!(bytes[end], b'}' | b':') {
+                let span = Span::new(offset + start, offset + end);
+                args.push(Expr {
+                    kind: ExprKind::Place(Place {
+                        var: format[start..end].to_string(),
+                        fields: Vec::new(),
+                        span,
+                    }),
+                    span,
+                });
+            }
+            i = end;
+            continue;
+        }
+        i += 1;
+    }
+    args
+}
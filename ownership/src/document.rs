@@ -0,0 +1,339 @@
+// 拥有文本, 同时给出借用它的摘录.
+//
+// dangle() 说明了函数不能返回指向局部 String 的引用;
+// generic_type_and_trait 里的 ImportantExcerpt<'a> 也不能比它借用的文本活得更久.
+// OwnedDocument 自己拥有 String, 摘录 (句子/段落) 以 &str 的形式返回, 生命周期绑定在 &self 上,
+// 所以文档被修改或 drop 之前, 借用检查器会保证这些 &str 已经不再使用.
+//
+// 如果需要把摘录存下来以后再用 (例如跨越一次编辑), 就用 Excerpt:
+// 它只记录字节区间和文档的版本号, 不借用文档. 之后通过 resolve 取回 &str,
+// 期间发生的编辑会被重放到区间上 (rebase): 编辑在摘录之前就平移, 在之后就不变, 与摘录重叠则摘录失效.
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExcerptKind {
+    Sentence,
+    Paragraph,
+    Custom,
+}
+
+// 不借用文档的摘录句柄
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Excerpt {
+    doc: u64,
+    revision: usize,
+    pub range: Range<usize>,
+    pub kind: ExcerptKind,
+}
+
+impl Excerpt {
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExcerptError {
+    // 句柄来自另一个文档
+    OtherDocument,
+    // 句柄的版本比文档还新
+    FutureRevision { revision: usize, current: usize },
+    // 句柄创建之后的某次编辑修改了它所指的文本
+    Invalidated { revision: usize },
+    OutOfBounds { index: usize, len: usize },
+    NotCharBoundary { index: usize },
+}
+
+impl fmt::Display for ExcerptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExcerptError::OtherDocument => write!(f, "excerpt belongs to another document"),
+            ExcerptError::FutureRevision { revision, current } => write!(
+                f,
+                "excerpt is from revision {} but the document is at revision {}",
+                revision, current
+            ),
+            ExcerptError::Invalidated { revision } => {
+                write!(
+                    f,
+                    "excerpt was invalidated by the edit at revision {}",
+                    revision
+                )
+            }
+            ExcerptError::OutOfBounds { index, len } => {
+                write!(f, "byte index {} is out of bounds of length {}", index, len)
+            }
+            ExcerptError::NotCharBoundary { index } => {
+                write!(f, "byte index {} is not a char boundary", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExcerptError {}
+
+// 一次编辑: 把 [start, start + removed) 替换成 inserted 个字节
+#[derive(Debug, Clone, Copy)]
+struct Edit {
+    start: usize,
+    removed: usize,
+    inserted: usize,
+}
+
+// Clone 出来的文档有新的 id: 两份文档之后的编辑历史各不相同, 原文档的句柄不能用在副本上.
+#[derive(Debug)]
+pub struct OwnedDocument {
+    id: u64,
+    text: String,
+    // edits[i] 把版本 i 变成版本 i + 1
+    edits: Vec<Edit>,
+    sentences: Vec<Range<usize>>,
+    paragraphs: Vec<Range<usize>>,
+}
+
+fn is_sentence_end(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '。' | '！' | '？')
+}
+
+// 去掉区间首尾的空白
+fn trimmed(text: &str, range: Range<usize>) -> Range<usize> {
+    let s = &text[range.clone()];
+    let start = range.start + (s.len() - s.trim_start().len());
+    let end = range.start + s.trim_end().len();
+    start..end.max(start)
+}
+
+// 句子以 . ! ? 。！？ 结尾, 英文标点后面必须是空白或文本结尾 (这样 3.14 不会被切开)
+fn index_sentences(text: &str) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        let ends = is_sentence_end(c)
+            && (!c.is_ascii() || next.is_none_or(char::is_whitespace))
+            && !next.is_some_and(is_sentence_end);
+        // 空行也结束一个句子, 标题之类没有句号的行不会和下一段连在一起
+        let blank_line = c == '\n'
+            && text[i + 1..]
+                .trim_start_matches([' ', '\t'])
+                .starts_with('\n');
+        if ends || blank_line {
+            let end = if ends { i + c.len_utf8() } else { i };
+            let range = trimmed(text, start..end);
+            if !range.is_empty() {
+                sentences.push(range);
+            }
+            start = end;
+        }
+    }
+    let range = trimmed(text, start..text.len());
+    if !range.is_empty() {
+        sentences.push(range);
+    }
+    sentences
+}
+
+// 段落之间用空行分隔
+fn index_paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            let range = trimmed(text, start..offset);
+            if !range.is_empty() {
+                paragraphs.push(range);
+            }
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    let range = trimmed(text, start..text.len());
+    if !range.is_empty() {
+        paragraphs.push(range);
+    }
+    paragraphs
+}
+
+impl Clone for OwnedDocument {
+    fn clone(&self) -> Self {
+        OwnedDocument {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            text: self.text.clone(),
+            edits: self.edits.clone(),
+            sentences: self.sentences.clone(),
+            paragraphs: self.paragraphs.clone(),
+        }
+    }
+}
+
+impl OwnedDocument {
+    pub fn new(text: impl Into<String>) -> Self {
+        let mut doc = OwnedDocument {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            text: text.into(),
+            edits: Vec::new(),
+            sentences: Vec::new(),
+            paragraphs: Vec::new(),
+        };
+        doc.reindex();
+        doc
+    }
+
+    fn reindex(&mut self) {
+        self.sentences = index_sentences(&self.text);
+        self.paragraphs = index_paragraphs(&self.text);
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    // 每次编辑版本号加 1
+    pub fn revision(&self) -> usize {
+        self.edits.len()
+    }
+
+    pub fn sentences(&self) -> impl Iterator<Item = &str> {
+        self.sentences.iter().map(|r| &self.text[r.clone()])
+    }
+
+    pub fn paragraphs(&self) -> impl Iterator<Item = &str> {
+        self.paragraphs.iter().map(|r| &self.text[r.clone()])
+    }
+
+    pub fn sentence(&self, i: usize) -> Option<&str> {
+        self.sentences.get(i).map(|r| &self.text[r.clone()])
+    }
+
+    pub fn paragraph(&self, i: usize) -> Option<&str> {
+        self.paragraphs.get(i).map(|r| &self.text[r.clone()])
+    }
+
+    fn handle(&self, range: Range<usize>, kind: ExcerptKind) -> Excerpt {
+        Excerpt {
+            doc: self.id,
+            revision: self.revision(),
+            range,
+            kind,
+        }
+    }
+
+    pub fn sentence_excerpt(&self, i: usize) -> Option<Excerpt> {
+        let range = self.sentences.get(i)?.clone();
+        Some(self.handle(range, ExcerptKind::Sentence))
+    }
+
+    pub fn paragraph_excerpt(&self, i: usize) -> Option<Excerpt> {
+        let range = self.paragraphs.get(i)?.clone();
+        Some(self.handle(range, ExcerptKind::Paragraph))
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<(), ExcerptError> {
+        for index in [range.start, range.end] {
+            if index > self.text.len() {
+                return Err(ExcerptError::OutOfBounds {
+                    index,
+                    len: self.text.len(),
+                });
+            }
+            if !self.text.is_char_boundary(index) {
+                return Err(ExcerptError::NotCharBoundary { index });
+            }
+        }
+        Ok(())
+    }
+
+    // 任意字节区间的摘录
+    pub fn excerpt(&self, range: Range<usize>) -> Result<Excerpt, ExcerptError> {
+        self.check_range(&range)?;
+        Ok(self.handle(range.start..range.end.max(range.start), ExcerptKind::Custom))
+    }
+
+    // 把句柄更新到当前版本
+    pub fn rebase(&self, excerpt: &Excerpt) -> Result<Excerpt, ExcerptError> {
+        if excerpt.doc != self.id {
+            return Err(ExcerptError::OtherDocument);
+        }
+        if excerpt.revision > self.revision() {
+            return Err(ExcerptError::FutureRevision {
+                revision: excerpt.revision,
+                current: self.revision(),
+            });
+        }
+        let mut range = excerpt.range.clone();
+        for (i, edit) in self.edits.iter().enumerate().skip(excerpt.revision) {
+            let edit_end = edit.start + edit.removed;
+            if edit_end <= range.start {
+                // 在摘录之前 (包括正好在开头插入): 平移.
+                // range 是 pub 的, 被改坏时 (例如 end < start) 平移可能溢出
+                let shift = |at: usize| {
+                    at.checked_sub(edit.removed)
+                        .and_then(|at| at.checked_add(edit.inserted))
+                        .ok_or(ExcerptError::OutOfBounds {
+                            index: at,
+                            len: self.text.len(),
+                        })
+                };
+                range = shift(range.start)?..shift(range.end)?;
+            } else if edit.start >= range.end {
+                // 在摘录之后 (包括正好在结尾插入): 不变
+            } else {
+                return Err(ExcerptError::Invalidated { revision: i + 1 });
+            }
+        }
+        Ok(Excerpt {
+            revision: self.revision(),
+            range,
+            ..excerpt.clone()
+        })
+    }
+
+    pub fn resolve(&self, excerpt: &Excerpt) -> Result<&str, ExcerptError> {
+        let range = self.rebase(excerpt)?.range;
+        // 句柄的 range 是 pub 的, 可能被改成了任意值
+        self.check_range(&range)?;
+        self.text
+            .get(range.clone())
+            .ok_or(ExcerptError::OutOfBounds {
+                index: range.start,
+                len: self.text.len(),
+            })
+    }
+
+    // 用 with 替换 range 里的文本, 之后重新划分句子和段落
+    pub fn replace_range(&mut self, range: Range<usize>, with: &str) -> Result<(), ExcerptError> {
+        self.check_range(&range)?;
+        let range = range.start..range.end.max(range.start);
+        self.edits.push(Edit {
+            start: range.start,
+            removed: range.len(),
+            inserted: with.len(),
+        });
+        self.text.replace_range(range, with);
+        self.reindex();
+        Ok(())
+    }
+
+    pub fn insert(&mut self, at: usize, text: &str) -> Result<(), ExcerptError> {
+        self.replace_range(at..at, text)
+    }
+
+    pub fn remove(&mut self, range: Range<usize>) -> Result<(), ExcerptError> {
+        self.replace_range(range, "")
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.insert(self.text.len(), text).unwrap();
+    }
+}
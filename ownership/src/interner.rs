@@ -0,0 +1,243 @@
+// 字符串驻留 (string interning).
+//
+// 相同内容的字符串只保存一份, 用一个 Symbol(u32) 代表它:
+// 比较两个 Symbol 只需要比较两个 u32, 通过 Symbol 取回 &str 也只是一次下标访问.
+//
+// 与 static_in_ref() 里的 &'static 对应, 有两种模式:
+// 1. Interner::new(): 字符串归 Interner 所有, resolve 返回的 &str 不能比 Interner 活得更久.
+// 2. Interner::leaking(): 字符串通过 Box::leak 泄漏, 一直存活到程序结束, 可以拿到 &'static str.
+//    适合数量有限, 整个程序都要用的字符串 (关键字, 标识符, 配置项的名字...).
+//
+// Interner 是线程安全的, 可以放在 static 里被多个线程共享, 见 global().
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+// 不同 Interner 产生的 Symbol 不能混用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    // 不同字符串的个数
+    pub symbols: usize,
+    // 这些字符串本身占用的字节数
+    pub string_bytes: usize,
+    // 哈希表和下标表大约占用的字节数
+    pub table_bytes: usize,
+    // intern 被调用的次数
+    pub requests: usize,
+    // 因为字符串已经驻留而省下的字节数
+    pub bytes_saved: usize,
+    pub leaking: bool,
+}
+
+// 驻留的字符串. 非 leaking 模式下来自 Box::into_raw, 由 Interner 在 drop 时通过这个指针释放;
+// leaking 模式下来自 Box::leak, 永远不释放. 两种情况下数据都不会被修改, 也不会因为
+// Vec 或 HashMap 扩容而移动.
+#[derive(Clone, Copy)]
+struct Stored(NonNull<str>);
+
+// 与 Box<str> 一样, 只是指向一块不可变的堆内存
+unsafe impl Send for Stored {}
+unsafe impl Sync for Stored {}
+
+impl Stored {
+    // 调用者保证返回的 &str 不会活过这块内存 (非 leaking 模式下即 Interner 本身)
+    unsafe fn as_str<'a>(self) -> &'a str {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Borrow<str> for Stored {
+    fn borrow(&self) -> &str {
+        // 借用 self 的 Stored 都在 Inner 里, 不会活过 Interner
+        unsafe { self.as_str() }
+    }
+}
+
+impl Hash for Stored {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // 必须与 str 的 Hash 一致, 才能用 &str 在 HashMap 里查找
+        <Self as Borrow<str>>::borrow(self).hash(state)
+    }
+}
+
+impl PartialEq for Stored {
+    fn eq(&self, other: &Self) -> bool {
+        <Self as Borrow<str>>::borrow(self) == <Self as Borrow<str>>::borrow(other)
+    }
+}
+
+impl Eq for Stored {}
+
+#[derive(Default)]
+struct Inner {
+    // 两张表里的 Stored 指向同一份数据
+    map: HashMap<Stored, Symbol>,
+    strings: Vec<Stored>,
+    string_bytes: usize,
+}
+
+pub struct Interner {
+    inner: RwLock<Inner>,
+    leaking: bool,
+    requests: AtomicUsize,
+    bytes_saved: AtomicUsize,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::with_mode(false)
+    }
+
+    pub fn leaking() -> Self {
+        Self::with_mode(true)
+    }
+
+    fn with_mode(leaking: bool) -> Self {
+        Interner {
+            inner: RwLock::new(Inner::default()),
+            leaking,
+            requests: AtomicUsize::new(0),
+            bytes_saved: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_leaking(&self) -> bool {
+        self.leaking
+    }
+
+    pub fn intern(&self, s: &str) -> Symbol {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        // 大多数情况下字符串已经驻留, 只需要读锁
+        if let Some(sym) = self.get(s) {
+            self.bytes_saved.fetch_add(s.len(), Ordering::Relaxed);
+            return sym;
+        }
+        let mut inner = self.inner.write().unwrap();
+        // 拿到写锁之前, 其他线程可能已经插入了同一个字符串
+        if let Some(&sym) = inner.map.get(s) {
+            self.bytes_saved.fetch_add(s.len(), Ordering::Relaxed);
+            return sym;
+        }
+        let sym = Symbol(u32::try_from(inner.strings.len()).expect("too many symbols"));
+        let boxed = s.to_owned().into_boxed_str();
+        let stored = if self.leaking {
+            Stored(NonNull::from(Box::leak(boxed)))
+        } else {
+            // 保留 into_raw 得到的指针, drop 时用它释放
+            Stored(unsafe { NonNull::new_unchecked(Box::into_raw(boxed)) })
+        };
+        inner.map.insert(stored, sym);
+        inner.strings.push(stored);
+        inner.string_bytes += s.len();
+        sym
+    }
+
+    // 只查找, 不插入
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        self.inner.read().unwrap().map.get(s).copied()
+    }
+
+    // Symbol 只是一个下标, 不记录来自哪个 Interner. 传入别的 Interner 产生的 Symbol 时,
+    // 下标越界会 panic, 没越界就返回这里同一下标的字符串, 结果没有意义 (但不是 UB)
+    pub fn resolve(&self, sym: Symbol) -> &str {
+        self.try_resolve(sym).expect("symbol index out of range")
+    }
+
+    pub fn try_resolve(&self, sym: Symbol) -> Option<&str> {
+        let stored = self
+            .inner
+            .read()
+            .unwrap()
+            .strings
+            .get(sym.index())
+            .copied()?;
+        // 返回的 &str 借用了 self, 字符串在 Interner drop 之前一直有效
+        Some(unsafe { stored.as_str() })
+    }
+
+    // 只有 leaking 模式下才能拿到 &'static str
+    pub fn resolve_static(&self, sym: Symbol) -> Option<&'static str> {
+        if !self.leaking {
+            return None;
+        }
+        let stored = self
+            .inner
+            .read()
+            .unwrap()
+            .strings
+            .get(sym.index())
+            .copied()?;
+        // leaking 模式下字符串来自 Box::leak, 永远不会释放
+        Some(unsafe { stored.as_str() })
+    }
+
+    // 驻留并直接返回 &'static str, 非 leaking 模式下返回 None
+    pub fn intern_static(&self, s: &str) -> Option<&'static str> {
+        let sym = self.intern(s);
+        self.resolve_static(sym)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> Stats {
+        let inner = self.inner.read().unwrap();
+        // HashMap 每个槽位还有 1 字节的控制信息
+        let map_bytes = inner.map.capacity() * (size_of::<(Stored, Symbol)>() + 1);
+        let vec_bytes = inner.strings.capacity() * size_of::<Stored>();
+        Stats {
+            symbols: inner.strings.len(),
+            string_bytes: inner.string_bytes,
+            table_bytes: map_bytes + vec_bytes,
+            requests: self.requests.load(Ordering::Relaxed),
+            bytes_saved: self.bytes_saved.load(Ordering::Relaxed),
+            leaking: self.leaking,
+        }
+    }
+}
+
+impl Drop for Interner {
+    fn drop(&mut self) {
+        if self.leaking {
+            return;
+        }
+        let inner = self.inner.get_mut().unwrap_or_else(|e| e.into_inner());
+        inner.map.clear();
+        for s in inner.strings.drain(..) {
+            // 这些指针都来自 intern() 里的 Box::into_raw, 而且此时已经没有别的引用了:
+            // resolve 返回的 &str 借用了 self, 不可能活过 drop.
+            unsafe { drop(Box::from_raw(s.0.as_ptr())) };
+        }
+    }
+}
+
+// 整个程序共享的 leaking Interner
+pub fn global() -> &'static Interner {
+    static GLOBAL: OnceLock<Interner> = OnceLock::new();
+    GLOBAL.get_or_init(Interner::leaking)
+}
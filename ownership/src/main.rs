@@ -1,3 +1,19 @@
+#[cfg(any(test, feature = "alloc-count"))]
+mod alloc_count;
+mod borrowck;
+mod document;
+mod interner;
+mod normalize;
+mod rope;
+mod slice_views;
+mod small_string;
+mod str_slice;
+
+// 跑测试时总是编译并注册 alloc_count, 以便单元测试里可以用 measure
+#[cfg(any(test, feature = "alloc-count"))]
+#[global_allocator]
+static GLOBAL: alloc_count::Counting = alloc_count::Counting;
+
 fn shallow_transfer_vs_deep_clone() {
     let s1 = String::from("s1");
     //shallow, 栈上数据被move
@@ -14,6 +30,85 @@ fn shallow_transfer_vs_deep_clone() {
     println!("s2: {}", s2);
 }
 
+#[cfg(feature = "alloc-count")]
+fn measure_transfer_vs_clone() {
+    use alloc_count::{measure, measure_with};
+
+    let s1 = String::from("hello");
+    // move 只复制栈上的 (ptr, len, capacity), 不分配堆内存
+    let (s2, stats) = measure_with(|| s1);
+    println!("move:  {:?}", stats);
+    assert_eq!(stats.allocations, 0);
+    assert_eq!(stats.bytes_allocated, 0);
+
+    // clone 还要复制堆上的 5 个字节
+    let (s3, stats) = measure_with(|| s2.clone());
+    println!("clone: {:?}", stats);
+    assert_eq!(stats.allocations, 1);
+    assert_eq!(stats.bytes_allocated, 5);
+    assert_eq!(stats.net(), 5);
+    assert_eq!(s2, s3);
+
+    // Copy 类型的数组完全在栈上
+    let a = [11, 22, 33, 44];
+    let stats = measure(|| {
+        let b = a;
+        println!("b: {:?}", b);
+    });
+    assert_eq!(stats.bytes_allocated, 0);
+
+    // 闭包里创建又释放的内存: net 为 0, peak 是最多时占用的字节数
+    let stats = measure(|| {
+        let v: Vec<u64> = Vec::with_capacity(100);
+        drop(v);
+        let v: Vec<u8> = vec![0; 10];
+        drop(v);
+    });
+    println!("temp:  {:?}", stats);
+    assert_eq!(stats.allocations, 2);
+    assert_eq!(stats.deallocations, 2);
+    assert_eq!(stats.net(), 0);
+    assert_eq!(stats.peak, 800);
+
+    // drop 闭包之前分配的内存, net 为负
+    let stats = measure(|| drop(s3));
+    assert_eq!(stats.net(), -5);
+    assert_eq!(stats.peak, 0);
+
+    // 嵌套: 内层的分配也计入外层
+    let outer = measure(|| {
+        let _a = String::from("abc");
+        let inner = measure(|| {
+            let _b = String::from("defg");
+        });
+        assert_eq!(inner.bytes_allocated, 4);
+        assert_eq!(inner.peak, 4);
+    });
+    assert_eq!(outer.bytes_allocated, 7);
+    assert_eq!(outer.peak, 7);
+
+    // 内层 panic 时, 外层的统计照样恢复, 内层 panic 之前的分配也计入外层
+    let outer = measure(|| {
+        let _a = vec![0u8; 10_000];
+        let result = std::panic::catch_unwind(|| {
+            measure(|| {
+                let _b = "b".repeat(100);
+                panic!("inside measure");
+            })
+        });
+        assert!(result.is_err());
+    });
+    assert!(outer.bytes_allocated >= 10_100);
+
+    // 其他线程的分配不计入
+    let stats = measure(|| {
+        std::thread::scope(|s| {
+            s.spawn(|| vec![0u8; 1000]);
+        });
+    });
+    assert!(stats.bytes_allocated < 1000);
+}
+
 fn shallow_copy_vs_shallow_transfer() {
     //如果一个类型实现了 Copy trait，那么一个旧的变量在将其赋值给其他变量后仍然可用。
     // Rust 不允许自身或其任何部分实现了 Drop trait 的类型实现 Copy trait。
@@ -83,6 +178,139 @@ fn no_dangle() -> String {
 //ref 是 ordinary pointer;
 //slice 是 two-word pointer.
 
+fn use_owned_document() {
+    use document::{ExcerptError, ExcerptKind, OwnedDocument};
+
+    let text = "Call me Ishmael. Some years ago, never mind how long precisely, \
+                I thought I would sail about a little.\n\n\
+                Rust 的所有权规则很简单。每个值都有一个 owner！\n\n\
+                pi is 3.14. Done";
+    let mut doc = OwnedDocument::new(text);
+
+    // 摘录是借用 doc 的 &str, 不复制数据
+    let first: &str = doc.sentence(0).unwrap();
+    assert_eq!(first, "Call me Ishmael.");
+    assert_eq!(first.as_ptr(), doc.text().as_ptr());
+    assert_eq!(
+        doc.sentences().collect::<Vec<_>>(),
+        [
+            "Call me Ishmael.",
+            "Some years ago, never mind how long precisely, I thought I would sail about a little.",
+            "Rust 的所有权规则很简单。",
+            "每个值都有一个 owner！",
+            "pi is 3.14.",
+            "Done",
+        ]
+    );
+    assert_eq!(doc.paragraphs().count(), 3);
+    assert_eq!(doc.paragraph(2), Some("pi is 3.14. Done"));
+
+    // // 与 dangle() 一样, 摘录不能比 doc 活得更久
+    // // cannot move out of `doc` because it is borrowed [E0505]
+    // let first = doc.sentence(0).unwrap();
+    // drop(doc);
+    // println!("{}", first);
+
+    // // 借用着摘录的时候不能修改 doc
+    // // cannot borrow `doc` as mutable because it is also borrowed as immutable [E0502]
+    // let first = doc.sentence(0).unwrap();
+    // doc.push_str("!");
+    // println!("{}", first);
+
+    // Excerpt 不借用 doc, 可以先存起来, 修改 doc 之后再取回
+    let ishmael = doc.sentence_excerpt(0).unwrap();
+    let owner = doc.sentence_excerpt(3).unwrap();
+    let rust = doc.paragraph_excerpt(1).unwrap();
+    assert_eq!(rust.kind, ExcerptKind::Paragraph);
+    let pi = doc
+        .excerpt(text.find("3.14").unwrap()..text.find(". Done").unwrap())
+        .unwrap();
+    assert_eq!(doc.resolve(&pi), Ok("3.14"));
+
+    doc.insert(0, "Chapter 1.\n\n").unwrap();
+    doc.push_str(".");
+    assert_eq!(doc.revision(), 2);
+    assert_eq!(doc.sentence(0), Some("Chapter 1."));
+    assert_eq!(doc.paragraphs().count(), 4);
+    assert_eq!(doc.resolve(&ishmael), Ok("Call me Ishmael."));
+    assert_eq!(doc.resolve(&owner), Ok("每个值都有一个 owner！"));
+    assert_eq!(doc.resolve(&pi), Ok("3.14"));
+
+    // 修改了摘录中的文本, 摘录失效
+    let at = doc.text().find("Ishmael").unwrap();
+    doc.replace_range(at..at + "Ishmael".len(), "Bob").unwrap();
+    assert_eq!(doc.sentence(1), Some("Call me Bob."));
+    assert_eq!(
+        doc.resolve(&ishmael),
+        Err(ExcerptError::Invalidated { revision: 3 })
+    );
+    // 其他摘录不受影响, rebase 后的句柄可以代替旧的保存
+    let rust = doc.rebase(&rust).unwrap();
+    assert_eq!(rust.revision(), 3);
+    assert_eq!(
+        doc.resolve(&rust),
+        Ok("Rust 的所有权规则很简单。每个值都有一个 owner！")
+    );
+
+    // 来自其他文档的句柄, 越界和不在 char 边界上的区间都会报错
+    let other = OwnedDocument::new("Call me Ishmael.");
+    assert_eq!(other.resolve(&owner), Err(ExcerptError::OtherDocument));
+    assert!(doc.excerpt(0..10_000).is_err());
+    let at = doc.text().find('所').unwrap();
+    assert_eq!(
+        doc.excerpt(at + 1..at + 3),
+        Err(ExcerptError::NotCharBoundary { index: at + 1 })
+    );
+    // 句柄的 range 被改坏了也只是返回错误
+    let mut bad = rust.clone();
+    bad.range = at + 1..at + 3;
+    assert_eq!(
+        doc.resolve(&bad),
+        Err(ExcerptError::NotCharBoundary { index: at + 1 })
+    );
+    bad.range = 0..10_000;
+    assert!(matches!(
+        doc.resolve(&bad),
+        Err(ExcerptError::OutOfBounds { .. })
+    ));
+    // 平移旧版本句柄时也不会溢出
+    let mut small = OwnedDocument::new("abc");
+    let mut old = small.excerpt(2..3).unwrap();
+    small.replace_range(0..1, "").unwrap();
+    let (start, end) = (2, 0);
+    old.range = start..end;
+    assert!(matches!(
+        small.resolve(&old),
+        Err(ExcerptError::OutOfBounds { .. })
+    ));
+    let mut old = small.excerpt(1..2).unwrap();
+    small.replace_range(0..0, "xyz").unwrap();
+    old.range = usize::MAX - 1..usize::MAX;
+    assert!(matches!(
+        small.resolve(&old),
+        Err(ExcerptError::OutOfBounds { .. })
+    ));
+
+    // clone 出来的副本是另一个文档, 两边各自编辑之后句柄不能混用
+    let mut copy = doc.clone();
+    assert_eq!(copy.text(), doc.text());
+    assert_eq!(copy.resolve(&rust), Err(ExcerptError::OtherDocument));
+    copy.push_str(" The end.");
+    let end = copy.sentence_excerpt(copy.sentences().count() - 1).unwrap();
+    assert_eq!(copy.resolve(&end), Ok("The end."));
+    let len = copy.text().len();
+    copy.remove(len - " The end.".len()..len).unwrap();
+    assert_eq!(copy.text(), doc.text());
+    assert!(matches!(
+        copy.resolve(&end),
+        Err(ExcerptError::Invalidated { .. })
+    ));
+    assert_eq!(doc.resolve(&end), Err(ExcerptError::OtherDocument));
+
+    let text: String = doc.into_string();
+    println!("{}", text);
+}
+
 fn use_shared_slice() {
     let mut a = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
     let b = &a[5..]; //b is &[i32]
@@ -111,6 +339,133 @@ fn use_mutable_slice() {
     println!("a: {:?}", a);
 }
 
+fn parallel_slice_views() {
+    use slice_views::*;
+    use std::thread;
+
+    // 固定大小的块, 每个线程处理一块
+    let mut a: Vec<i32> = (0..10).collect();
+    thread::scope(|s| {
+        for chunk in chunks(&mut a, 3).unwrap() {
+            s.spawn(move || chunk.iter_mut().for_each(|x| *x *= 10));
+        }
+    });
+    assert_eq!(a, [0, 10, 20, 30, 40, 50, 60, 70, 80, 90]);
+
+    // // chunks 返回的视图借用了 a, 在它们用完之前不能再使用 a
+    // // cannot borrow `a` as immutable because it is also borrowed as mutable [E0502]
+    // let views = chunks(&mut a, 3).unwrap();
+    // println!("a: {:?}", a);
+    // views[0][0] = 1;
+
+    // 平均分给 4 个线程, 各自求和后写回第一个元素
+    let mut a: Vec<u64> = (1..=10).collect();
+    let parts = split_even(&mut a, 4).unwrap();
+    assert_eq!(
+        parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+        [3, 3, 2, 2]
+    );
+    thread::scope(|s| {
+        for part in parts {
+            s.spawn(move || part[0] = part.iter().sum());
+        }
+    });
+    assert_eq!(a, [6, 2, 3, 15, 5, 6, 15, 8, 19, 10]);
+
+    let mut a = [1, 2, 3, 4, 5];
+    let parts = split_at_indices(&mut a, &[1, 1, 4]).unwrap();
+    assert_eq!(
+        parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+        [1, 0, 3, 1]
+    );
+    assert_eq!(
+        split_at_indices(&mut a, &[3, 2]).unwrap_err(),
+        ViewError::BadSplit { index: 2, len: 5 }
+    );
+    assert_eq!(chunks(&mut a, 0).unwrap_err(), ViewError::ZeroSize);
+
+    // 按谓词: 连续相同的值一组, 每组各自排序; 以 0 为分隔符切开
+    let mut a = [3, 1, 2, -5, -4, -6, 9, 8];
+    for run in split_runs(&mut a, |x, y| (*x < 0) == (*y < 0)) {
+        run.sort();
+    }
+    assert_eq!(a, [1, 2, 3, -6, -5, -4, 8, 9]);
+    let mut a = [1, 2, 0, 3, 0, 4, 5, 6];
+    thread::scope(|s| {
+        for part in split_by(&mut a, |x| *x == 0) {
+            s.spawn(move || part.reverse());
+        }
+    });
+    assert_eq!(a, [2, 1, 0, 3, 0, 6, 5, 4]);
+
+    // 跨步: 偶数下标和奇数下标分给两个线程
+    let mut a = [0; 9];
+    thread::scope(|s| {
+        for mut view in strided(&mut a, 2).unwrap() {
+            s.spawn(move || {
+                let k = view.offset() as i32 + 1;
+                view.iter_mut().for_each(|x| *x = k);
+            });
+        }
+    });
+    assert_eq!(a, [1, 2, 1, 2, 1, 2, 1, 2, 1]);
+    let mut views = strided(&mut a, 4).unwrap();
+    let last = &mut views[3];
+    assert_eq!((last.stride(), last.len(), last.is_empty()), (4, 2, false));
+    *last.get_mut(1).unwrap() = 0;
+    assert_eq!(last.get(1), Some(&0));
+    assert_eq!(last.iter().copied().collect::<Vec<_>>(), [2, 0]);
+    assert_eq!(a[7], 0);
+
+    // 二维: 3 行 4 列的行优先数组
+    let mut grid: Vec<i32> = (0..12).collect();
+    for row in rows(&mut grid, 4).unwrap() {
+        row.reverse();
+    }
+    assert_eq!(grid, [3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8]);
+    thread::scope(|s| {
+        for column in columns(&mut grid, 4).unwrap() {
+            s.spawn(move || {
+                for x in column {
+                    *x = -*x;
+                }
+            });
+        }
+    });
+    assert_eq!(grid[..4], [-3, -2, -1, 0]);
+    assert_eq!(
+        rows(&mut grid, 5).unwrap_err(),
+        ViewError::NotRectangular { len: 12, width: 5 }
+    );
+
+    // 5 x 7 的图像切成 3 x 2 的块, 每个线程把自己的块涂上编号
+    let (width, height) = (7, 5);
+    let mut image = vec![0u8; width * height];
+    let tiles = tiles(&mut image, width, 3, 2).unwrap();
+    assert_eq!(tiles.len(), 9);
+    assert_eq!((tiles[2].origin(), tiles[2].width()), ((0, 6), 1));
+    assert_eq!(tiles[8].height(), 1);
+    let mut tiles = tiles;
+    *tiles[4].get_mut(1, 1).unwrap() = b'!';
+    tiles[4].row_mut(0).unwrap().fill(b'?');
+    assert_eq!(tiles[4].get(1, 1), Some(&b'!'));
+    assert_eq!(tiles[4].get(2, 0), None);
+    thread::scope(|s| {
+        for (i, mut tile) in tiles.into_iter().enumerate() {
+            s.spawn(move || {
+                for (_, px) in tile.iter_mut() {
+                    *px = b'a' + i as u8;
+                }
+            });
+        }
+    });
+    for row in image.chunks(width) {
+        println!("{}", String::from_utf8_lossy(row));
+    }
+    assert_eq!(&image[..width], b"aaabbbc");
+    assert_eq!(&image[4 * width..], b"ggghhhi");
+}
+
 fn str_() {
     //&str 底层是 &[u8].
     // 但是字符串 slice range 的索引必须位于有效的 UTF-8 字符边界内，
@@ -126,6 +481,54 @@ fn str_() {
     // println!("&s[..1]{}", &s[..1]);
 }
 
+fn safe_str_slice() {
+    use str_slice::*;
+
+    let s = String::from("中国");
+    // &s[..1] 会 panic, try_slice 则返回错误和最近的合法边界
+    let err = try_slice(&s, ..1).unwrap_err();
+    assert_eq!(
+        err,
+        SliceError::NotCharBoundary {
+            index: 1,
+            floor: 0,
+            ceil: 3
+        }
+    );
+    println!("try_slice(\"中国\", ..1): {}", err);
+    assert_eq!(try_slice(&s, ..3), Ok("中"));
+    assert_eq!(try_slice(&s, 3..), Ok("国"));
+    assert_eq!(
+        try_slice(&s, ..7),
+        Err(SliceError::OutOfBounds { index: 7, len: 6 })
+    );
+    let (start, end) = (3, 0);
+    assert_eq!(
+        try_slice(&s, start..end),
+        Err(SliceError::Inverted { start: 3, end: 0 })
+    );
+    assert_eq!(
+        try_slice(&s, ..=usize::MAX),
+        Err(SliceError::OutOfBounds {
+            index: usize::MAX,
+            len: 6
+        })
+    );
+    use std::ops::Bound;
+    assert!(try_slice(&s, (Bound::Excluded(usize::MAX), Bound::Unbounded)).is_err());
+    assert_eq!(char_slice(&s, ..1), Ok("中"));
+    assert_eq!(char_slice(&s, 1..=1), Ok("国"));
+    assert_eq!(char_slice(&s, 2..), Ok(""));
+    assert!(char_slice(&s, ..3).is_err());
+
+    // 按字素簇切分和截断, 更多例子 (CJK, emoji, 组合字符) 见 str_slice 的单元测试
+    let s = "a👍🏽b";
+    assert_eq!(char_slice(s, 1..2), Ok("👍"));
+    assert_eq!(grapheme_slice(s, 1..2), Ok("👍🏽"));
+    assert_eq!(truncate_bytes(s, 7), "a👍");
+    assert_eq!(truncate_graphemes(s, 7), "a");
+}
+
 fn bad_signature(s: &String) {
     println!("s: {}", s);
 }
@@ -134,6 +537,115 @@ fn good_signature(s: &str) {
     println!("s: {}", s);
 }
 
+fn use_small_string() {
+    use small_string::{SmallString, INLINE_CAP};
+    use std::collections::{BTreeSet, HashMap};
+    use std::fmt::Write;
+
+    // 短字符串存在栈上
+    let s = SmallString::from("u1");
+    assert!(s.is_inline());
+    assert_eq!(s, "u1");
+    // Deref<Target = str>: 可以直接调用 str 的方法, 也可以传给 good_signature(&str)
+    assert_eq!(s.len(), 2);
+    assert!(s.starts_with('u'));
+    good_signature(&s);
+
+    // 正好 23 个字节还在栈上, 中文按字节计
+    let s = SmallString::from("a".repeat(INLINE_CAP).as_str());
+    assert!(s.is_inline());
+    let s = SmallString::from("八个汉字就超过了");
+    assert!(!s.is_inline());
+    assert_eq!(s.len(), 24);
+
+    // push_str 超过 23 字节时 spill 到堆上
+    let mut s = SmallString::from("hello");
+    s.push_str(", ");
+    s.push('w');
+    s.push_str("orld");
+    assert!(s.is_inline());
+    s.push_str(", from SmallString");
+    assert!(!s.is_inline());
+    assert_eq!(s, "hello, world, from SmallString");
+
+    // From<String> 直接接管长字符串的堆内存
+    let long = String::from("this string is longer than 23 bytes");
+    let ptr = long.as_ptr();
+    let s = SmallString::from(long);
+    assert_eq!(s.as_ptr(), ptr);
+    let back = String::from(s);
+    assert_eq!(back.as_ptr(), ptr);
+
+    // Display, Debug, Write
+    let mut s = SmallString::new();
+    write!(s, "hi-{}", 42).unwrap();
+    assert_eq!(format!("{} {:?} {:>6}", s, s, s), "hi-42 \"hi-42\"  hi-42");
+
+    // Hash 和 Ord: 可以作为 HashMap 的 key (并用 &str 查找), 放进 BTreeSet 排序
+    let mut counts: HashMap<SmallString, usize> = HashMap::new();
+    for word in "from hi from u1 hi from".split(' ') {
+        *counts.entry(word.into()).or_default() += 1;
+    }
+    assert_eq!(counts["from"], 3);
+    assert_eq!(counts.get("hi"), Some(&2));
+    let sorted: BTreeSet<SmallString> = counts.into_keys().collect();
+    assert_eq!(
+        sorted.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+        ["from", "hi", "u1"]
+    );
+    let (short, long) = (SmallString::from("a"), SmallString::from("a".repeat(30)));
+    assert!(short.is_inline() && !long.is_inline() && short < long);
+    assert_eq!(SmallString::from('中'), "中");
+    assert_eq!("abc".chars().rev().collect::<SmallString>(), "cba");
+
+    println!(
+        "size_of::<SmallString>() = {}",
+        std::mem::size_of::<SmallString>()
+    );
+}
+
+#[cfg(feature = "alloc-count")]
+fn bench_small_string() {
+    use small_string::SmallString;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    const N: usize = 1_000_000;
+    let words = [
+        "u1",
+        "hi",
+        "from",
+        "someone@example.com",
+        "a string longer than 23 bytes",
+    ];
+
+    let start = Instant::now();
+    let stats = alloc_count::measure(|| {
+        for i in 0..N {
+            black_box(String::from(words[i % words.len()]));
+        }
+    });
+    println!(
+        "String:      {:?} ({} allocations)",
+        start.elapsed(),
+        stats.allocations
+    );
+
+    let start = Instant::now();
+    let stats = alloc_count::measure(|| {
+        for i in 0..N {
+            black_box(SmallString::from(words[i % words.len()]));
+        }
+    });
+    println!(
+        "SmallString: {:?} ({} allocations)",
+        start.elapsed(),
+        stats.allocations
+    );
+    // 5 个词里只有最后一个超过 23 字节
+    assert_eq!(stats.allocations, N / words.len());
+}
+
 fn str_vs_String() {
     //如果有一个字符串 slice，可以直接传递它。
     // 如果有一个 String，则可以传递整个 String 的 slice 或对 String 的引用。
@@ -147,6 +659,350 @@ fn str_vs_String() {
     let slice: &str = &s;
 }
 
+fn normalize_with_cow() {
+    use normalize::{normalize, trim, CaseFold, Normalizer};
+    use std::borrow::Cow;
+
+    let is_borrowed = |s: &Cow<str>| matches!(s, Cow::Borrowed(_));
+
+    // 已经规范的输入: 直接借用, 不分配内存
+    let s = normalize("hello, world");
+    assert!(is_borrowed(&s));
+    assert_eq!(s, "hello, world");
+    let s = normalize("  café 中文 ");
+    assert!(is_borrowed(&s));
+    assert_eq!(s, "café 中文");
+
+    // 只需要 trim 的输入返回子串, 同样不分配
+    let s = trim(Cow::Borrowed("\t hi \n"));
+    assert!(is_borrowed(&s));
+    assert_eq!(s, "hi");
+
+    // 需要修改的输入: 每个真正修改了字符串的步骤分配一次, 这里是大小写和空白两步
+    let s = normalize("  Hello,\t\n  WORLD  ");
+    assert_eq!(s, "hello, world");
+    assert!(!is_borrowed(&s));
+
+    // 组合字符序列合成
+    let s = Normalizer::new()
+        .compose(true)
+        .normalize("cafe\u{301} nin\u{303}o");
+    assert_eq!(s, "café niño");
+    assert_eq!(s.chars().count(), 9);
+    // 不认识的组合不变
+    let s = Normalizer::new().compose(true).normalize("x\u{301}");
+    assert!(is_borrowed(&s));
+
+    // 全角转半角
+    let s = Normalizer::new()
+        .halfwidth(true)
+        .normalize("你好！（ＲＵＳＴ）　１２３。「引用」、");
+    assert_eq!(s, "你好!(RUST) 123｡｢引用｣､");
+
+    // ASCII 大小写折叠不碰非 ASCII 字符
+    let ascii = Normalizer::new().case(CaseFold::Ascii);
+    let unicode = Normalizer::new().case(CaseFold::Unicode);
+    assert_eq!(ascii.normalize("ÉCOLE Rust"), "École rust");
+    assert_eq!(unicode.normalize("ÉCOLE Rust"), "école rust");
+    assert_eq!(unicode.normalize("ΣΑΣ"), "σασ");
+    assert!(is_borrowed(&unicode.normalize("straße")));
+
+    // 空白
+    let ws = Normalizer::new().collapse_whitespace(true);
+    assert!(is_borrowed(&ws.normalize("a b c")));
+    assert_eq!(ws.normalize("a  b\tc\u{3000}d"), "a b c d");
+    let trimmed = Normalizer::new().trim(true).normalize("  a b  ");
+    assert_eq!(trimmed, "a b");
+    assert!(is_borrowed(&trimmed));
+
+    // 全部步骤
+    let s = normalize("　ＨＥＬＬＯ，  Cafe\u{301}！ ");
+    assert_eq!(s, "hello, café!");
+    println!("normalized: {:?}", s);
+}
+
+fn use_rope() {
+    use rope::Rope;
+
+    let mut rope = Rope::from("hello world");
+    rope.insert(5, ",").unwrap();
+    rope.insert(12, "!").unwrap();
+    assert_eq!(rope, "hello, world!");
+    rope.remove(..7).unwrap();
+    assert_eq!(rope, "world!");
+    assert!(rope.insert(100, "x").is_err());
+
+    // 位置都按 char 计, 不会切断多字节字符
+    let mut rope = Rope::from("中国");
+    rope.insert(1, "华人民共和").unwrap();
+    assert_eq!(rope, "中华人民共和国");
+    assert_eq!(rope.len_chars(), 7);
+    assert_eq!(rope.len_bytes(), 21);
+    assert_eq!(rope.char(6), Some('国'));
+    assert_eq!(rope.slice(1..3).unwrap(), "华人");
+    let (start, end) = (5, 2);
+    assert_eq!(
+        rope.slice(start..end),
+        Err(rope::RangeError::Inverted { start: 5, end: 2 })
+    );
+    assert!(rope.slice(..=usize::MAX).is_err());
+    assert!(rope.remove(8..).is_err());
+
+    // 行和列
+    let rope = Rope::from("fn main() {\n    println!(\"中文\");\n}\n");
+    assert_eq!(rope.len_lines(), 4);
+    assert_eq!(rope.line_to_char(1), Some(12));
+    assert_eq!(rope.line_col_to_char(1, 4), Some(16));
+    assert_eq!(rope.char(16), Some('p'));
+    assert_eq!(rope.char_to_line_col(16), Ok((1, 4)));
+    assert_eq!(rope.line_col_to_char(2, 1), Some(rope.len_chars() - 1));
+    assert_eq!(rope.line_col_to_char(2, 2), None);
+    assert_eq!(rope.line_col_to_char(1, usize::MAX), None);
+    assert_eq!(rope.line_col_to_char(usize::MAX, 0), None);
+    assert_eq!(rope.char(usize::MAX), None);
+    assert_eq!(rope.line_col_to_char(3, 0), Some(rope.len_chars()));
+    assert_eq!(rope.char_to_line_col(rope.len_chars()), Ok((3, 0)));
+    assert_eq!(rope.line_to_char(4), None);
+
+    // 大文本: 树是平衡的, chunks 不复制数据
+    let line = "Rust 所有权: 每个值都有一个 owner.\n";
+    let text = line.repeat(100_000);
+    let mut rope = Rope::from(text.as_str());
+    println!(
+        "{} bytes, {} lines, height {}",
+        rope.len_bytes(),
+        rope.len_lines(),
+        rope.height()
+    );
+    assert!(rope.height() <= 20);
+    assert_eq!(rope.chunks().map(str::len).sum::<usize>(), text.len());
+
+    // 用按行存放的 Vec<String> 对照
+    let mut expected: Vec<String> = text.lines().map(String::from).collect();
+    for i in 0..1000 {
+        let line_no = (i * 7919) % 100_000;
+        let at = rope.line_col_to_char(line_no, 5).unwrap();
+        rope.insert(at, "ownership ").unwrap();
+        expected[line_no].insert_str(5, "ownership ");
+    }
+    let expected = expected.join("\n") + "\n";
+    assert_eq!(String::from(&rope), expected);
+    assert!(rope.height() <= 30);
+
+    let mut tail = rope.clone();
+    let head = {
+        let mut head = tail.clone();
+        tail = head.split_off(10).unwrap();
+        head
+    };
+    let mut joined = head.clone();
+    joined.append(tail);
+    assert_eq!(joined, rope);
+    assert_eq!(head.to_string(), "Rust owner");
+
+    let len = rope.len_chars();
+    rope.remove(10..len - 10).unwrap();
+    assert_eq!(rope.len_chars(), 20);
+    println!("{:?}", rope);
+}
+
+fn bench_rope() {
+    use rope::Rope;
+    use std::time::Instant;
+
+    // 4 MB ASCII 文本, char 下标和字节下标相同, 可以直接用 String::insert_str
+    let text = "the quick brown fox jumps over the lazy dog\n".repeat(100_000);
+    const N: usize = 2_000;
+    let positions: Vec<usize> = (0..N).map(|i| (i * 2_654_435_761) % text.len()).collect();
+
+    let start = Instant::now();
+    let mut s = text.clone();
+    for &at in &positions {
+        s.insert_str(at, "inserted");
+    }
+    println!("String::insert_str: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let mut rope = Rope::from(text.as_str());
+    for &at in &positions {
+        rope.insert(at, "inserted").unwrap();
+    }
+    println!("Rope::insert:       {:?}", start.elapsed());
+
+    assert_eq!(rope, s.as_str());
+}
+
+fn toy_borrowck() {
+    use borrowck::{check, error_codes};
+
+    // shallow_transfer_vs_deep_clone() 里注释掉的代码
+    let source = r#"
+let s1 = String::from("s1");
+let s2 = s1;
+println!("s1: {}", s1);
+"#;
+    let errors = check(source).unwrap_err();
+    assert_eq!(errors[0].code, Some("E0382"));
+    assert_eq!(errors[0].message, "borrow of moved value: `s1`");
+    println!("{}\n", errors[0].render(source));
+
+    // clone 之后 s1 仍然可用; Copy 类型赋值后旧变量仍然可用
+    assert_eq!(
+        check(
+            r#"
+let s1 = String::from("s1");
+let s2 = s1.clone();
+println!("s1: {}", s1);
+let a = 10;
+let b = a;
+println!("a: {}", a);
+let c = "literal";
+let d = c;
+println!("{c} {d}");
+"#
+        ),
+        Ok(())
+    );
+
+    // shared_ref_vs_mutable_ref()
+    let source = r#"
+let mut s1 = String::from("s1");
+let p1_s1 = &mut s1;
+let p2_s1 = &mut s1;
+println!("p1_s1: {:?}", p1_s1);
+"#;
+    let errors = check(source).unwrap_err();
+    assert_eq!(errors[0].code, Some("E0499"));
+    println!("{}\n", errors[0].render(source));
+
+    // NLL: p1_s1 之后不再使用, 第二个 &mut 没有问题
+    let nll = r#"
+let mut s1 = String::from("s1");
+let p1_s1 = &mut s1;
+println!("{}", p1_s1);
+let p2_s1 = &mut s1;
+println!("{}", p2_s1);
+println!("{}", s1);
+"#;
+    assert_eq!(check(nll), Ok(()));
+
+    let source = r#"
+let mut s1 = String::from("s1");
+let p1_s1 = &mut s1;
+let p2_s1 = p1_s1;
+println!("s1: {:?}", s1);
+println!("p1_s1: {:?}", p1_s1);
+println!("p2_s1: {:?}", p2_s1);
+"#;
+    assert_eq!(error_codes(source), ["E0502", "E0382"]);
+    for e in check(source).unwrap_err() {
+        println!("{}\n", e.render(source));
+    }
+
+    // 引用本身可以重新指向别的数据
+    let source = r#"
+let s1 = String::from("s1");
+let s2 = String::from("s2");
+let mut p = &s1;
+println!("p: {:?}", p);
+p = &s2;
+println!("p: {:?}", p);
+"#;
+    assert_eq!(check(source), Ok(()));
+
+    // use_shared_slice() 的两种顺序
+    let ok = "let mut a = vec![0, 1, 2]; let b = &a; println!(\"{:?}\", b); a.push(3);";
+    let err = "let mut a = vec![0, 1, 2]; let b = &a; a.push(3); println!(\"{:?}\", b);";
+    assert_eq!(check(ok), Ok(()));
+    assert_eq!(error_codes(err), ["E0502"]);
+
+    // partial_move()
+    let source = r#"
+#[derive(Debug)]
+struct Droppable {
+    int_field: i32,
+    string_field1: String,
+    string_field2: String,
+}
+
+let a = Droppable {
+    int_field: 0,
+    string_field1: "".to_string(),
+    string_field2: "".to_string(),
+};
+let i = a.int_field;
+println!("{a:?}");
+let s = a.string_field1;
+println!("{a:?}");
+println!("{:?}", a.int_field);
+println!("{:?}", a.string_field2);
+println!("{:?}", a.string_field1);
+"#;
+    let errors = check(source).unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].message, "borrow of partially moved value: `a`");
+    assert_eq!(
+        errors[1].message,
+        "borrow of moved value: `a.string_field1`"
+    );
+    for e in &errors {
+        println!("{}\n", e.render(source));
+    }
+
+    // what_mutable_mean() 和其他错误码
+    let cases = [
+        ("let a = vec![1]; a.clear();", "E0596"),
+        ("let a = vec![1]; a = vec![2];", "E0384"),
+        ("let x; let y = x;", "E0381"),
+        ("let x; x = 1; let y = x;", ""),
+        (
+            "let mut x = 1; let r = &mut x; let y = x; println!(\"{}\", r);",
+            "E0503",
+        ),
+        (
+            "let s = String::new(); let r = &s; drop(s); println!(\"{}\", r);",
+            "E0505",
+        ),
+        (
+            "let mut s = String::new(); let r = &s; s = String::new(); use(r);",
+            "E0506",
+        ),
+        (
+            "let r; { let s = String::new(); r = &s; } println!(\"{}\", r);",
+            "E0597",
+        ),
+        (
+            "let s = String::new(); { let r = &s; println!(\"{}\", r); } drop(s);",
+            "",
+        ),
+        ("let mut s = String::new(); f(&mut s, &s);", "E0502"),
+        ("let d = D { f: 1 }; d.f = 2;", "E0594"),
+        (
+            "let mut d = D { s: String::new() }; let t = d; d.s = String::new();",
+            "E0382",
+        ),
+        (
+            "let mut d = D { s: String::new() }; let t = d.s; d.s = String::new(); use(d);",
+            "",
+        ),
+        ("println!(\"{}\", nope);", "E0425"),
+    ];
+    for (source, code) in cases {
+        let codes = error_codes(source);
+        assert_eq!(codes.first().copied().unwrap_or(""), code, "{}", source);
+    }
+
+    // 语法错误
+    let errors = check("let = 5;").unwrap_err();
+    assert_eq!(errors[0].code, None);
+    println!("{}", errors[0].render("let = 5;"));
+    // 嵌套过深也是语法错误, 而不是栈溢出
+    for deep in ["{".repeat(100_000), "f(".repeat(100_000)] {
+        let errors = check(&deep).unwrap_err();
+        assert_eq!(errors[0].code, None);
+    }
+}
+
 fn what_mutable_mean() {
     //shared or mutable 都是编译期要处理的逻辑, 被编译好的二进制可执行文件中是不包含shared or mutable的信息的
 
@@ -251,7 +1107,103 @@ fn static_in_ref() {
     // println!("static_string reference remains alive: {}", static_string);
 }
 
+fn use_interner() {
+    use interner::{global, Interner};
+
+    let interner = Interner::new();
+    assert!(interner.is_empty() && !interner.is_leaking());
+    let a = interner.intern("hello");
+    let b = interner.intern("world");
+    // 相同内容的字符串得到同一个 Symbol, 比较时只比较 u32
+    let c = interner.intern(&String::from("hello"));
+    assert_eq!(a, c);
+    assert_ne!(a, b);
+    assert_eq!(interner.resolve(a), "hello");
+    assert_eq!(interner.get("world"), Some(b));
+    assert_eq!(interner.get("rust"), None);
+    assert_eq!(interner.len(), 2);
+    // 非 leaking 模式拿不到 &'static str
+    assert_eq!(interner.resolve_static(a), None);
+
+    // 已经驻留的字符串再 intern 和 resolve 都不分配内存
+    let lookup = || {
+        assert_eq!(interner.intern("hello"), a);
+        assert_eq!(interner.resolve(b), "world");
+    };
+    #[cfg(feature = "alloc-count")]
+    assert_eq!(alloc_count::measure(lookup).allocations, 0);
+    #[cfg(not(feature = "alloc-count"))]
+    lookup();
+
+    let stats = interner.stats();
+    println!("{:?}", stats);
+    assert_eq!(stats.symbols, 2);
+    assert_eq!(stats.string_bytes, 10);
+    assert_eq!(stats.requests, 4);
+    assert_eq!(stats.bytes_saved, 10);
+
+    // // resolve 返回的 &str 借用了 interner
+    // // cannot move out of `interner` because it is borrowed [E0505]
+    // let s = interner.resolve(a);
+    // drop(interner);
+    // println!("{}", s);
+    drop(interner);
+
+    // leaking 模式下, 字符串一直活到程序结束
+    let s: &'static str = {
+        let owned = String::from("I'm leaked");
+        global().intern_static(&owned).unwrap()
+    };
+    println!("s: {}", s);
+    assert_eq!(
+        global().intern_static("I'm leaked").unwrap().as_ptr(),
+        s.as_ptr()
+    );
+
+    // 多个线程共享一个 Interner, 同一个字符串只会得到一个 Symbol
+    let words = ["let", "mut", "fn", "let", "fn", "match"];
+    let symbols: Vec<Vec<_>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| words.map(|w| global().intern(w)).to_vec()))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert!(symbols.windows(2).all(|w| w[0] == w[1]));
+    assert_eq!(symbols[0][0], symbols[0][3]);
+    for (w, sym) in words.iter().zip(&symbols[0]) {
+        assert_eq!(global().resolve_static(*sym), Some(*w));
+    }
+    println!("{:?}", global().stats());
+}
+
 fn main() {
-    // what_mutable_mean();
-    partial_move();
+    // $ cargo run -- demo toy_borrowck
+    // $ cargo run --release -- demo bench_rope
+    // $ cargo run --release --features alloc-count -- demo bench_small_string
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("demo") => match args.get(1).map(|s| s.as_str()) {
+            Some("what_mutable_mean") => what_mutable_mean(),
+            Some("partial_move") => partial_move(),
+            Some("safe_str_slice") => safe_str_slice(),
+            #[cfg(feature = "alloc-count")]
+            Some("measure_transfer_vs_clone") => measure_transfer_vs_clone(),
+            Some("use_interner") => use_interner(),
+            Some("normalize_with_cow") => normalize_with_cow(),
+            Some("bench_rope") => bench_rope(),
+            Some("use_rope") => use_rope(),
+            Some("use_owned_document") => use_owned_document(),
+            #[cfg(feature = "alloc-count")]
+            Some("bench_small_string") => bench_small_string(),
+            Some("use_small_string") => use_small_string(),
+            Some("toy_borrowck") => toy_borrowck(),
+            Some("parallel_slice_views") => parallel_slice_views(),
+            Some(other) => {
+                eprintln!("unknown demo: {}", other);
+                std::process::exit(2);
+            }
+            None => partial_move(),
+        },
+        _ => partial_move(),
+    }
 }
@@ -0,0 +1,297 @@
+// 基于 Cow<str> 的文本规范化.
+//
+// good_signature() 说明了参数应该用 &str. 返回值也一样:
+// 大多数输入本来就是规范的, 这时直接借用输入 (Cow::Borrowed), 不分配内存;
+// 只有真的需要修改时才分配一个新的 String (Cow::Owned).
+// 对已经是 Owned 的中间结果, 能原地修改的步骤 (trim, ASCII 小写) 也不会再分配.
+//
+// 支持的步骤 (按执行顺序):
+// 0. trim: 去掉首尾空白. 放在最前面, 这样只有首尾空白的输入不需要分配
+// 1. compose: 把 "e\u{301}" 这类常见的 基本字母 + 组合字符 合成 "é" (类似 NFC, 只覆盖 Latin-1)
+// 2. halfwidth: 全角 ASCII 字符 (！，：Ａ１ 等) 和全角空格转为半角, 。「」、 转为半角形式 ｡｢｣､
+// 3. case: 大小写折叠, ASCII 或 Unicode (用 char::to_lowercase, 不是完整的 case folding, 例如 ß 不会变成 ss)
+// 4. collapse_whitespace: 连续的空白字符合并成一个空格
+
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseFold {
+    None,
+    Ascii,
+    Unicode,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Normalizer {
+    compose: bool,
+    halfwidth: bool,
+    case: CaseFold,
+    collapse_whitespace: bool,
+    trim: bool,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Normalizer {
+    // 默认什么都不做
+    pub fn new() -> Self {
+        Normalizer {
+            compose: false,
+            halfwidth: false,
+            case: CaseFold::None,
+            collapse_whitespace: false,
+            trim: false,
+        }
+    }
+
+    // 所有步骤都打开, 大小写按 Unicode 折叠
+    pub fn all() -> Self {
+        Normalizer {
+            compose: true,
+            halfwidth: true,
+            case: CaseFold::Unicode,
+            collapse_whitespace: true,
+            trim: true,
+        }
+    }
+
+    pub fn compose(mut self, yes: bool) -> Self {
+        self.compose = yes;
+        self
+    }
+
+    pub fn halfwidth(mut self, yes: bool) -> Self {
+        self.halfwidth = yes;
+        self
+    }
+
+    pub fn case(mut self, case: CaseFold) -> Self {
+        self.case = case;
+        self
+    }
+
+    pub fn collapse_whitespace(mut self, yes: bool) -> Self {
+        self.collapse_whitespace = yes;
+        self
+    }
+
+    pub fn trim(mut self, yes: bool) -> Self {
+        self.trim = yes;
+        self
+    }
+
+    pub fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let mut s = Cow::Borrowed(s);
+        if self.trim {
+            s = trim(s);
+        }
+        if self.compose {
+            s = compose(s);
+        }
+        if self.halfwidth {
+            s = halfwidth(s);
+        }
+        s = match self.case {
+            CaseFold::None => s,
+            CaseFold::Ascii => ascii_lowercase(s),
+            CaseFold::Unicode => lowercase(s),
+        };
+        if self.collapse_whitespace {
+            s = collapse_whitespace(s);
+        }
+        s
+    }
+}
+
+// 去掉首尾空白. 借用时返回子串, 拥有时原地截断, 都不分配
+pub fn trim(s: Cow<'_, str>) -> Cow<'_, str> {
+    match s {
+        Cow::Borrowed(b) => Cow::Borrowed(b.trim()),
+        Cow::Owned(mut o) => {
+            let end = o.trim_end().len();
+            o.truncate(end);
+            let start = o.len() - o.trim_start().len();
+            o.drain(..start);
+            Cow::Owned(o)
+        }
+    }
+}
+
+// 从第一个需要修改的位置开始, 把剩下的部分重新生成一遍; 没有需要修改的地方就原样返回
+fn rewrite<'a>(
+    s: Cow<'a, str>,
+    needs_change: impl Fn(&str) -> Option<usize>,
+    rest: impl FnOnce(&str, &mut String),
+) -> Cow<'a, str> {
+    let Some(first) = needs_change(&s) else {
+        return s;
+    };
+    let mut out = String::with_capacity(s.len());
+    out.push_str(&s[..first]);
+    rest(&s[first..], &mut out);
+    Cow::Owned(out)
+}
+
+// 逐个 char 映射, f 返回 None 表示不变
+fn map_chars(s: Cow<'_, str>, f: impl Fn(char) -> Option<char>) -> Cow<'_, str> {
+    rewrite(
+        s,
+        |s| {
+            s.char_indices()
+                .find(|&(_, c)| f(c).is_some())
+                .map(|(i, _)| i)
+        },
+        |rest, out| out.extend(rest.chars().map(|c| f(c).unwrap_or(c))),
+    )
+}
+
+pub fn ascii_lowercase(s: Cow<'_, str>) -> Cow<'_, str> {
+    match s {
+        Cow::Borrowed(b) if !b.bytes().any(|c| c.is_ascii_uppercase()) => Cow::Borrowed(b),
+        Cow::Borrowed(b) => Cow::Owned(b.to_ascii_lowercase()),
+        // 大小写 ASCII 字母都是 1 个字节, 可以原地修改
+        Cow::Owned(mut o) => {
+            o.make_ascii_lowercase();
+            Cow::Owned(o)
+        }
+    }
+}
+
+pub fn lowercase(s: Cow<'_, str>) -> Cow<'_, str> {
+    // 一个 char 的小写可能是多个 char, 例如 'İ' => "i\u{307}"
+    let changes = |c: char| {
+        let mut lower = c.to_lowercase();
+        !(lower.len() == 1 && lower.next() == Some(c))
+    };
+    rewrite(
+        s,
+        |s| s.char_indices().find(|&(_, c)| changes(c)).map(|(i, _)| i),
+        |rest, out| out.extend(rest.chars().flat_map(char::to_lowercase)),
+    )
+}
+
+pub fn collapse_whitespace(s: Cow<'_, str>) -> Cow<'_, str> {
+    // 需要修改的地方: 不是 ' ' 的空白字符, 或者连续两个空白字符
+    let first = |s: &str| {
+        let mut prev_space = false;
+        for (i, c) in s.char_indices() {
+            if c.is_whitespace() && (c != ' ' || prev_space) {
+                return Some(if prev_space { i - 1 } else { i });
+            }
+            prev_space = c == ' ';
+        }
+        None
+    };
+    rewrite(s, first, |rest, out| {
+        let mut prev_space = false;
+        for c in rest.chars() {
+            if c.is_whitespace() {
+                if !prev_space {
+                    out.push(' ');
+                }
+                prev_space = true;
+            } else {
+                out.push(c);
+                prev_space = false;
+            }
+        }
+    })
+}
+
+fn to_halfwidth(c: char) -> Option<char> {
+    match c {
+        // 全角 ASCII: U+FF01..=U+FF5E 对应 U+0021..=U+007E
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21),
+        '\u{3000}' => Some(' '),
+        '。' => Some('｡'),
+        '「' => Some('｢'),
+        '」' => Some('｣'),
+        '、' => Some('､'),
+        _ => None,
+    }
+}
+
+pub fn halfwidth(s: Cow<'_, str>) -> Cow<'_, str> {
+    map_chars(s, to_halfwidth)
+}
+
+// (组合字符, 基本字母, 合成后的字母), 只覆盖 Latin-1 里的字母
+const COMPOSITIONS: [(char, &str, &str); 7] = [
+    ('\u{300}', "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ('\u{301}', "AEIOUYaeiouy", "ÁÉÍÓÚÝáéíóúý"),
+    ('\u{302}', "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    ('\u{303}', "ANOano", "ÃÑÕãñõ"),
+    ('\u{308}', "AEIOUaeiouy", "ÄËÏÖÜäëïöüÿ"),
+    ('\u{30A}', "Aa", "Åå"),
+    ('\u{327}', "Cc", "Çç"),
+];
+
+fn composed(base: char, mark: char) -> Option<char> {
+    let (_, bases, results) = COMPOSITIONS.iter().find(|(m, _, _)| *m == mark)?;
+    let i = bases.chars().position(|b| b == base)?;
+    results.chars().nth(i)
+}
+
+pub fn compose(s: Cow<'_, str>) -> Cow<'_, str> {
+    let first = |s: &str| {
+        let mut chars = s.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if let Some(&(_, mark)) = chars.peek() {
+                if composed(c, mark).is_some() {
+                    return Some(i);
+                }
+            }
+        }
+        None
+    };
+    rewrite(s, first, |rest, out| {
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match chars.peek().and_then(|&mark| composed(c, mark)) {
+                Some(composed) => {
+                    out.push(composed);
+                    chars.next();
+                }
+                None => out.push(c),
+            }
+        }
+    })
+}
+
+// 常用的组合: 全部步骤
+pub fn normalize(s: &str) -> Cow<'_, str> {
+    Normalizer::all().normalize(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_count::{measure, measure_with};
+
+    #[test]
+    fn unchanged_input_is_borrowed_without_allocating() {
+        for input in ["hello, world", "  café 中文 ", "a b c", ""] {
+            let (s, stats) = measure_with(|| normalize(input));
+            assert!(matches!(s, Cow::Borrowed(_)), "{:?}", input);
+            assert_eq!(stats.allocations, 0, "{:?}", input);
+        }
+        let (s, stats) = measure_with(|| trim(Cow::Borrowed("\t hi \n")));
+        assert!(matches!(s, Cow::Borrowed("hi")));
+        assert_eq!(stats.allocations, 0);
+    }
+
+    #[test]
+    fn each_changing_step_allocates_once() {
+        // 大小写和空白两步
+        let stats = measure(|| {
+            let s = normalize("  Hello,\t\n  WORLD  ");
+            assert!(matches!(s, Cow::Owned(_)));
+            assert_eq!(s, "hello, world");
+        });
+        assert_eq!(stats.allocations, 2);
+    }
+}
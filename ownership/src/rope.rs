@@ -0,0 +1,575 @@
+// Rope: 用平衡二叉树保存一串小的字符串块, 适合编辑很长的文本.
+//
+// str_vs_String() 里只有连续存储的 String. 在一个几 MB 的 String 中间插入一个字符,
+// 后面所有字节都要往后挪一次, 是 O(n) 的. Rope 把文本切成最多 MAX_LEAF 字节的块,
+// 块挂在 AVL 树的叶子上, 插入和删除只需要 split + join, 是 O(log n) 的.
+//
+// 所有的位置都是 char 下标 (不是字节下标), 行号和列号都从 0 开始, 列号也按 char 计.
+
+use std::fmt;
+use std::ops::{Bound, Range, RangeBounds};
+
+const MAX_LEAF: usize = 1024;
+
+// 子树的统计信息
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Info {
+    bytes: usize,
+    chars: usize,
+    newlines: usize,
+}
+
+impl Info {
+    fn of(s: &str) -> Self {
+        Info {
+            bytes: s.len(),
+            chars: s.chars().count(),
+            newlines: s.bytes().filter(|&b| b == b'\n').count(),
+        }
+    }
+
+    fn add(self, other: Info) -> Info {
+        Info {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            newlines: self.newlines + other.newlines,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(String, Info),
+    Branch {
+        left: Box<Node>,
+        right: Box<Node>,
+        info: Info,
+        height: usize,
+    },
+}
+
+impl Node {
+    fn leaf(s: String) -> Node {
+        let info = Info::of(&s);
+        Node::Leaf(s, info)
+    }
+
+    fn empty() -> Node {
+        Node::Leaf(String::new(), Info::default())
+    }
+
+    fn branch(left: Node, right: Node) -> Node {
+        Node::Branch {
+            info: left.info().add(right.info()),
+            height: left.height().max(right.height()) + 1,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn info(&self) -> Info {
+        match self {
+            Node::Leaf(_, info) | Node::Branch { info, .. } => *info,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf(..) => 0,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    fn into_children(self) -> (Node, Node) {
+        match self {
+            Node::Branch { left, right, .. } => (*left, *right),
+            Node::Leaf(..) => unreachable!("leaf has no children"),
+        }
+    }
+
+    // 由一组块自底向上建出完全平衡的树
+    fn build(mut chunks: Vec<Node>) -> Node {
+        if chunks.is_empty() {
+            return Node::empty();
+        }
+        while chunks.len() > 1 {
+            let mut next = Vec::with_capacity(chunks.len().div_ceil(2));
+            let mut iter = chunks.into_iter();
+            while let Some(left) = iter.next() {
+                next.push(match iter.next() {
+                    Some(right) => Node::branch(left, right),
+                    None => left,
+                });
+            }
+            chunks = next;
+        }
+        chunks.pop().unwrap()
+    }
+}
+
+// 把 s 切成不超过 MAX_LEAF 字节的块, 不切断 char
+fn chunks_of(s: &str) -> Vec<Node> {
+    let mut chunks = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(MAX_LEAF);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        chunks.push(Node::leaf(rest[..end].to_string()));
+        rest = &rest[end..];
+    }
+    chunks
+}
+
+// 第 n 个 char 的字节偏移
+fn byte_of_char(s: &str, n: usize) -> usize {
+    s.char_indices().nth(n).map_or(s.len(), |(i, _)| i)
+}
+
+// 高度差不超过 1 时直接连接, 否则做一次 AVL 旋转
+fn balance(left: Node, right: Node) -> Node {
+    let (hl, hr) = (left.height(), right.height());
+    if hl > hr + 1 {
+        let (a, b) = left.into_children();
+        if a.height() >= b.height() {
+            Node::branch(a, Node::branch(b, right))
+        } else {
+            let (b1, b2) = b.into_children();
+            Node::branch(Node::branch(a, b1), Node::branch(b2, right))
+        }
+    } else if hr > hl + 1 {
+        let (a, b) = right.into_children();
+        if b.height() >= a.height() {
+            Node::branch(Node::branch(left, a), b)
+        } else {
+            let (a1, a2) = a.into_children();
+            Node::branch(Node::branch(left, a1), Node::branch(a2, b))
+        }
+    } else {
+        Node::branch(left, right)
+    }
+}
+
+// 连接两棵树, 沿着较高那棵树的边缘往下走到高度相近的位置再连接, O(log n)
+fn join(left: Node, right: Node) -> Node {
+    if left.info().bytes == 0 {
+        return right;
+    }
+    if right.info().bytes == 0 {
+        return left;
+    }
+    let (hl, hr) = (left.height(), right.height());
+    if hl > hr + 1 {
+        let (a, b) = left.into_children();
+        return balance(a, join(b, right));
+    }
+    if hr > hl + 1 {
+        let (a, b) = right.into_children();
+        return balance(join(left, a), b);
+    }
+    match (left, right) {
+        // 两个小块合并成一个, 避免插入很多短字符串后叶子越来越碎
+        (Node::Leaf(mut a, ia), Node::Leaf(b, ib)) if a.len() + b.len() <= MAX_LEAF => {
+            a.push_str(&b);
+            Node::Leaf(a, ia.add(ib))
+        }
+        (left, right) => Node::branch(left, right),
+    }
+}
+
+// 在第 at 个 char 处分成两棵树, O(log n)
+fn split(node: Node, at: usize) -> (Node, Node) {
+    if at == 0 {
+        return (Node::empty(), node);
+    }
+    if at >= node.info().chars {
+        return (node, Node::empty());
+    }
+    match node {
+        Node::Leaf(mut s, _) => {
+            let right = s.split_off(byte_of_char(&s, at));
+            (Node::leaf(s), Node::leaf(right))
+        }
+        Node::Branch { left, right, .. } => {
+            let left_chars = left.info().chars;
+            if at <= left_chars {
+                let (a, b) = split(*left, at);
+                (a, join(b, *right))
+            } else {
+                let (a, b) = split(*right, at - left_chars);
+                (join(*left, a), b)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub index: usize,
+    pub len: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "char index {} is out of bounds of rope with {} chars",
+            self.index, self.len
+        )
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+// 按 range 操作的方法 (remove / chunks_in / slice) 的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeError {
+    // 区间起点大于终点
+    Inverted { start: usize, end: usize },
+    OutOfBounds(OutOfBounds),
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Inverted { start, end } => {
+                write!(f, "char range starts at {} but ends at {}", start, end)
+            }
+            RangeError::OutOfBounds(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for RangeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RangeError::OutOfBounds(e) => Some(e),
+            RangeError::Inverted { .. } => None,
+        }
+    }
+}
+
+impl From<OutOfBounds> for RangeError {
+    fn from(e: OutOfBounds) -> Self {
+        RangeError::OutOfBounds(e)
+    }
+}
+
+#[derive(Clone)]
+pub struct Rope {
+    root: Node,
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Rope {
+            root: Node::empty(),
+        }
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.root.info().bytes
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.root.info().chars
+    }
+
+    // 行数 = 换行符个数 + 1, 与编辑器的行号一致: 空文本也有 1 行
+    pub fn len_lines(&self) -> usize {
+        self.root.info().newlines + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len_bytes() == 0
+    }
+
+    // 树的高度, 可以用来确认树是平衡的
+    pub fn height(&self) -> usize {
+        self.root.height()
+    }
+
+    fn check(&self, index: usize) -> Result<(), OutOfBounds> {
+        if index > self.len_chars() {
+            return Err(OutOfBounds {
+                index,
+                len: self.len_chars(),
+            });
+        }
+        Ok(())
+    }
+
+    fn take(&mut self) -> Node {
+        std::mem::replace(&mut self.root, Node::empty())
+    }
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) -> Result<(), OutOfBounds> {
+        self.check(char_idx)?;
+        if text.is_empty() {
+            return Ok(());
+        }
+        let (left, right) = split(self.take(), char_idx);
+        let middle = Node::build(chunks_of(text));
+        self.root = join(join(left, middle), right);
+        Ok(())
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.insert(self.len_chars(), text).unwrap();
+    }
+
+    pub fn append(&mut self, other: Rope) {
+        self.root = join(self.take(), other.root);
+    }
+
+    fn to_range(&self, range: impl RangeBounds<usize>) -> Result<Range<usize>, RangeError> {
+        // ..=usize::MAX 的终点无法表示, 一定越界
+        let after = |i: usize| {
+            i.checked_add(1).ok_or(OutOfBounds {
+                index: i,
+                len: self.len_chars(),
+            })
+        };
+        let start = match range.start_bound() {
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => after(i)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&i) => after(i)?,
+            Bound::Excluded(&i) => i,
+            Bound::Unbounded => self.len_chars(),
+        };
+        if start > end {
+            return Err(RangeError::Inverted { start, end });
+        }
+        self.check(end)?;
+        Ok(start..end)
+    }
+
+    // 删除一段 char
+    pub fn remove(&mut self, range: impl RangeBounds<usize>) -> Result<(), RangeError> {
+        let range = self.to_range(range)?;
+        let (left, rest) = split(self.take(), range.start);
+        let (_, right) = split(rest, range.len());
+        self.root = join(left, right);
+        Ok(())
+    }
+
+    // 在第 char_idx 个 char 处分成两个 Rope, self 保留前一半
+    pub fn split_off(&mut self, char_idx: usize) -> Result<Rope, OutOfBounds> {
+        self.check(char_idx)?;
+        let (left, right) = split(self.take(), char_idx);
+        self.root = left;
+        Ok(Rope { root: right })
+    }
+
+    pub fn char(&self, char_idx: usize) -> Option<char> {
+        // char_idx..=char_idx 由 to_range 检查, usize::MAX 不会溢出
+        self.chunks_in(char_idx..=char_idx)
+            .ok()?
+            .next()
+            .and_then(|s| s.chars().next())
+    }
+
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks::new(&self.root, 0..self.len_chars())
+    }
+
+    // 按顺序给出覆盖 range 的若干个 &str, 不复制数据
+    pub fn chunks_in(&self, range: impl RangeBounds<usize>) -> Result<Chunks<'_>, RangeError> {
+        let range = self.to_range(range)?;
+        Ok(Chunks::new(&self.root, range))
+    }
+
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Result<String, RangeError> {
+        Ok(self.chunks_in(range)?.collect())
+    }
+
+    // 第 line 行开头的 char 下标
+    pub fn line_to_char(&self, line: usize) -> Option<usize> {
+        if line >= self.len_lines() {
+            return None;
+        }
+        if line == 0 {
+            return Some(0);
+        }
+        // 找到第 line 个换行符 (从 1 开始数), 行首就在它后面
+        let mut node = &self.root;
+        let mut newlines = line;
+        let mut chars = 0;
+        loop {
+            match node {
+                Node::Branch { left, right, .. } => {
+                    let info = left.info();
+                    if newlines <= info.newlines {
+                        node = left;
+                    } else {
+                        newlines -= info.newlines;
+                        chars += info.chars;
+                        node = right;
+                    }
+                }
+                Node::Leaf(s, _) => {
+                    let (i, _) = s
+                        .char_indices()
+                        .filter(|&(_, c)| c == '\n')
+                        .nth(newlines - 1)?;
+                    return Some(chars + s[..i].chars().count() + 1);
+                }
+            }
+        }
+    }
+
+    // char 下标 -> (行, 列)
+    pub fn char_to_line_col(&self, char_idx: usize) -> Result<(usize, usize), OutOfBounds> {
+        self.check(char_idx)?;
+        let mut node = &self.root;
+        let mut idx = char_idx;
+        let mut line = 0;
+        loop {
+            match node {
+                Node::Branch { left, right, .. } => {
+                    let info = left.info();
+                    if idx < info.chars {
+                        node = left;
+                    } else {
+                        idx -= info.chars;
+                        line += info.newlines;
+                        node = right;
+                    }
+                }
+                Node::Leaf(s, _) => {
+                    let before = &s[..byte_of_char(s, idx)];
+                    line += before.matches('\n').count();
+                    break;
+                }
+            }
+        }
+        let col = char_idx - self.line_to_char(line).unwrap();
+        Ok((line, col))
+    }
+
+    // (行, 列) -> char 下标. 列可以等于行的长度 (指向行尾), 超出时返回 None
+    pub fn line_col_to_char(&self, line: usize, col: usize) -> Option<usize> {
+        let start = self.line_to_char(line)?;
+        let end = self
+            .line_to_char(line + 1)
+            .map_or(self.len_chars(), |next| next - 1);
+        start.checked_add(col).filter(|&i| i <= end)
+    }
+}
+
+impl Default for Rope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for Rope {
+    fn from(s: &str) -> Self {
+        Rope {
+            root: Node::build(chunks_of(s)),
+        }
+    }
+}
+
+impl From<String> for Rope {
+    fn from(s: String) -> Self {
+        Rope::from(s.as_str())
+    }
+}
+
+impl From<&Rope> for String {
+    fn from(rope: &Rope) -> Self {
+        let mut s = String::with_capacity(rope.len_bytes());
+        s.extend(rope.chunks());
+        s
+    }
+}
+
+impl From<Rope> for String {
+    fn from(rope: Rope) -> Self {
+        String::from(&rope)
+    }
+}
+
+impl fmt::Display for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl fmt::Debug for Rope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rope({:?})", String::from(self))
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Rope) -> bool {
+        self.len_bytes() == other.len_bytes()
+            && self
+                .chunks()
+                .flat_map(str::bytes)
+                .eq(other.chunks().flat_map(str::bytes))
+    }
+}
+
+impl Eq for Rope {}
+
+impl PartialEq<str> for Rope {
+    fn eq(&self, other: &str) -> bool {
+        self.len_bytes() == other.len() && self.chunks().flat_map(str::bytes).eq(other.bytes())
+    }
+}
+
+impl PartialEq<&str> for Rope {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+// 深度优先遍历叶子, 只给出与 range 相交的部分
+pub struct Chunks<'a> {
+    // (节点, 节点第一个 char 在整个 Rope 中的下标)
+    stack: Vec<(&'a Node, usize)>,
+    range: Range<usize>,
+}
+
+impl<'a> Chunks<'a> {
+    fn new(root: &'a Node, range: Range<usize>) -> Self {
+        Chunks {
+            stack: vec![(root, 0)],
+            range,
+        }
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        while let Some((node, offset)) = self.stack.pop() {
+            let end = offset + node.info().chars;
+            if end <= self.range.start || offset >= self.range.end {
+                continue;
+            }
+            match node {
+                Node::Branch { left, right, .. } => {
+                    self.stack.push((right, offset + left.info().chars));
+                    self.stack.push((left, offset));
+                }
+                Node::Leaf(s, _) => {
+                    let from = self.range.start.saturating_sub(offset);
+                    let to = self.range.end.min(end) - offset;
+                    let from_byte = byte_of_char(s, from);
+                    let to_byte = from_byte + byte_of_char(&s[from_byte..], to - from);
+                    return Some(&s[from_byte..to_byte]);
+                }
+            }
+        }
+        None
+    }
+}
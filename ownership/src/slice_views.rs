@@ -0,0 +1,270 @@
+// 把一个 &mut [T] 切分成互不重叠的可变视图, 交给多个线程同时原地修改.
+//
+// use_mutable_slice() 里只有一个 &mut a[5..]. 借用规则不允许同时存在两个指向同一数据的 &mut,
+// 但是指向不相交区间的 &mut 是可以共存的, 标准库的 split_at_mut / chunks_mut 就是这样做的.
+// 这里的每个函数都从一个 &mut [T] 出发, 只用 split_at_mut / chunks_mut / iter_mut 这些安全的 API,
+// 所以得到的视图一定互不重叠, 不需要调用者保证什么, 也没有 unsafe.
+//
+// T: Send 时这些视图也是 Send 的, 可以直接 move 进 thread::scope 里的线程.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewError {
+    // 块大小, 步长或宽度为 0
+    ZeroSize,
+    // 切分位置没有递增或者超出了长度
+    BadSplit { index: usize, len: usize },
+    // 长度不是宽度的整数倍, 不能看作行优先的二维数组
+    NotRectangular { len: usize, width: usize },
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewError::ZeroSize => write!(f, "size must be greater than zero"),
+            ViewError::BadSplit { index, len } => {
+                write!(
+                    f,
+                    "split index {} is out of order or out of bounds for length {}",
+                    index, len
+                )
+            }
+            ViewError::NotRectangular { len, width } => {
+                write!(f, "length {} is not a multiple of width {}", len, width)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+// 每块 size 个元素, 最后一块可能不满
+pub fn chunks<T>(slice: &mut [T], size: usize) -> Result<Vec<&mut [T]>, ViewError> {
+    if size == 0 {
+        return Err(ViewError::ZeroSize);
+    }
+    Ok(slice.chunks_mut(size).collect())
+}
+
+// 尽量平均地分成 parts 块 (前面的块多一个元素), 适合分给 parts 个线程
+pub fn split_even<T>(slice: &mut [T], parts: usize) -> Result<Vec<&mut [T]>, ViewError> {
+    if parts == 0 {
+        return Err(ViewError::ZeroSize);
+    }
+    let (base, extra) = (slice.len() / parts, slice.len() % parts);
+    let mut rest = slice;
+    let mut out = Vec::with_capacity(parts);
+    for i in 0..parts {
+        let (head, tail) = rest.split_at_mut(base + usize::from(i < extra));
+        out.push(head);
+        rest = tail;
+    }
+    Ok(out)
+}
+
+// 在给定的位置切开, indices 必须递增且不超过长度. 返回 indices.len() + 1 块
+pub fn split_at_indices<'a, T>(
+    slice: &'a mut [T],
+    indices: &[usize],
+) -> Result<Vec<&'a mut [T]>, ViewError> {
+    let len = slice.len();
+    let mut prev = 0;
+    for &index in indices {
+        if index < prev || index > len {
+            return Err(ViewError::BadSplit { index, len });
+        }
+        prev = index;
+    }
+    let mut rest = slice;
+    let mut out = Vec::with_capacity(indices.len() + 1);
+    let mut consumed = 0;
+    for &index in indices {
+        let (head, tail) = rest.split_at_mut(index - consumed);
+        out.push(head);
+        rest = tail;
+        consumed = index;
+    }
+    out.push(rest);
+    Ok(out)
+}
+
+// 相邻两个元素满足 same(a, b) 时属于同一块, 例如按值分组的连续段
+pub fn split_runs<T>(slice: &mut [T], same: impl FnMut(&T, &T) -> bool) -> Vec<&mut [T]> {
+    slice.chunk_by_mut(same).collect()
+}
+
+// 以 pred 为真的元素作为分隔符切开, 分隔符本身不属于任何一块
+pub fn split_by<T>(slice: &mut [T], pred: impl FnMut(&T) -> bool) -> Vec<&mut [T]> {
+    slice.split_mut(pred).collect()
+}
+
+// 跨步视图: 下标为 offset, offset + stride, offset + 2 * stride, ... 的元素
+pub struct StridedMut<'a, T> {
+    offset: usize,
+    stride: usize,
+    items: Vec<&'a mut T>,
+}
+
+impl<'a, T> StridedMut<'a, T> {
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        self.items.get(i).map(|item| &**item)
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        self.items.get_mut(i).map(|item| &mut **item)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter().map(|item| &**item)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + use<'_, 'a, T> {
+        self.items.iter_mut().map(|item| &mut **item)
+    }
+}
+
+impl<'a, T> IntoIterator for StridedMut<'a, T> {
+    type Item = &'a mut T;
+    type IntoIter = std::vec::IntoIter<&'a mut T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+// 分成 stride 个跨步视图, 第 k 个视图包含下标模 stride 余 k 的元素
+pub fn strided<T>(slice: &mut [T], stride: usize) -> Result<Vec<StridedMut<'_, T>>, ViewError> {
+    if stride == 0 {
+        return Err(ViewError::ZeroSize);
+    }
+    let mut views: Vec<StridedMut<T>> = (0..stride.min(slice.len()))
+        .map(|offset| StridedMut {
+            offset,
+            stride,
+            items: Vec::with_capacity(slice.len() / stride + 1),
+        })
+        .collect();
+    // iter_mut 给出的每个 &mut T 只会被放进一个视图
+    for (i, item) in slice.iter_mut().enumerate() {
+        views[i % stride].items.push(item);
+    }
+    Ok(views)
+}
+
+fn check_rect(len: usize, width: usize) -> Result<(), ViewError> {
+    if width == 0 {
+        return Err(ViewError::ZeroSize);
+    }
+    if !len.is_multiple_of(width) {
+        return Err(ViewError::NotRectangular { len, width });
+    }
+    Ok(())
+}
+
+// 行优先的二维数组的每一行
+pub fn rows<T>(buf: &mut [T], width: usize) -> Result<Vec<&mut [T]>, ViewError> {
+    check_rect(buf.len(), width)?;
+    Ok(buf.chunks_mut(width).collect())
+}
+
+// 行优先的二维数组的每一列, 就是步长为 width 的跨步视图
+pub fn columns<T>(buf: &mut [T], width: usize) -> Result<Vec<StridedMut<'_, T>>, ViewError> {
+    check_rect(buf.len(), width)?;
+    strided(buf, width)
+}
+
+// 二维数组中的一个矩形区域, 由若干行片段组成
+pub struct TileMut<'a, T> {
+    row: usize,
+    col: usize,
+    width: usize,
+    rows: Vec<&'a mut [T]>,
+}
+
+impl<'a, T> TileMut<'a, T> {
+    // 左上角在整个二维数组中的 (行, 列)
+    pub fn origin(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    // (r, c) 是区域内的坐标
+    pub fn get(&self, r: usize, c: usize) -> Option<&T> {
+        self.rows.get(r)?.get(c)
+    }
+
+    pub fn get_mut(&mut self, r: usize, c: usize) -> Option<&mut T> {
+        self.rows.get_mut(r)?.get_mut(c)
+    }
+
+    pub fn row_mut(&mut self, r: usize) -> Option<&mut [T]> {
+        self.rows.get_mut(r).map(|row| &mut **row)
+    }
+
+    // 按行优先顺序给出 ((整体的行, 整体的列), 元素)
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = ((usize, usize), &mut T)> + use<'_, 'a, T> {
+        let (row, col) = (self.row, self.col);
+        self.rows.iter_mut().enumerate().flat_map(move |(r, line)| {
+            line.iter_mut()
+                .enumerate()
+                .map(move |(c, item)| ((row + r, col + c), item))
+        })
+    }
+}
+
+// 把宽为 width 的二维数组切成 tile_w x tile_h 的小块, 右边和下边的块可能更小.
+// 块按行优先顺序排列.
+pub fn tiles<T>(
+    buf: &mut [T],
+    width: usize,
+    tile_w: usize,
+    tile_h: usize,
+) -> Result<Vec<TileMut<'_, T>>, ViewError> {
+    check_rect(buf.len(), width)?;
+    if tile_w == 0 || tile_h == 0 {
+        return Err(ViewError::ZeroSize);
+    }
+    let height = buf.len() / width;
+    let across = width.div_ceil(tile_w);
+    let mut tiles: Vec<TileMut<T>> = (0..height.div_ceil(tile_h) * across)
+        .map(|i| {
+            let col = (i % across) * tile_w;
+            TileMut {
+                row: (i / across) * tile_h,
+                col,
+                width: tile_w.min(width - col),
+                rows: Vec::with_capacity(tile_h),
+            }
+        })
+        .collect();
+    for (r, line) in buf.chunks_mut(width).enumerate() {
+        for (c, piece) in line.chunks_mut(tile_w).enumerate() {
+            tiles[(r / tile_h) * across + c].rows.push(piece);
+        }
+    }
+    Ok(tiles)
+}
@@ -0,0 +1,286 @@
+// 短字符串优化 (small string optimization).
+//
+// String::from("u1") 这样的短字符串, 也要在堆上分配一次内存.
+// SmallString 把不超过 INLINE_CAP (23) 个字节的字符串直接存放在栈上 (inline),
+// 超过之后才转移 (spill) 到堆上的 String.
+//
+// 这里为了不写 unsafe 的布局技巧, 用了一个普通的 enum, 所以 size_of::<SmallString>() 是 32 而不是 24.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+pub const INLINE_CAP: usize = 23;
+
+#[derive(Clone)]
+enum Repr {
+    Inline { len: u8, buf: [u8; INLINE_CAP] },
+    Heap(String),
+}
+
+#[derive(Clone)]
+pub struct SmallString(Repr);
+
+impl SmallString {
+    pub const fn new() -> Self {
+        SmallString(Repr::Inline {
+            len: 0,
+            buf: [0; INLINE_CAP],
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // buf[..len] 只会从 &str 复制进来, 而且总是在 char 边界上截断, 一定是合法的 UTF-8
+            Repr::Inline { len, buf } => unsafe {
+                std::str::from_utf8_unchecked(&buf[..*len as usize])
+            },
+            Repr::Heap(s) => s,
+        }
+    }
+
+    // 是否存放在栈上
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+
+    pub fn capacity(&self) -> usize {
+        match &self.0 {
+            Repr::Inline { .. } => INLINE_CAP,
+            Repr::Heap(s) => s.capacity(),
+        }
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        match &mut self.0 {
+            Repr::Inline { len, buf } => {
+                let old = *len as usize;
+                let new = old + s.len();
+                if new <= INLINE_CAP {
+                    buf[old..new].copy_from_slice(s.as_bytes());
+                    *len = new as u8;
+                } else {
+                    // spill: 只分配一次, 容量正好够放下两部分
+                    let mut heap = String::with_capacity(new);
+                    heap.push_str(self.as_str());
+                    heap.push_str(s);
+                    self.0 = Repr::Heap(heap);
+                }
+            }
+            Repr::Heap(heap) => heap.push_str(s),
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    // 清空后保留原来的存储, 与 String::clear 一样
+    pub fn clear(&mut self) {
+        match &mut self.0 {
+            Repr::Inline { len, .. } => *len = 0,
+            Repr::Heap(heap) => heap.clear(),
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        match self.0 {
+            Repr::Inline { .. } => self.as_str().to_string(),
+            Repr::Heap(s) => s,
+        }
+    }
+}
+
+impl Default for SmallString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for SmallString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SmallString {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+// 有了 Borrow<str>, HashMap<SmallString, _> 可以用 &str 查找
+impl Borrow<str> for SmallString {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl From<&str> for SmallString {
+    fn from(s: &str) -> Self {
+        let mut small = SmallString::new();
+        small.push_str(s);
+        small
+    }
+}
+
+// 长字符串直接接管 String 的堆内存, 不再复制
+impl From<String> for SmallString {
+    fn from(s: String) -> Self {
+        if s.len() <= INLINE_CAP {
+            SmallString::from(s.as_str())
+        } else {
+            SmallString(Repr::Heap(s))
+        }
+    }
+}
+
+impl From<char> for SmallString {
+    fn from(c: char) -> Self {
+        SmallString::from(c.encode_utf8(&mut [0; 4]) as &str)
+    }
+}
+
+impl From<SmallString> for String {
+    fn from(s: SmallString) -> Self {
+        s.into_string()
+    }
+}
+
+impl FromIterator<char> for SmallString {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut s = SmallString::new();
+        s.extend(iter);
+        s
+    }
+}
+
+impl Extend<char> for SmallString {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|c| self.push(c));
+    }
+}
+
+impl<'a> Extend<&'a str> for SmallString {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|s| self.push_str(s));
+    }
+}
+
+impl fmt::Display for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Write for SmallString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+// 比较和哈希都只看内容, 与存放在哪里无关. Hash 必须与 str 一致, Borrow<str> 才能正确工作.
+impl Hash for SmallString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialEq for SmallString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SmallString {}
+
+impl PartialEq<str> for SmallString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SmallString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for SmallString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_count::{measure, measure_with};
+
+    #[test]
+    fn up_to_23_bytes_stays_inline() {
+        for len in 0..=INLINE_CAP {
+            let input = "a".repeat(len);
+            let (s, stats) = measure_with(|| SmallString::from(input.as_str()));
+            assert!(s.is_inline());
+            assert_eq!(s, input.as_str());
+            assert_eq!(stats.allocations, 0);
+        }
+        // 中文按字节计: 7 个汉字 21 字节
+        let (s, stats) = measure_with(|| SmallString::from("七个汉字还在栈"));
+        assert!(s.is_inline());
+        assert_eq!(stats.allocations, 0);
+        // 对比: String 总要分配
+        assert_eq!(measure(|| drop(String::from("u1"))).allocations, 1);
+    }
+
+    #[test]
+    fn spills_to_heap_at_24_bytes() {
+        let input = "a".repeat(INLINE_CAP + 1);
+        let (s, stats) = measure_with(|| SmallString::from(input.as_str()));
+        assert!(!s.is_inline());
+        assert_eq!(s.len(), 24);
+        assert_eq!(stats.allocations, 1);
+
+        let (s, stats) = measure_with(|| SmallString::from("八个汉字就超过了"));
+        assert!(!s.is_inline());
+        assert_eq!(s.len(), 24);
+        assert_eq!(stats.allocations, 1);
+    }
+
+    #[test]
+    fn push_str_crossing_the_boundary() {
+        let mut s = SmallString::from("a".repeat(20).as_str());
+        let stats = measure(|| {
+            s.push_str("bc");
+            s.push('d');
+        });
+        assert_eq!(s.len(), INLINE_CAP);
+        assert!(s.is_inline());
+        assert_eq!(stats.allocations, 0);
+
+        // 超过 23 字节时 spill 到堆上, 只分配一次
+        let stats = measure(|| s.push_str("efg"));
+        assert!(!s.is_inline());
+        assert_eq!(s.len(), 26);
+        assert!(s.ends_with("abcdefg"));
+        assert_eq!(stats.allocations, 1);
+    }
+}
@@ -0,0 +1,211 @@
+// 按字符边界安全地切分字符串.
+//
+// str_() 里 &s[..1] 会 panic, 因为 "中国" 的第 1 个字节位于 '中' (bytes 0..3) 的中间.
+// 这里提供几种不会 panic 的切分方式:
+// 1. 按 char 下标切分
+// 2. 按字素簇 (grapheme cluster, 人眼看到的 "一个字符") 下标切分
+// 3. try_slice 按字节切分, 不在字符边界上时返回错误, 并给出最近的合法边界
+// 4. 在不切断字符的前提下, 把字符串截断到不超过给定的字节数
+
+use std::fmt;
+use std::ops::{Bound, Range, RangeBounds};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SliceError {
+    // 区间起点大于终点
+    Inverted {
+        start: usize,
+        end: usize,
+    },
+    // 下标超出了字符串长度 (字节/char/字素簇个数)
+    OutOfBounds {
+        index: usize,
+        len: usize,
+    },
+    // 字节下标落在某个字符的中间, floor/ceil 是它前后最近的字符边界
+    NotCharBoundary {
+        index: usize,
+        floor: usize,
+        ceil: usize,
+    },
+}
+
+impl fmt::Display for SliceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SliceError::Inverted { start, end } => {
+                write!(f, "slice index starts at {} but ends at {}", start, end)
+            }
+            SliceError::OutOfBounds { index, len } => {
+                write!(f, "index {} is out of bounds of length {}", index, len)
+            }
+            SliceError::NotCharBoundary { index, floor, ceil } => write!(
+                f,
+                "byte index {} is not a char boundary; nearest boundaries are {} and {}",
+                index, floor, ceil
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SliceError {}
+
+// 把任意的 RangeBounds 转换成 [start, end), 并检查是否越界
+fn to_range(range: impl RangeBounds<usize>, len: usize) -> Result<Range<usize>, SliceError> {
+    // ..=usize::MAX 的终点无法表示, 一定越界
+    let after = |i: usize| {
+        i.checked_add(1)
+            .ok_or(SliceError::OutOfBounds { index: i, len })
+    };
+    let start = match range.start_bound() {
+        Bound::Included(&i) => i,
+        Bound::Excluded(&i) => after(i)?,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&i) => after(i)?,
+        Bound::Excluded(&i) => i,
+        Bound::Unbounded => len,
+    };
+    if start > end {
+        return Err(SliceError::Inverted { start, end });
+    }
+    if end > len {
+        return Err(SliceError::OutOfBounds { index: end, len });
+    }
+    Ok(start..end)
+}
+
+// 不超过 index 的最大字符边界
+pub fn floor_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    // UTF-8 编码的字符最长 4 个字节, 最多往前找 3 次
+    (0..=index).rev().find(|&i| s.is_char_boundary(i)).unwrap()
+}
+
+// 不小于 index 的最小字符边界
+pub fn ceil_char_boundary(s: &str, index: usize) -> usize {
+    if index >= s.len() {
+        return s.len();
+    }
+    (index..=s.len()).find(|&i| s.is_char_boundary(i)).unwrap()
+}
+
+// 与 &s[range] 一样按字节切分, 但是不会 panic
+pub fn try_slice(s: &str, range: impl RangeBounds<usize>) -> Result<&str, SliceError> {
+    let range = to_range(range, s.len())?;
+    for index in [range.start, range.end] {
+        if !s.is_char_boundary(index) {
+            return Err(SliceError::NotCharBoundary {
+                index,
+                floor: floor_char_boundary(s, index),
+                ceil: ceil_char_boundary(s, index),
+            });
+        }
+    }
+    Ok(&s[range])
+}
+
+// 第 n 个 char 的字节偏移, n 等于 char 个数时返回 s.len()
+fn char_offset(s: &str, n: usize) -> Option<usize> {
+    s.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(s.len()))
+        .nth(n)
+}
+
+// 按 char 下标切分, 例如 char_slice("中国人", 1..2) == Ok("国")
+pub fn char_slice(s: &str, range: impl RangeBounds<usize>) -> Result<&str, SliceError> {
+    let range = to_range(range, s.chars().count())?;
+    let start = char_offset(s, range.start).unwrap();
+    let end = char_offset(s, range.end).unwrap();
+    Ok(&s[start..end])
+}
+
+// 按字素簇下标切分. "e\u{301}" (é) 和 "👍🏽" 都只算一个字素簇, 不会被切开.
+pub fn grapheme_slice(s: &str, range: impl RangeBounds<usize>) -> Result<&str, SliceError> {
+    let offsets: Vec<usize> = s
+        .grapheme_indices(true)
+        .map(|(i, _)| i)
+        .chain(std::iter::once(s.len()))
+        .collect();
+    let range = to_range(range, offsets.len() - 1)?;
+    Ok(&s[offsets[range.start]..offsets[range.end]])
+}
+
+// 截断到最多 max_bytes 个字节, 不会切断 char.
+// 注意组合字符序列仍可能被切开, 例如 "e\u{301}" 截断到 1 个字节得到 "e".
+pub fn truncate_bytes(s: &str, max_bytes: usize) -> &str {
+    &s[..floor_char_boundary(s, max_bytes)]
+}
+
+// 截断到最多 max_bytes 个字节, 不会切断字素簇
+pub fn truncate_graphemes(s: &str, max_bytes: usize) -> &str {
+    let end = s
+        .grapheme_indices(true)
+        .map(|(i, g)| i + g.len())
+        .take_while(|&end| end <= max_bytes)
+        .last()
+        .unwrap_or(0);
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cjk() {
+        let s = "你好, 世界";
+        assert_eq!(char_slice(s, 4..), Ok("世界"));
+        assert_eq!(truncate_bytes(s, 5), "你");
+        assert_eq!(truncate_bytes(s, 6), "你好");
+        assert_eq!(truncate_bytes(s, 100), s);
+        assert_eq!(floor_char_boundary(s, 10), 8);
+        assert_eq!(ceil_char_boundary(s, 10), 11);
+    }
+
+    #[test]
+    fn emoji() {
+        // emoji: 👍🏽 是 👍 加上肤色修饰符 🏽, 2 个 char, 8 个字节, 1 个字素簇.
+        // 👨‍👩‍👧 是 3 个 emoji 用 ZWJ (U+200D) 连起来, 5 个 char, 18 个字节, 1 个字素簇.
+        let s = "a👍🏽b👨‍👩‍👧c";
+        assert_eq!(char_slice(s, 1..2), Ok("👍"));
+        assert_eq!(grapheme_slice(s, 1..2), Ok("👍🏽"));
+        assert_eq!(grapheme_slice(s, 3..4), Ok("👨‍👩‍👧"));
+        assert_eq!(grapheme_slice(s, 4..), Ok("c"));
+        assert_eq!(
+            grapheme_slice(s, 4..6),
+            Err(SliceError::OutOfBounds { index: 6, len: 5 })
+        );
+        assert_eq!(truncate_bytes(s, 7), "a👍");
+        assert_eq!(truncate_graphemes(s, 7), "a");
+        assert_eq!(truncate_graphemes(s, 9), "a👍🏽");
+        assert_eq!(truncate_graphemes(s, 20), "a👍🏽b");
+        assert!(try_slice(s, ..2).is_err());
+    }
+
+    #[test]
+    fn combining_marks() {
+        // 组合字符序列: e + U+0301 (combining acute accent) 显示为 é
+        let s = "cafe\u{301}!";
+        assert_eq!(s.chars().count(), 6);
+        assert_eq!(char_slice(s, 3..4), Ok("e"));
+        assert_eq!(grapheme_slice(s, 3..4), Ok("e\u{301}"));
+        assert_eq!(truncate_bytes(s, 5), "cafe");
+        assert_eq!(truncate_bytes(s, 6), "cafe\u{301}");
+        assert_eq!(truncate_graphemes(s, 5), "caf");
+        assert_eq!(truncate_graphemes(s, 6), "cafe\u{301}");
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(char_slice("", ..), Ok(""));
+        assert_eq!(grapheme_slice("", ..), Ok(""));
+        assert_eq!(truncate_bytes("", 3), "");
+    }
+}
@@ -0,0 +1,235 @@
+// AuditLog: User 的修改记录
+//
+// 通过 UserStore::with_audit 打开之后, UserStore 的每次修改 (create / update / delete,
+// 以及 Authenticator 登录时的 record_sign_in) 都会追加记录: 时间, 操作者 (Actor), 用户, 发生了什么.
+// update 的记录由 UserPatch::diff 得到, 一次修改了几个字段就追加几条.
+//
+// 时间来自注入的 Clock. 记录按追加顺序排列, 时间不会倒退 (时钟回拨时沿用上一条的时间),
+// 所以按时间范围查询可以二分.
+//
+// InactivityPolicy: 超过 N 天没有登录的用户设为 active = false.
+// "最后一次活动" 取 store 中保存的最后登录时间, 从未登录过的取创建时间 (见 Account::last_active),
+// 与 Authenticator 读写的是同一份数据, 随 store 落盘, 重启后照样生效, 不需要打开审计.
+// 旧日志里既没有登录时间也没有创建时间的用户不处理.
+
+use crate::clock::Clock;
+use crate::patch::UserPatch;
+use crate::store::{StoreError, UserId, UserStore};
+use crate::User;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Actor {
+    // 系统任务, 例如 InactivityPolicy
+    System,
+    // 用户自己, 例如登录
+    User(UserId),
+    Admin(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum EventKind {
+    Created,
+    SignedIn,
+    UsernameChanged { from: String, to: String },
+    EmailChanged { from: String, to: String },
+    Activated,
+    Deactivated,
+    SignInCountChanged { from: u64, to: u64 },
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEvent {
+    pub at: SystemTime,
+    pub actor: Actor,
+    pub user: UserId,
+    pub kind: EventKind,
+}
+
+pub struct AuditLog {
+    events: Vec<AuditEvent>,
+    // 每个用户的记录在 events 中的下标
+    by_user: HashMap<UserId, Vec<usize>>,
+    clock: Box<dyn Clock>,
+}
+
+// 与 slice::partition_point 相同, 第一个使 pred 为 false 的下标
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+// 时间递增的 len 条记录中, 落在 range 内的下标范围. at(i) 是第 i 条的时间
+fn index_range(
+    range: &impl RangeBounds<SystemTime>,
+    len: usize,
+    at: impl Fn(usize) -> SystemTime,
+) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(t) => partition_point(len, |i| at(i) < *t),
+        Bound::Excluded(t) => partition_point(len, |i| at(i) <= *t),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(t) => partition_point(len, |i| at(i) <= *t),
+        Bound::Excluded(t) => partition_point(len, |i| at(i) < *t),
+        Bound::Unbounded => len,
+    };
+    (start, end.max(start))
+}
+
+impl AuditLog {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        AuditLog {
+            events: Vec::new(),
+            by_user: HashMap::new(),
+            clock: Box::new(clock),
+        }
+    }
+
+    fn push(&mut self, actor: &Actor, user: UserId, kind: EventKind) {
+        let mut at = self.clock.now();
+        if let Some(last) = self.events.last() {
+            at = at.max(last.at);
+        }
+        self.by_user
+            .entry(user)
+            .or_default()
+            .push(self.events.len());
+        self.events.push(AuditEvent {
+            at,
+            actor: actor.clone(),
+            user,
+            kind,
+        });
+    }
+
+    pub(crate) fn record_created(&mut self, actor: &Actor, id: UserId) {
+        self.push(actor, id, EventKind::Created);
+    }
+
+    pub(crate) fn record_deleted(&mut self, actor: &Actor, id: UserId) {
+        self.push(actor, id, EventKind::Deleted);
+    }
+
+    // sign_in 为 true 时, sign_in_count 的变化记录为 SignedIn
+    pub(crate) fn record_update(
+        &mut self,
+        actor: &Actor,
+        id: UserId,
+        before: &User,
+        after: &User,
+        sign_in: bool,
+    ) {
+        let patch = UserPatch::diff(before, after);
+        if let Some(username) = patch.username {
+            let kind = EventKind::UsernameChanged {
+                from: before.username.to_string(),
+                to: username.to_string(),
+            };
+            self.push(actor, id, kind);
+        }
+        if let Some(email) = patch.email {
+            let kind = EventKind::EmailChanged {
+                from: before.email.to_string(),
+                to: email.to_string(),
+            };
+            self.push(actor, id, kind);
+        }
+        match patch.active {
+            Some(true) => self.push(actor, id, EventKind::Activated),
+            Some(false) => self.push(actor, id, EventKind::Deactivated),
+            None => {}
+        }
+        if sign_in {
+            self.push(actor, id, EventKind::SignedIn);
+        } else if let Some(to) = patch.sign_in_count {
+            let kind = EventKind::SignInCountChanged {
+                from: before.sign_in_count,
+                to,
+            };
+            self.push(actor, id, kind);
+        }
+    }
+
+    pub fn events(&self) -> &[AuditEvent] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn for_user(&self, id: UserId) -> impl Iterator<Item = &AuditEvent> {
+        self.by_user
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.events[i])
+    }
+
+    pub fn between(&self, range: impl RangeBounds<SystemTime>) -> &[AuditEvent] {
+        let (start, end) = index_range(&range, self.events.len(), |i| self.events[i].at);
+        &self.events[start..end]
+    }
+
+    pub fn for_user_between(
+        &self,
+        id: UserId,
+        range: impl RangeBounds<SystemTime>,
+    ) -> impl Iterator<Item = &AuditEvent> {
+        let indices = self.by_user.get(&id).map_or(&[][..], |v| &v[..]);
+        let (start, end) = index_range(&range, indices.len(), |i| self.events[indices[i]].at);
+        indices[start..end].iter().map(|&i| &self.events[i])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InactivityPolicy {
+    pub max_idle_days: u64,
+}
+
+impl InactivityPolicy {
+    pub fn new(max_idle_days: u64) -> Self {
+        InactivityPolicy { max_idle_days }
+    }
+
+    // 天数很大时停在 u64::MAX 秒, 相当于永远不停用
+    pub fn max_idle(&self) -> Duration {
+        Duration::from_secs(self.max_idle_days.saturating_mul(24 * 60 * 60))
+    }
+
+    // 返回被停用的用户, 时间取 store 的 Clock
+    pub fn run(&self, store: &mut UserStore) -> Result<Vec<UserId>, StoreError> {
+        let now = store.now();
+        let idle: Vec<UserId> = store
+            .iter()
+            .filter(|(_, user)| user.active)
+            .filter_map(|(id, _)| {
+                let last = store.account(id)?.last_active()?;
+                let elapsed = now.duration_since(last).unwrap_or_default();
+                (elapsed >= self.max_idle()).then_some(id)
+            })
+            .collect();
+        for &id in &idle {
+            store.deactivate_as(Actor::System, id)?;
+        }
+        Ok(idle)
+    }
+}
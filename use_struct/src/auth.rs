@@ -0,0 +1,335 @@
+// Authenticator: 密码登录, 登录失败锁定, 会话令牌
+//
+// 密码: 使用 Argon2id (内存困难的 KDF) 加随机 salt 哈希, 保存为 PHC 字符串
+//   $argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>
+// 参数和 salt 都在字符串里, 以后调大参数也不影响旧密码的校验.
+// 校验由 argon2 完成, 最后一步的比较是常数时间的.
+//
+// 登录: sign_in(username, password) 成功后 sign_in_count + 1, 在 store 中记录最后登录时间, 返回会话令牌.
+// 用户名不存在 (或者还没有设置密码) 时也会对一个假的哈希做一次校验, 这样响应时间不会泄露用户名是否存在;
+// 错误也都是 InvalidCredentials, 不区分 "用户不存在" 和 "密码错误".
+//
+// 锁定: 连续失败 max_failed_attempts 次后锁定 lockout 时长, 锁定期间即使密码正确也拒绝.
+// 成功登录会清零失败次数.
+//
+// 会话令牌: 32 字节随机数的十六进制, 对调用者是不透明的.
+// 服务端只保存令牌的 SHA-256, 会话表泄露也不能直接拿来冒充用户.
+// 令牌有过期时间, validate 时检查, 过期的会话顺便删除.
+// validate 还会到 store 里确认用户仍然存在且处于激活状态, 停用或删除用户后他的会话立即作废.
+//
+// 持久化: 密码哈希和最后登录时间保存在 UserStore 的 Account 里, 随 store 的日志落盘, 重启后仍然有效.
+// 失败次数, 锁定状态和会话只在内存中: 重启之后所有用户需要重新登录, 进行中的锁定也会解除.
+// lockout 和 session_ttl 很大 (例如 Duration::MAX) 时, 到期时间取一个很远的时间, 不会溢出.
+
+use crate::clock::{Clock, SystemClock};
+use crate::store::{StoreError, UserId, UserStore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+#[derive(Debug)]
+pub enum AuthError {
+    // 用户名不存在或者密码错误
+    InvalidCredentials,
+    Locked { until: SystemTime },
+    Inactive,
+    WeakPassword { len: usize },
+    InvalidToken,
+    Expired,
+    Hash(argon2::password_hash::Error),
+    Store(StoreError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::InvalidCredentials => write!(f, "invalid username or password"),
+            AuthError::Locked { until } => write!(f, "account is locked until {:?}", until),
+            AuthError::Inactive => write!(f, "account is deactivated"),
+            AuthError::WeakPassword { len } => write!(
+                f,
+                "password is {} characters, at least {} required",
+                len, MIN_PASSWORD_LEN
+            ),
+            AuthError::InvalidToken => write!(f, "invalid session token"),
+            AuthError::Expired => write!(f, "session has expired"),
+            AuthError::Hash(e) => write!(f, "password hash error: {}", e),
+            AuthError::Store(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<StoreError> for AuthError {
+    fn from(e: StoreError) -> Self {
+        AuthError::Store(e)
+    }
+}
+
+impl From<argon2::password_hash::Error> for AuthError {
+    fn from(e: argon2::password_hash::Error) -> Self {
+        AuthError::Hash(e)
+    }
+}
+
+// 不透明的会话令牌, Debug 不打印内容, 避免被写进日志
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionToken(String);
+
+impl SessionToken {
+    fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        SessionToken(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionToken(..)")
+    }
+}
+
+// 只在内存中的登录状态, 密码哈希在 UserStore 的 Account 里
+#[derive(Debug, Clone, Default)]
+struct Credential {
+    failed_attempts: u32,
+    locked_until: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    user: UserId,
+    expires_at: SystemTime,
+}
+
+pub struct Authenticator<C: Clock = SystemClock> {
+    credentials: HashMap<UserId, Credential>,
+    // key 是令牌的 SHA-256
+    sessions: HashMap<[u8; 32], Session>,
+    max_failed_attempts: u32,
+    lockout: Duration,
+    session_ttl: Duration,
+    params: Params,
+    // 用户名不存在或没有密码时用来校验的哈希, 第一次需要时生成
+    dummy_hash: Option<String>,
+    clock: C,
+}
+
+fn token_key(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+// 大约 100 年, SystemTime + Duration 溢出时用来代替 "永远"
+const FOREVER: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+fn saturating_add(t: SystemTime, d: Duration) -> SystemTime {
+    t.checked_add(d)
+        .or_else(|| t.checked_add(FOREVER))
+        .unwrap_or(t)
+}
+
+impl Authenticator<SystemClock> {
+    pub fn new() -> Self {
+        Authenticator::with_clock(SystemClock)
+    }
+}
+
+impl Default for Authenticator<SystemClock> {
+    fn default() -> Self {
+        Authenticator::new()
+    }
+}
+
+impl<C: Clock> Authenticator<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Authenticator {
+            credentials: HashMap::new(),
+            sessions: HashMap::new(),
+            max_failed_attempts: 5,
+            lockout: Duration::from_secs(15 * 60),
+            session_ttl: Duration::from_secs(24 * 60 * 60),
+            params: Params::DEFAULT,
+            dummy_hash: None,
+            clock,
+        }
+    }
+
+    pub fn max_failed_attempts(mut self, n: u32) -> Self {
+        self.max_failed_attempts = n.max(1);
+        self
+    }
+
+    pub fn lockout(mut self, d: Duration) -> Self {
+        self.lockout = d;
+        self
+    }
+
+    pub fn session_ttl(mut self, d: Duration) -> Self {
+        self.session_ttl = d;
+        self
+    }
+
+    // 新哈希使用的 Argon2 参数: 内存 (KiB), 迭代次数, 并行度. 默认值是 OWASP 推荐的 19 MiB, 2, 1
+    pub fn kdf_params(mut self, m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Self, AuthError> {
+        self.params = Params::new(m_cost, t_cost, p_cost, None)
+            .map_err(argon2::password_hash::Error::from)?;
+        self.dummy_hash = None;
+        Ok(self)
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    fn hash(&self, password: &str) -> Result<String, AuthError> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(self
+            .argon2()
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string())
+    }
+
+    // 参数从 PHC 字符串里读取, 与当前的 kdf_params 无关
+    fn verify(password: &str, hash: &str) -> Result<bool, AuthError> {
+        let parsed = PasswordHash::new(hash)?;
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // 哈希写入 store, 用户不存在时返回 StoreError::NotFound
+    pub fn set_password(
+        &mut self,
+        store: &mut UserStore,
+        id: UserId,
+        password: &str,
+    ) -> Result<(), AuthError> {
+        let len = password.chars().count();
+        if len < MIN_PASSWORD_LEN {
+            return Err(AuthError::WeakPassword { len });
+        }
+        let hash = self.hash(password)?;
+        store.set_password_hash(id, hash)?;
+        self.credentials.remove(&id);
+        Ok(())
+    }
+
+    pub fn sign_in(
+        &mut self,
+        store: &mut UserStore,
+        username: &str,
+        password: &str,
+    ) -> Result<SessionToken, AuthError> {
+        let now = self.clock.now();
+        let found = store
+            .find_by_username(username)
+            .map(|(id, u)| (id, u.active));
+        let hash = found.and_then(|(id, _)| store.account(id)?.password_hash.clone());
+        let (Some((id, active)), Some(hash)) = (found, hash) else {
+            // 与正常的校验花费同样的时间
+            if self.dummy_hash.is_none() {
+                self.dummy_hash = Some(self.hash("not a real password")?);
+            }
+            Self::verify(password, self.dummy_hash.as_ref().unwrap())?;
+            return Err(AuthError::InvalidCredentials);
+        };
+
+        let credential = self.credentials.entry(id).or_default();
+        if let Some(until) = credential.locked_until {
+            if now < until {
+                return Err(AuthError::Locked { until });
+            }
+            credential.locked_until = None;
+        }
+        if !Self::verify(password, &hash)? {
+            credential.failed_attempts += 1;
+            if credential.failed_attempts >= self.max_failed_attempts {
+                credential.failed_attempts = 0;
+                let until = saturating_add(now, self.lockout);
+                credential.locked_until = Some(until);
+                return Err(AuthError::Locked { until });
+            }
+            return Err(AuthError::InvalidCredentials);
+        }
+        credential.failed_attempts = 0;
+        // 密码正确才告诉调用者账号被停用了
+        if !active {
+            return Err(AuthError::Inactive);
+        }
+
+        store.record_sign_in(id, now)?;
+        let token = SessionToken::generate();
+        self.sessions.insert(
+            token_key(token.as_str()),
+            Session {
+                user: id,
+                expires_at: saturating_add(now, self.session_ttl),
+            },
+        );
+        Ok(token)
+    }
+
+    // 令牌有效且用户仍然存在并处于激活状态时返回对应的用户.
+    // 用户被停用 (包括 InactivityPolicy 停用) 或删除后, 他的会话随之作废
+    pub fn validate(&mut self, store: &UserStore, token: &str) -> Result<UserId, AuthError> {
+        let key = token_key(token);
+        let session = self.sessions.get(&key).ok_or(AuthError::InvalidToken)?;
+        if self.clock.now() >= session.expires_at {
+            self.sessions.remove(&key);
+            return Err(AuthError::Expired);
+        }
+        let id = session.user;
+        let result = match store.get(id) {
+            None => Err(AuthError::InvalidToken),
+            Some(user) if !user.active => Err(AuthError::Inactive),
+            Some(_) => return Ok(id),
+        };
+        self.sessions.remove(&key);
+        result
+    }
+
+    // 返回令牌是否存在
+    pub fn sign_out(&mut self, token: &str) -> bool {
+        self.sessions.remove(&token_key(token)).is_some()
+    }
+
+    // 使某个用户的所有会话失效, 例如修改密码之后
+    pub fn sign_out_all(&mut self, id: UserId) -> usize {
+        let before = self.sessions.len();
+        self.sessions.retain(|_, s| s.user != id);
+        before - self.sessions.len()
+    }
+
+    // 删除所有过期的会话, 返回删除的数量
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let before = self.sessions.len();
+        self.sessions.retain(|_, s| now < s.expires_at);
+        before - self.sessions.len()
+    }
+
+    pub fn failed_attempts(&self, id: UserId) -> u32 {
+        self.credentials.get(&id).map_or(0, |c| c.failed_attempts)
+    }
+}
@@ -0,0 +1,50 @@
+// Clock: 可以替换的时间来源
+//
+// 登录锁定, 会话过期这些逻辑都依赖当前时间. 直接调用 SystemTime::now() 的话,
+// 演示和断言就只能真的 sleep 等待; 通过 Clock 注入, 可以用 ManualClock 手动拨动时间.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// clone 出来的 ManualClock 共享同一个时间, 一个拨动, 其它的都能看到
+#[derive(Debug, Clone)]
+pub struct ManualClock(Arc<Mutex<SystemTime>>);
+
+impl ManualClock {
+    pub fn new(start: SystemTime) -> Self {
+        ManualClock(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.0.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, d: Duration) {
+        *self.0.lock().unwrap() += d;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
@@ -0,0 +1,376 @@
+// User 列表的 CSV 导入导出
+//
+// 格式 (RFC 4180):
+//   第一行是表头 username,email,active,sign_in_count
+//   行以 CRLF 结尾 (导入时也接受 LF)
+//   字段含有 "," '"' CR 或 LF 时用双引号括起来, 字段内的 '"' 写成 ""
+//   被引号括起来的字段可以跨行
+//
+// 导入:
+//   表头按名字匹配 (忽略大小写和首尾空白), 列的顺序可以不同, 多出来的列忽略 (可以重名, 例如结尾的空列);
+//   缺少列或者 HEADER 里的列重复则整个导入失败.
+//   active 接受 true/false, yes/no, y/n, t/f, 1/0 (忽略大小写).
+//   sign_in_count 是非负整数, 空值当作 0.
+//   username 和 email 按 Username / Email 的规则校验.
+//   每一行单独校验, 出错的行记录到 ImportReport::errors (行号, 列, 原因), 不影响其它行.
+//   行号是这一条记录在文件中开始的物理行号, 从 1 开始, 表头是第 1 行.
+//   空行跳过.
+
+use crate::email::Email;
+use crate::store::{StoreError, UserId, UserStore};
+use crate::username::Username;
+use crate::User;
+use std::fmt;
+
+pub const HEADER: [&str; 4] = ["username", "email", "active", "sign_in_count"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub line: usize,
+    // None 表示整行的问题, 例如字段数不对
+    pub column: Option<&'static str>,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.reason),
+            None => write!(f, "line {}: {}", self.line, self.reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvError {
+    Empty,
+    // 表头这一行本身无法解析
+    BadHeader(RowError),
+    MissingColumn(&'static str),
+    DuplicateColumn(String),
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Empty => write!(f, "CSV has no header row"),
+            CsvError::BadHeader(e) => write!(f, "malformed header: {}", e),
+            CsvError::MissingColumn(name) => write!(f, "header is missing column {:?}", name),
+            CsvError::DuplicateColumn(name) => write!(f, "header has column {:?} twice", name),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+#[derive(Debug)]
+pub struct ImportReport<T> {
+    // (行号, 导入的结果)
+    pub imported: Vec<(usize, T)>,
+    pub errors: Vec<RowError>,
+}
+
+impl<T> ImportReport<T> {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+fn needs_quotes(field: &str) -> bool {
+    field.contains([',', '"', '\r', '\n'])
+}
+
+fn write_field(out: &mut String, field: &str) {
+    if needs_quotes(field) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+pub fn write_record<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_field(out, field.as_ref());
+    }
+    out.push_str("\r\n");
+}
+
+pub fn export<'a>(users: impl IntoIterator<Item = &'a User>) -> String {
+    let mut out = String::new();
+    write_record(&mut out, &HEADER);
+    for user in users {
+        write_record(
+            &mut out,
+            &[
+                user.username.as_str(),
+                user.email.as_str(),
+                if user.active { "true" } else { "false" },
+                &user.sign_in_count.to_string(),
+            ],
+        );
+    }
+    out
+}
+
+// 一条记录: 开始的行号和字段. 结构错误 (例如引号没有闭合) 返回 Err
+pub type Record = Result<(usize, Vec<String>), RowError>;
+
+// RFC 4180 的解析器, 按记录迭代
+pub struct Records<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+pub fn records(input: &str) -> Records<'_> {
+    // 忽略 UTF-8 BOM, Excel 导出的 CSV 常常带有
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    Records {
+        input,
+        pos: 0,
+        line: 1,
+    }
+}
+
+impl Records<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    // 在行尾时消耗换行符 (CRLF, LF 或单独的 CR) 并返回 true
+    fn eat_newline(&mut self) -> bool {
+        let rest = &self.input[self.pos..];
+        let n = if rest.starts_with("\r\n") {
+            2
+        } else if rest.starts_with('\n') || rest.starts_with('\r') {
+            1
+        } else {
+            return false;
+        };
+        self.pos += n;
+        self.line += 1;
+        true
+    }
+
+    // 出错之后跳到下一行, 继续解析后面的记录
+    fn skip_line(&mut self) {
+        while self.pos < self.input.len() && !self.eat_newline() {
+            self.pos += self.peek().unwrap().len_utf8();
+        }
+    }
+
+    fn quoted(&mut self, start_line: usize) -> Result<String, RowError> {
+        let mut field = String::new();
+        // 跳过开头的引号
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(RowError {
+                        line: start_line,
+                        column: None,
+                        reason: String::from("quoted field is not closed"),
+                    })
+                }
+                Some('"') => {
+                    self.pos += 1;
+                    if self.peek() == Some('"') {
+                        self.pos += 1;
+                        field.push('"');
+                    } else {
+                        break;
+                    }
+                }
+                Some(c) => {
+                    if c == '\n' || (c == '\r' && !self.input[self.pos..].starts_with("\r\n")) {
+                        self.line += 1;
+                    }
+                    field.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+        match self.peek() {
+            None | Some(',' | '\r' | '\n') => Ok(field),
+            Some(c) => Err(RowError {
+                line: self.line,
+                column: None,
+                reason: format!("unexpected {:?} after closing quote", c),
+            }),
+        }
+    }
+
+    fn unquoted(&mut self) -> Result<String, RowError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            match c {
+                ',' | '\r' | '\n' => break,
+                '"' => {
+                    return Err(RowError {
+                        line: self.line,
+                        column: None,
+                        reason: String::from("quote inside unquoted field"),
+                    })
+                }
+                _ => self.pos += c.len_utf8(),
+            }
+        }
+        Ok(self.input[start..self.pos].to_string())
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        // 跳过空行
+        while self.eat_newline() {}
+        if self.pos >= self.input.len() {
+            return None;
+        }
+        let start_line = self.line;
+        let mut fields = Vec::new();
+        loop {
+            let field = if self.peek() == Some('"') {
+                self.quoted(start_line)
+            } else {
+                self.unquoted()
+            };
+            match field {
+                Ok(field) => fields.push(field),
+                Err(e) => {
+                    self.skip_line();
+                    return Some(Err(e));
+                }
+            }
+            if self.peek() == Some(',') {
+                self.pos += 1;
+                continue;
+            }
+            self.eat_newline();
+            return Some(Ok((start_line, fields)));
+        }
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "t" | "1" => Some(true),
+        "false" | "no" | "n" | "f" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_row(line: usize, get: impl Fn(usize) -> String) -> Result<User, RowError> {
+    let err = |column, reason: String| RowError {
+        line,
+        column: Some(column),
+        reason,
+    };
+    let username = Username::parse(&get(0)).map_err(|e| err("username", e.to_string()))?;
+    let email = Email::parse(&get(1)).map_err(|e| err("email", e.to_string()))?;
+    let active = get(2);
+    let active = parse_bool(&active)
+        .ok_or_else(|| err("active", format!("{:?} is not a boolean", active)))?;
+    let count = get(3);
+    let sign_in_count = match count.trim() {
+        "" => 0,
+        s => s.parse().map_err(|_| {
+            err(
+                "sign_in_count",
+                format!("{:?} is not a non-negative integer", count),
+            )
+        })?,
+    };
+    Ok(User {
+        username,
+        email,
+        active,
+        sign_in_count,
+    })
+}
+
+// 解析 CSV, 表头有问题时返回 Err, 否则返回每一行的结果
+pub fn import(input: &str) -> Result<ImportReport<User>, CsvError> {
+    let mut records = records(input);
+    let header = match records.next() {
+        None => return Err(CsvError::Empty),
+        Some(Err(e)) => return Err(CsvError::BadHeader(e)),
+        Some(Ok((_, fields))) => fields,
+    };
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+    // 多出来的列会被忽略, 只有我们要读的列重复时才有歧义
+    for (i, name) in header.iter().enumerate() {
+        if HEADER.contains(&name.as_str()) && header[..i].contains(name) {
+            return Err(CsvError::DuplicateColumn(name.clone()));
+        }
+    }
+    // positions[k] 是 HEADER[k] 在文件中的列号
+    let mut positions = [0; HEADER.len()];
+    for (k, name) in HEADER.iter().enumerate() {
+        positions[k] = header
+            .iter()
+            .position(|h| h == name)
+            .ok_or(CsvError::MissingColumn(name))?;
+    }
+
+    let mut report = ImportReport {
+        imported: Vec::new(),
+        errors: Vec::new(),
+    };
+    for record in records {
+        let (line, fields) = match record {
+            Ok(record) => record,
+            Err(e) => {
+                report.errors.push(e);
+                continue;
+            }
+        };
+        if fields.len() != header.len() {
+            report.errors.push(RowError {
+                line,
+                column: None,
+                reason: format!("expected {} fields, found {}", header.len(), fields.len()),
+            });
+            continue;
+        }
+        match parse_row(line, |k| fields[positions[k]].clone()) {
+            Ok(user) => report.imported.push((line, user)),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    Ok(report)
+}
+
+// 导入并写入 store, 唯一性冲突也记录为该行的错误
+pub fn import_into(store: &mut UserStore, input: &str) -> Result<ImportReport<UserId>, CsvError> {
+    let parsed = import(input)?;
+    let mut report = ImportReport {
+        imported: Vec::new(),
+        errors: parsed.errors,
+    };
+    for (line, user) in parsed.imported {
+        match store.create(user) {
+            Ok(id) => report.imported.push((line, id)),
+            Err(e) => {
+                let column = match e {
+                    StoreError::DuplicateUsername(_) => Some("username"),
+                    StoreError::DuplicateEmail(_) => Some("email"),
+                    _ => None,
+                };
+                report.errors.push(RowError {
+                    line,
+                    column,
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+    report.errors.sort_by_key(|e| e.line);
+    Ok(report)
+}
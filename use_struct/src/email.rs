@@ -0,0 +1,465 @@
+// Email: 经过校验和规范化的邮箱地址
+//
+// 支持的是 RFC 5322 addr-spec 的一个子集:
+//   addr-spec  = local-part "@" domain
+//   local-part = dot-atom, 即用 "." 连接的若干段 atext, "." 不能在开头结尾, 也不能连续
+//   atext      = ALPHA / DIGIT / "!" / "#" / "$" / "%" / "&" / "'" / "*" / "+" / "-" /
+//                "/" / "=" / "?" / "^" / "_" / "`" / "{" / "|" / "}" / "~"
+//   domain     = 用 "." 连接的至少两个 label, 每个 label 由字母, 数字和 "-" 组成,
+//                "-" 不能在开头结尾, 顶级域名不能全是数字
+// 不支持: 引号括起来的 local-part ("john doe"@example.com), 注释 (CFWS),
+// 域名字面量 (user@[192.0.2.1]), 非 ASCII 的 local-part (RFC 6531).
+//
+// 长度限制 (RFC 5321): local-part 最多 64 字节, label 最多 63 字节,
+// domain 最多 253 字节, 整个地址最多 254 字节. 都是按转换成 punycode 之后计算的.
+//
+// 国际化域名: 含非 ASCII 字符的 label 先转成小写, 再用 punycode (RFC 3492) 编码成 "xn--" 开头的形式,
+// 所以 "用户@例子.测试" 被拒绝 (local-part 非 ASCII), "a@例子.测试" 保存为 "a@xn--fsqu00a.xn--0zwm56d".
+// 这里只做了小写映射, 没有实现完整的 IDNA2008 / UTS #46 规则.
+// 已经是 "xn--" 形式的 label 必须能解码, 并且解码后确实含有非 ASCII 字符.
+//
+// 规范化: domain 转成小写. local-part 按 RFC 是区分大小写的, 保持原样;
+// UserStore 比较 email 时不区分大小写, 见 store.rs.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+pub const MAX_LEN: usize = 254;
+pub const MAX_LOCAL_LEN: usize = 64;
+pub const MAX_DOMAIN_LEN: usize = 253;
+pub const MAX_LABEL_LEN: usize = 63;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailError {
+    Empty,
+    MissingAt,
+    EmptyLocalPart,
+    LocalPartTooLong { len: usize },
+    // local-part 中不允许的字符, index 是在输入中的字节位置
+    InvalidLocalChar { ch: char, index: usize },
+    // "." 在 local-part 的开头, 结尾或者连续出现
+    MisplacedDot { index: usize },
+    QuotedLocalPart,
+    EmptyDomain,
+    DomainLiteral,
+    SingleLabelDomain,
+    // 第 label 个 label (从 0 开始) 为空, 即 domain 中有连续的 "." 或者以 "." 开头结尾
+    EmptyLabel { label: usize },
+    LabelTooLong { label: String, len: usize },
+    InvalidLabelChar { label: String, ch: char },
+    LabelHyphen { label: String },
+    NumericTld { tld: String },
+    InvalidPunycode { label: String },
+    DomainTooLong { len: usize },
+    TooLong { len: usize },
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailError::Empty => write!(f, "email is empty"),
+            EmailError::MissingAt => write!(f, "email must contain '@'"),
+            EmailError::EmptyLocalPart => write!(f, "local part before '@' is empty"),
+            EmailError::LocalPartTooLong { len } => {
+                write!(
+                    f,
+                    "local part is {} bytes, at most {} allowed",
+                    len, MAX_LOCAL_LEN
+                )
+            }
+            EmailError::InvalidLocalChar { ch, index } => {
+                write!(
+                    f,
+                    "character {:?} at {} is not allowed in local part",
+                    ch, index
+                )
+            }
+            EmailError::MisplacedDot { index } => {
+                write!(
+                    f,
+                    "'.' at {} is at the start, end, or next to another '.'",
+                    index
+                )
+            }
+            EmailError::QuotedLocalPart => write!(f, "quoted local parts are not supported"),
+            EmailError::EmptyDomain => write!(f, "domain after '@' is empty"),
+            EmailError::DomainLiteral => {
+                write!(f, "domain literals like [192.0.2.1] are not supported")
+            }
+            EmailError::SingleLabelDomain => write!(f, "domain must have at least two labels"),
+            EmailError::EmptyLabel { label } => write!(f, "domain label {} is empty", label),
+            EmailError::LabelTooLong { label, len } => {
+                write!(
+                    f,
+                    "domain label {:?} is {} bytes, at most {} allowed",
+                    label, len, MAX_LABEL_LEN
+                )
+            }
+            EmailError::InvalidLabelChar { label, ch } => {
+                write!(
+                    f,
+                    "character {:?} is not allowed in domain label {:?}",
+                    ch, label
+                )
+            }
+            EmailError::LabelHyphen { label } => {
+                write!(f, "domain label {:?} starts or ends with '-'", label)
+            }
+            EmailError::NumericTld { tld } => write!(f, "top-level domain {:?} is all digits", tld),
+            EmailError::InvalidPunycode { label } => {
+                write!(f, "domain label {:?} is not valid punycode", label)
+            }
+            EmailError::DomainTooLong { len } => {
+                write!(
+                    f,
+                    "domain is {} bytes, at most {} allowed",
+                    len, MAX_DOMAIN_LEN
+                )
+            }
+            EmailError::TooLong { len } => {
+                write!(f, "email is {} bytes, at most {} allowed", len, MAX_LEN)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmailError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Email {
+    // 规范化后的 "local@domain"
+    addr: String,
+    // "@" 的位置
+    at: usize,
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+fn parse_local(local: &str) -> Result<(), EmailError> {
+    if local.is_empty() {
+        return Err(EmailError::EmptyLocalPart);
+    }
+    if local.starts_with('"') {
+        return Err(EmailError::QuotedLocalPart);
+    }
+    if local.len() > MAX_LOCAL_LEN {
+        return Err(EmailError::LocalPartTooLong { len: local.len() });
+    }
+    let mut prev_dot = true;
+    for (index, ch) in local.char_indices() {
+        if ch == '.' {
+            if prev_dot {
+                return Err(EmailError::MisplacedDot { index });
+            }
+            prev_dot = true;
+        } else if is_atext(ch) {
+            prev_dot = false;
+        } else {
+            return Err(EmailError::InvalidLocalChar { ch, index });
+        }
+    }
+    if prev_dot {
+        return Err(EmailError::MisplacedDot {
+            index: local.len() - 1,
+        });
+    }
+    Ok(())
+}
+
+// 返回规范化后的 label: 小写, 非 ASCII 的转成 punycode
+fn parse_label(label: &str) -> Result<String, EmailError> {
+    let label = label.to_lowercase();
+    // punycode 编码后不会比原来的 char 数更短, 太长的 label 不必编码 (解码) 就可以拒绝,
+    // 也避免了为很长的输入做 O(n^2) 的编码
+    let chars = label.chars().count();
+    if chars > MAX_LABEL_LEN {
+        return Err(EmailError::LabelTooLong { label, len: chars });
+    }
+    let ascii = if label.is_ascii() {
+        if let Some(encoded) = label.strip_prefix("xn--") {
+            match punycode::decode(encoded) {
+                Some(decoded) if !decoded.is_ascii() => {}
+                _ => return Err(EmailError::InvalidPunycode { label }),
+            }
+        }
+        label
+    } else {
+        let invalid = label
+            .chars()
+            .find(|&c| c.is_ascii() && !(c.is_ascii_alphanumeric() || c == '-'));
+        if let Some(ch) = invalid {
+            return Err(EmailError::InvalidLabelChar { label, ch });
+        }
+        match punycode::encode(&label) {
+            Some(encoded) => format!("xn--{}", encoded),
+            None => {
+                let len = label.len();
+                return Err(EmailError::LabelTooLong { label, len });
+            }
+        }
+    };
+    if ascii.len() > MAX_LABEL_LEN {
+        return Err(EmailError::LabelTooLong {
+            len: ascii.len(),
+            label: ascii,
+        });
+    }
+    if let Some(ch) = ascii
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '-'))
+    {
+        return Err(EmailError::InvalidLabelChar { label: ascii, ch });
+    }
+    if ascii.starts_with('-') || ascii.ends_with('-') {
+        return Err(EmailError::LabelHyphen { label: ascii });
+    }
+    Ok(ascii)
+}
+
+fn parse_domain(domain: &str) -> Result<String, EmailError> {
+    if domain.is_empty() {
+        return Err(EmailError::EmptyDomain);
+    }
+    if domain.starts_with('[') {
+        return Err(EmailError::DomainLiteral);
+    }
+    let mut labels = Vec::new();
+    for (i, label) in domain.split('.').enumerate() {
+        if label.is_empty() {
+            return Err(EmailError::EmptyLabel { label: i });
+        }
+        labels.push(parse_label(label)?);
+    }
+    if labels.len() < 2 {
+        return Err(EmailError::SingleLabelDomain);
+    }
+    let tld = labels.last().unwrap();
+    if tld.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(EmailError::NumericTld { tld: tld.clone() });
+    }
+    let domain = labels.join(".");
+    if domain.len() > MAX_DOMAIN_LEN {
+        return Err(EmailError::DomainTooLong { len: domain.len() });
+    }
+    Ok(domain)
+}
+
+impl Email {
+    pub fn parse(s: &str) -> Result<Email, EmailError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(EmailError::Empty);
+        }
+        // local-part 里不能有 "@" (不支持引号), 所以最后一个 "@" 就是分隔符
+        let at = s.rfind('@').ok_or(EmailError::MissingAt)?;
+        let local = &s[..at];
+        parse_local(local)?;
+        let domain = parse_domain(&s[at + 1..])?;
+        let addr = format!("{}@{}", local, domain);
+        if addr.len() > MAX_LEN {
+            return Err(EmailError::TooLong { len: addr.len() });
+        }
+        Ok(Email { addr, at })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn local_part(&self) -> &str {
+        &self.addr[..self.at]
+    }
+
+    // ASCII 形式, 国际化域名是 "xn--" 开头的
+    pub fn domain(&self) -> &str {
+        &self.addr[self.at + 1..]
+    }
+
+    // 把 punycode 的 label 解码回 Unicode, 用于显示
+    pub fn unicode_domain(&self) -> String {
+        self.domain()
+            .split('.')
+            .map(|label| match label.strip_prefix("xn--") {
+                Some(encoded) => punycode::decode(encoded).expect("validated in parse"),
+                None => label.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl fmt::Display for Email {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.addr)
+    }
+}
+
+impl FromStr for Email {
+    type Err = EmailError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Email::parse(s)
+    }
+}
+
+impl TryFrom<String> for Email {
+    type Error = EmailError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Email::parse(&s)
+    }
+}
+
+impl From<Email> for String {
+    fn from(email: Email) -> String {
+        email.addr
+    }
+}
+
+impl AsRef<str> for Email {
+    fn as_ref(&self) -> &str {
+        &self.addr
+    }
+}
+
+impl PartialEq<str> for Email {
+    fn eq(&self, other: &str) -> bool {
+        self.addr == other
+    }
+}
+
+impl PartialEq<&str> for Email {
+    fn eq(&self, other: &&str) -> bool {
+        self.addr == *other
+    }
+}
+
+// RFC 3492 Punycode, 只处理一个 label, 不含 "xn--" 前缀
+mod punycode {
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+    const SKEW: u32 = 38;
+    const DAMP: u32 = 700;
+    const INITIAL_BIAS: u32 = 72;
+    const INITIAL_N: u32 = 128;
+
+    fn adapt(mut delta: u32, num_points: u32, first_time: bool) -> u32 {
+        delta /= if first_time { DAMP } else { 2 };
+        delta += delta / num_points;
+        let mut k = 0;
+        while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+            delta /= BASE - T_MIN;
+            k += BASE;
+        }
+        k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+    }
+
+    fn threshold(k: u32, bias: u32) -> u32 {
+        if k <= bias {
+            T_MIN
+        } else if k >= bias + T_MAX {
+            T_MAX
+        } else {
+            k - bias
+        }
+    }
+
+    fn encode_digit(d: u32) -> char {
+        match d {
+            0..=25 => (b'a' + d as u8) as char,
+            _ => (b'0' + (d - 26) as u8) as char,
+        }
+    }
+
+    fn decode_digit(c: char) -> Option<u32> {
+        match c {
+            'a'..='z' => Some(c as u32 - 'a' as u32),
+            'A'..='Z' => Some(c as u32 - 'A' as u32),
+            '0'..='9' => Some(c as u32 - '0' as u32 + 26),
+            _ => None,
+        }
+    }
+
+    // 输入太长, 中间结果溢出时返回 None
+    pub fn encode(input: &str) -> Option<String> {
+        let chars: Vec<u32> = input.chars().map(|c| c as u32).collect();
+        let mut output: String = input.chars().filter(char::is_ascii).collect();
+        let basic = u32::try_from(output.len()).ok()?;
+        let mut handled = basic;
+        if basic > 0 {
+            output.push('-');
+        }
+        let (mut n, mut delta, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+        while (handled as usize) < chars.len() {
+            let m = chars.iter().copied().filter(|&c| c >= n).min().unwrap();
+            delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+            n = m;
+            for &c in &chars {
+                if c < n {
+                    delta = delta.checked_add(1)?;
+                }
+                if c == n {
+                    let mut q = delta;
+                    let mut k = BASE;
+                    loop {
+                        let t = threshold(k, bias);
+                        if q < t {
+                            break;
+                        }
+                        output.push(encode_digit(t + (q - t) % (BASE - t)));
+                        q = (q - t) / (BASE - t);
+                        k += BASE;
+                    }
+                    output.push(encode_digit(q));
+                    bias = adapt(delta, handled + 1, handled == basic);
+                    delta = 0;
+                    handled += 1;
+                }
+            }
+            delta = delta.checked_add(1)?;
+            n += 1;
+        }
+        Some(output)
+    }
+
+    // 输入不合法或者溢出时返回 None
+    pub fn decode(input: &str) -> Option<String> {
+        let (basic, extended) = match input.rfind('-') {
+            Some(i) => (&input[..i], &input[i + 1..]),
+            None => ("", input),
+        };
+        if !basic.is_ascii() {
+            return None;
+        }
+        let mut output: Vec<char> = basic.chars().collect();
+        let (mut n, mut i, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+        let mut digits = extended.chars().peekable();
+        while digits.peek().is_some() {
+            let old_i = i;
+            let mut w = 1u32;
+            let mut k = BASE;
+            loop {
+                let digit = decode_digit(digits.next()?)?;
+                i = i.checked_add(digit.checked_mul(w)?)?;
+                let t = threshold(k, bias);
+                if digit < t {
+                    break;
+                }
+                w = w.checked_mul(BASE - t)?;
+                k += BASE;
+            }
+            let len = output.len() as u32 + 1;
+            bias = adapt(i - old_i, len, old_i == 0);
+            n = n.checked_add(i / len)?;
+            i %= len;
+            output.insert(i as usize, char::from_u32(n)?);
+            i += 1;
+        }
+        Some(output.into_iter().collect())
+    }
+}
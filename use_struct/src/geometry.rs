@@ -0,0 +1,287 @@
+// 带位置的矩形 Rect<T>
+//
+// main.rs 里的 Rectangle 只有宽和高, 没有位置, 所以无法求交集, 并集, 判断点是否在里面.
+// Rect<T> 用左上角 (x, y) 加上宽高表示, 坐标类型 T 是泛型的, i32 / u32 / f64 等共用一份实现.
+//
+// 约定:
+//   x 轴向右, y 轴向下, min() 是左上角, max() 是右下角 (x + width, y + height)
+//   宽高不会是负数, new 和 from_corners 会把负的宽高翻转过来
+//   包含点的判断是半开区间 [min, max), 这样相邻的两个矩形不会同时包含边界上的点,
+//   与像素网格的直觉一致; 只接触边界的两个矩形不相交, intersection 返回 None
+//   整数坐标运算溢出的行为与普通的整数运算相同 (debug 下 panic)
+
+use std::fmt;
+use std::ops::{Add, Div, Mul, Sub};
+
+pub trait Coord:
+    Copy
+    + PartialOrd
+    + fmt::Debug
+    + fmt::Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+}
+
+macro_rules! impl_coord {
+    ($($t:ty => $zero:expr, $one:expr;)*) => {
+        $(impl Coord for $t {
+            const ZERO: Self = $zero;
+            const ONE: Self = $one;
+        })*
+    };
+}
+
+impl_coord! {
+    i32 => 0, 1;
+    i64 => 0, 1;
+    u32 => 0, 1;
+    u64 => 0, 1;
+    f32 => 0.0, 1.0;
+    f64 => 0.0, 1.0;
+}
+
+// PartialOrd 没有 min / max, 这里的 NaN 按 "不小于" 处理
+fn min<T: PartialOrd>(a: T, b: T) -> T {
+    if b < a {
+        b
+    } else {
+        a
+    }
+}
+
+fn max<T: PartialOrd>(a: T, b: T) -> T {
+    if b > a {
+        b
+    } else {
+        a
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point<T> {
+    pub x: T,
+    pub y: T,
+}
+
+impl<T: Coord> Point<T> {
+    pub fn new(x: T, y: T) -> Self {
+        Point { x, y }
+    }
+
+    pub fn translate(self, dx: T, dy: T) -> Self {
+        Point::new(self.x + dx, self.y + dy)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Point<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect<T> {
+    x: T,
+    y: T,
+    width: T,
+    height: T,
+}
+
+// 返回 (起点, 长度), 长度为负时翻转
+fn normalize<T: Coord>(start: T, len: T) -> (T, T) {
+    if len < T::ZERO {
+        (start + len, T::ZERO - len)
+    } else {
+        (start, len)
+    }
+}
+
+impl<T: Coord> Rect<T> {
+    pub fn new(x: T, y: T, width: T, height: T) -> Self {
+        let (x, width) = normalize(x, width);
+        let (y, height) = normalize(y, height);
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // 以两个对角为顶点的矩形, a 和 b 的顺序无关
+    pub fn from_corners(a: Point<T>, b: Point<T>) -> Self {
+        let (x0, x1) = (min(a.x, b.x), max(a.x, b.x));
+        let (y0, y1) = (min(a.y, b.y), max(a.y, b.y));
+        Rect {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        }
+    }
+
+    pub fn square(x: T, y: T, side: T) -> Self {
+        Rect::new(x, y, side, side)
+    }
+
+    pub fn x(&self) -> T {
+        self.x
+    }
+
+    pub fn y(&self) -> T {
+        self.y
+    }
+
+    pub fn width(&self) -> T {
+        self.width
+    }
+
+    pub fn height(&self) -> T {
+        self.height
+    }
+
+    pub fn min(&self) -> Point<T> {
+        Point::new(self.x, self.y)
+    }
+
+    pub fn max(&self) -> Point<T> {
+        Point::new(self.x + self.width, self.y + self.height)
+    }
+
+    // 整数坐标向下取整
+    pub fn center(&self) -> Point<T> {
+        let two = T::ONE + T::ONE;
+        Point::new(self.x + self.width / two, self.y + self.height / two)
+    }
+
+    pub fn area(&self) -> T {
+        self.width * self.height
+    }
+
+    pub fn perimeter(&self) -> T {
+        (self.width + self.height) * (T::ONE + T::ONE)
+    }
+
+    // 面积为 0
+    pub fn is_empty(&self) -> bool {
+        !(self.width > T::ZERO && self.height > T::ZERO)
+    }
+
+    // 只比较尺寸, 不考虑位置: other 能否严格地放进 self 里面
+    pub fn can_hold(&self, other: &Rect<T>) -> bool {
+        self.width > other.width && self.height > other.height
+    }
+
+    pub fn contains_point(&self, p: Point<T>) -> bool {
+        let max = self.max();
+        p.x >= self.x && p.x < max.x && p.y >= self.y && p.y < max.y
+    }
+
+    // other 在位置上完全位于 self 之内 (可以贴着边)
+    pub fn contains_rect(&self, other: &Rect<T>) -> bool {
+        let (max, other_max) = (self.max(), other.max());
+        other.x >= self.x && other.y >= self.y && other_max.x <= max.x && other_max.y <= max.y
+    }
+
+    // 有面积大于 0 的公共部分
+    pub fn intersects(&self, other: &Rect<T>) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    pub fn intersection(&self, other: &Rect<T>) -> Option<Rect<T>> {
+        let (a, b) = (self.max(), other.max());
+        let x0 = max(self.x, other.x);
+        let y0 = max(self.y, other.y);
+        let x1 = min(a.x, b.x);
+        let y1 = min(a.y, b.y);
+        (x0 < x1 && y0 < y1).then(|| Rect {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        })
+    }
+
+    // 同时包含两个矩形的最小矩形
+    pub fn union(&self, other: &Rect<T>) -> Rect<T> {
+        let (a, b) = (self.max(), other.max());
+        Rect::from_corners(
+            Point::new(min(self.x, other.x), min(self.y, other.y)),
+            Point::new(max(a.x, b.x), max(a.y, b.y)),
+        )
+    }
+
+    // 包含所有矩形的最小矩形, 没有矩形时返回 None
+    pub fn bounding_box<'a>(rects: impl IntoIterator<Item = &'a Rect<T>>) -> Option<Rect<T>>
+    where
+        T: 'a,
+    {
+        rects.into_iter().fold(None, |acc, r| match acc {
+            None => Some(*r),
+            Some(acc) => Some(acc.union(r)),
+        })
+    }
+
+    pub fn translate(&self, dx: T, dy: T) -> Rect<T> {
+        Rect::new(self.x + dx, self.y + dy, self.width, self.height)
+    }
+
+    // 以左上角为基准缩放宽高
+    pub fn scale(&self, factor: T) -> Rect<T> {
+        self.scale_xy(factor, factor)
+    }
+
+    pub fn scale_xy(&self, sx: T, sy: T) -> Rect<T> {
+        Rect::new(self.x, self.y, self.width * sx, self.height * sy)
+    }
+
+    // 以中心为基准缩放, 中心位置不变 (整数坐标可能有 1 的误差)
+    pub fn scale_about_center(&self, factor: T) -> Rect<T> {
+        let c = self.center();
+        let two = T::ONE + T::ONE;
+        let (w, h) = (self.width * factor, self.height * factor);
+        Rect::new(c.x - w / two, c.y - h / two, w, h)
+    }
+
+    // 点到矩形的距离的平方, 点在矩形内 (包括边界) 时为 0. 不用开方, 整数坐标也可以用
+    pub fn distance_squared_to(&self, p: Point<T>) -> T {
+        let max = self.max();
+        let gap = |v: T, lo: T, hi: T| {
+            if v < lo {
+                lo - v
+            } else if v > hi {
+                v - hi
+            } else {
+                T::ZERO
+            }
+        };
+        let dx = gap(p.x, self.x, max.x);
+        let dy = gap(p.y, self.y, max.y);
+        dx * dx + dy * dy
+    }
+
+    // 转换坐标类型, 例如 Rect<i32> -> Rect<f64>
+    pub fn cast<U: Coord + From<T>>(&self) -> Rect<U> {
+        Rect::new(
+            U::from(self.x),
+            U::from(self.y),
+            U::from(self.width),
+            U::from(self.height),
+        )
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Rect<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {})",
+            self.width, self.height, self.x, self.y
+        )
+    }
+}
@@ -1,22 +1,38 @@
-#[derive(Debug)]
+mod audit;
+mod auth;
+mod clock;
+mod csv;
+mod email;
+mod geometry;
+mod patch;
+mod rtree;
+mod store;
+mod trace;
+mod username;
+
+use email::Email;
+use serde::{Deserialize, Serialize};
+use username::Username;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct User {
-    username: String,
-    email: String,
+    username: Username,
+    email: Email,
     active: bool,
     sign_in_count: u64,
 }
 
 fn use_struct() {
     let u1 = User {
-        username: String::from("u1"),
-        email: String::from("u1@example.com"),
+        username: Username::parse("u1").unwrap(),
+        email: Email::parse("u1@example.com").unwrap(),
         active: true,
         sign_in_count: 42,
     };
     println!("u1: {:?}", u1);
 
-    let username = String::from("u2");
-    let email = String::from("u2@example.com");
+    let username = Username::parse("u2").unwrap();
+    let email = Email::parse("u2@example.com").unwrap();
     let u2 = User {
         username,
         email,
@@ -26,7 +42,7 @@ fn use_struct() {
     println!("u2: {:?}", u2);
 
     let u3 = User {
-        username: String::from("u3"),
+        username: Username::parse("u3").unwrap(),
         sign_in_count: 44,
         ..u1
     };
@@ -50,10 +66,734 @@ fn use_struct() {
     // let mut u4 = u1;
 
     let mut u5 = u2;
-    u5.username = String::from("u5");
+    u5.username = Username::parse("u5").unwrap();
     println!("u5: {:?}", u5);
 }
 
+fn use_user_store() {
+    use store::{StoreError, UserStore};
+
+    let path = std::env::temp_dir().join("use_struct_users.jsonl");
+    let _ = std::fs::remove_file(&path);
+
+    let mut users = UserStore::open(&path).unwrap();
+    assert!(users.is_empty());
+    let id1 = users.create(u1_like()).unwrap();
+    let id2 = users
+        .create(User {
+            username: Username::parse("u2").unwrap(),
+            email: Email::parse("u2@example.com").unwrap(),
+            active: true,
+            sign_in_count: 0,
+        })
+        .unwrap();
+
+    // username 重复
+    let err = users.create(u1_like()).unwrap_err();
+    assert!(matches!(err, StoreError::DuplicateUsername(ref name) if name == "u1"));
+    // email 不区分大小写
+    let err = users
+        .create(User {
+            username: Username::parse("u3").unwrap(),
+            email: Email::parse("U1@Example.COM").unwrap(),
+            active: true,
+            sign_in_count: 0,
+        })
+        .unwrap_err();
+    println!("{}", err);
+    assert!(matches!(err, StoreError::DuplicateEmail(_)));
+
+    assert_eq!(users.find_by_email("U2@EXAMPLE.com").unwrap().0, id2);
+    assert_eq!(users.find_by_username("u1").unwrap().1.sign_in_count, 42);
+
+    // 与 ..u1 不同, update 不会 move 走原来的字段; 冲突时原记录不变
+    let err = users.update(id2, |u| u.username = Username::parse("u1").unwrap());
+    assert!(err.is_err());
+    assert_eq!(users.get(id2).unwrap().username, "u2");
+    users
+        .update(id2, |u| {
+            u.username = Username::parse("u5").unwrap();
+            u.sign_in_count += 1;
+        })
+        .unwrap();
+    assert!(users.find_by_username("u2").is_none());
+    assert_eq!(users.find_by_username("u5").unwrap().0, id2);
+
+    users.deactivate(id1).unwrap();
+    let removed = users.delete(id2).unwrap();
+    assert_eq!(removed.username, "u5");
+    assert!(matches!(users.delete(id2), Err(StoreError::NotFound(_))));
+    // 被删除的用户的 email 可以重新注册
+    let id3 = users.create(removed).unwrap();
+    assert_ne!(id3, id2);
+
+    // 日志有 6 行, 只有 2 个用户, 4 行已经过时
+    assert_eq!(users.garbage(), 4);
+    drop(users);
+
+    // 模拟写到一半时崩溃: 最后一行不完整
+    let mut file = std::fs::File::options().append(true).open(&path).unwrap();
+    std::io::Write::write_all(&mut file, b"{\"op\":\"delete\",\"id\":").unwrap();
+    drop(file);
+
+    let mut users = UserStore::open(&path).unwrap();
+    assert_eq!(users.len(), 2);
+    assert!(!users.get(id1).unwrap().active);
+    assert_eq!(users.get(id3).unwrap().email, "u2@example.com");
+    // 不完整的行已经被截掉了
+    assert!(std::fs::read_to_string(&path).unwrap().ends_with("}\n"));
+
+    assert!(users.maybe_compact(0).unwrap());
+    assert_eq!(users.garbage(), 0);
+    let content = std::fs::read_to_string(&path).unwrap();
+    println!("{}", content);
+    assert_eq!(content.lines().count(), 2);
+
+    // 压缩后继续追加
+    let id4 = users
+        .create(User {
+            username: Username::parse("u4").unwrap(),
+            email: Email::parse("u4@example.com").unwrap(),
+            active: true,
+            sign_in_count: 0,
+        })
+        .unwrap();
+    drop(users);
+    let users = UserStore::open(&path).unwrap();
+    assert_eq!(users.len(), 3);
+    assert!(id4 > id3);
+    for (id, user) in users.iter() {
+        println!("{} {:?}", id, user);
+    }
+
+    // 中间的行损坏不是崩溃造成的, 报错而不是丢数据
+    std::fs::write(&path, "not json\n{}\n").unwrap();
+    assert!(matches!(
+        UserStore::open(&path),
+        Err(StoreError::Corrupt { line: 1, .. })
+    ));
+}
+
+fn use_validated_fields() {
+    use email::EmailError;
+    use username::UsernameError;
+
+    // String::from("u1@example.com") 和 String::from("garbage") 都是 String, 类型上区分不开;
+    // Email 和 Username 只能通过 parse 得到, 拿到了就一定是合法的
+    let email = Email::parse("  John.Doe+news@Example.COM ").unwrap();
+    assert_eq!(email, "John.Doe+news@example.com");
+    assert_eq!(email.local_part(), "John.Doe+news");
+    assert_eq!(email.domain(), "example.com");
+
+    // 国际化域名保存为 punycode
+    let email: Email = "a@例子.测试".parse().unwrap();
+    assert_eq!(email, "a@xn--fsqu00a.xn--0zwm56d");
+    assert_eq!(email.unicode_domain(), "例子.测试");
+    assert_eq!(Email::parse("a@XN--FSQU00A.xn--0zwm56d").unwrap(), email);
+    assert_eq!(
+        Email::parse("b@Bücher.example").unwrap().domain(),
+        "xn--bcher-kva.example"
+    );
+
+    let cases = [
+        ("", EmailError::Empty),
+        ("u1.example.com", EmailError::MissingAt),
+        ("@example.com", EmailError::EmptyLocalPart),
+        (
+            "a b@example.com",
+            EmailError::InvalidLocalChar { ch: ' ', index: 1 },
+        ),
+        (".u1@example.com", EmailError::MisplacedDot { index: 0 }),
+        ("u1..x@example.com", EmailError::MisplacedDot { index: 3 }),
+        ("u1.@example.com", EmailError::MisplacedDot { index: 2 }),
+        ("\"john doe\"@example.com", EmailError::QuotedLocalPart),
+        (
+            "用户@例子.测试",
+            EmailError::InvalidLocalChar {
+                ch: '用', index: 0
+            },
+        ),
+        ("u1@", EmailError::EmptyDomain),
+        ("u1@[192.0.2.1]", EmailError::DomainLiteral),
+        ("u1@localhost", EmailError::SingleLabelDomain),
+        ("u1@example..com", EmailError::EmptyLabel { label: 1 }),
+        (
+            "u1@192.0.2.1",
+            EmailError::NumericTld {
+                tld: String::from("1"),
+            },
+        ),
+        (
+            "u1@-example.com",
+            EmailError::LabelHyphen {
+                label: String::from("-example"),
+            },
+        ),
+        (
+            "u1@exa_mple.com",
+            EmailError::InvalidLabelChar {
+                label: String::from("exa_mple"),
+                ch: '_',
+            },
+        ),
+        (
+            "u1@xn--a-b.com",
+            EmailError::InvalidPunycode {
+                label: String::from("xn--a-b"),
+            },
+        ),
+    ];
+    for (input, expected) in cases {
+        let err = Email::parse(input).unwrap_err();
+        println!("{:?}: {}", input, err);
+        assert_eq!(err, expected);
+    }
+    let long_local = format!("{}@example.com", "a".repeat(65));
+    assert_eq!(
+        Email::parse(&long_local).unwrap_err(),
+        EmailError::LocalPartTooLong { len: 65 }
+    );
+    let long_label = format!("u1@{}.com", "a".repeat(64));
+    assert!(matches!(
+        Email::parse(&long_label),
+        Err(EmailError::LabelTooLong { len: 64, .. })
+    ));
+    // 很长的非 ASCII label 在 punycode 编码之前就被拒绝, 不会溢出
+    let huge_label = format!("a@{}\u{10FFFF}.com", "a".repeat(5000));
+    assert!(matches!(
+        Email::parse(&huge_label),
+        Err(EmailError::LabelTooLong { len: 5001, .. })
+    ));
+    // 编码后才超过 63 字节的 label
+    let wide_label = format!("u1@{}.com", "中".repeat(60));
+    assert!(matches!(
+        Email::parse(&wide_label),
+        Err(EmailError::LabelTooLong { .. })
+    ));
+    let long_domain = format!("u1@{}info", "abcdefghi.".repeat(25));
+    assert_eq!(
+        Email::parse(&long_domain).unwrap_err(),
+        EmailError::DomainTooLong { len: 253 + 1 }
+    );
+    let long_email = format!("{}@{}com", "a".repeat(64), "abcdefghi.".repeat(19));
+    assert_eq!(
+        Email::parse(&long_email).unwrap_err(),
+        EmailError::TooLong { len: 64 + 1 + 193 }
+    );
+
+    // 用户名规范化成小写
+    let name = Username::parse(" Alice_01 ").unwrap();
+    assert_eq!(name, "alice_01");
+    let cases = [
+        ("a", UsernameError::TooShort { len: 1 }),
+        ("1st", UsernameError::MustStartWithLetter { ch: '1' }),
+        ("_u1", UsernameError::MustStartWithLetter { ch: '_' }),
+        ("u1!", UsernameError::InvalidChar { ch: '!', index: 2 }),
+        ("zoë", UsernameError::InvalidChar { ch: 'ë', index: 2 }),
+        ("u1__x", UsernameError::ConsecutiveSeparators { index: 3 }),
+        ("u1.", UsernameError::TrailingSeparator),
+        ("Admin", UsernameError::Reserved(String::from("admin"))),
+    ];
+    for (input, expected) in cases {
+        let err = Username::parse(input).unwrap_err();
+        println!("{:?}: {}", input, err);
+        assert_eq!(err, expected);
+    }
+    assert_eq!(
+        Username::parse(&"a".repeat(33)).unwrap_err(),
+        UsernameError::TooLong { len: 33 }
+    );
+
+    // 反序列化时同样会校验, 非法的数据进不了 User
+    let user: User = serde_json::from_str(
+        r#"{"username":"U1","email":"u1@EXAMPLE.com","active":true,"sign_in_count":42}"#,
+    )
+    .unwrap();
+    assert_eq!(user, u1_like());
+    let err = serde_json::from_str::<User>(
+        r#"{"username":"root","email":"u1@example.com","active":true,"sign_in_count":42}"#,
+    )
+    .unwrap_err();
+    println!("{}", err);
+    assert!(err.to_string().contains("reserved"));
+}
+
+fn use_sign_in() {
+    use auth::{AuthError, Authenticator};
+    use clock::{Clock, ManualClock};
+    use std::time::{Duration, SystemTime};
+
+    let path = std::env::temp_dir().join("use_struct_sign_in.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let mut users = store::UserStore::open(&path).unwrap();
+    let id = users.create(u1_like()).unwrap();
+
+    let clock = ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    // 演示用最小的 KDF 参数, 默认参数每次哈希要几十毫秒
+    let mut auth = Authenticator::with_clock(clock.clone())
+        .kdf_params(8, 1, 1)
+        .unwrap()
+        .max_failed_attempts(3)
+        .lockout(Duration::from_secs(60))
+        .session_ttl(Duration::from_secs(3600));
+
+    assert!(matches!(
+        auth.set_password(&mut users, id, "short"),
+        Err(AuthError::WeakPassword { len: 5 })
+    ));
+    // 还没有设置密码, 与密码错误的结果相同
+    assert!(matches!(
+        auth.sign_in(&mut users, "u1", "hunter2hunter2"),
+        Err(AuthError::InvalidCredentials)
+    ));
+    auth.set_password(&mut users, id, "hunter2hunter2").unwrap();
+
+    // 成功登录: sign_in_count 从 42 变成 43
+    let token = auth.sign_in(&mut users, "U1", "hunter2hunter2").unwrap();
+    println!("{:?} {}", token, token.as_str().len());
+    assert_eq!(token.as_str().len(), 64);
+    assert_eq!(users.get(id).unwrap().sign_in_count, 43);
+    assert_eq!(users.last_sign_in(id), Some(clock.now()));
+    assert_eq!(auth.validate(&users, token.as_str()).unwrap(), id);
+    assert!(matches!(
+        auth.validate(&users, "forged"),
+        Err(AuthError::InvalidToken)
+    ));
+
+    // 用户名不存在和密码错误是同一个错误
+    assert!(matches!(
+        auth.sign_in(&mut users, "nobody", "hunter2hunter2"),
+        Err(AuthError::InvalidCredentials)
+    ));
+    assert!(matches!(
+        auth.sign_in(&mut users, "u1", "wrong password"),
+        Err(AuthError::InvalidCredentials)
+    ));
+    assert_eq!(auth.failed_attempts(id), 1);
+    auth.sign_in(&mut users, "u1", "wrong password")
+        .unwrap_err();
+    // 第 3 次失败后锁定, 锁定期间密码正确也不行
+    let err = auth
+        .sign_in(&mut users, "u1", "wrong password")
+        .unwrap_err();
+    println!("{}", err);
+    assert!(matches!(err, AuthError::Locked { .. }));
+    clock.advance(Duration::from_secs(30));
+    assert!(matches!(
+        auth.sign_in(&mut users, "u1", "hunter2hunter2"),
+        Err(AuthError::Locked { .. })
+    ));
+    clock.advance(Duration::from_secs(30));
+    let token2 = auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    assert_eq!(users.get(id).unwrap().sign_in_count, 44);
+    assert_ne!(token, token2);
+
+    // 会话过期
+    clock.advance(Duration::from_secs(3540));
+    assert!(matches!(
+        auth.validate(&users, token.as_str()),
+        Err(AuthError::Expired)
+    ));
+    assert_eq!(auth.validate(&users, token2.as_str()).unwrap(), id);
+    assert!(auth.sign_out(token2.as_str()));
+    assert!(matches!(
+        auth.validate(&users, token2.as_str()),
+        Err(AuthError::InvalidToken)
+    ));
+
+    // 让某个用户的所有会话失效; 过期的会话可以批量清理
+    let token3 = auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    assert_eq!(auth.sign_out_all(id), 2);
+    assert!(matches!(
+        auth.validate(&users, token3.as_str()),
+        Err(AuthError::InvalidToken)
+    ));
+    auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(auth.purge_expired(), 1);
+
+    // 停用的账号不能登录, 已经登录的会话也随之失效
+    let token4 = auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    users.deactivate(id).unwrap();
+    assert!(matches!(
+        auth.validate(&users, token4.as_str()),
+        Err(AuthError::Inactive)
+    ));
+    assert!(matches!(
+        auth.sign_in(&mut users, "u1", "hunter2hunter2"),
+        Err(AuthError::Inactive)
+    ));
+    assert_eq!(users.get(id).unwrap().sign_in_count, 48);
+    // 重新激活之后旧的会话也不会恢复
+    users.update(id, |u| u.active = true).unwrap();
+    assert!(matches!(
+        auth.validate(&users, token4.as_str()),
+        Err(AuthError::InvalidToken)
+    ));
+
+    // 重启: 密码哈希和最后登录时间随 store 落盘, 会话和锁定状态不保留
+    let last = users.last_sign_in(id);
+    drop(users);
+    let mut users = store::UserStore::open(&path).unwrap();
+    assert_eq!(users.last_sign_in(id), last);
+    let mut auth = Authenticator::with_clock(clock.clone())
+        .lockout(Duration::MAX)
+        .session_ttl(Duration::MAX);
+    assert!(matches!(
+        auth.validate(&users, token.as_str()),
+        Err(AuthError::InvalidToken)
+    ));
+    clock.advance(Duration::from_secs(60));
+    // 很大的 session_ttl 不会溢出
+    let token = auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    assert_eq!(auth.validate(&users, token.as_str()).unwrap(), id);
+    assert_eq!(users.last_sign_in(id), Some(clock.now()));
+    let mut auth = auth.max_failed_attempts(1);
+    let err = auth
+        .sign_in(&mut users, "u1", "wrong password")
+        .unwrap_err();
+    assert!(matches!(err, AuthError::Locked { until } if until > clock.now()));
+    let _ = std::fs::remove_file(&path);
+}
+
+fn use_user_patch() {
+    use patch::{PatchError, UserPatch};
+
+    let u1 = u1_like();
+    // 与 User { username, ..u1 } 不同, apply 只借用 patch, 在副本上原地修改, u1 仍然可用
+    let patch = UserPatch::new()
+        .username(Username::parse("u3").unwrap())
+        .sign_in_count(44);
+    let mut u3 = u1.clone();
+    patch.apply(&mut u3);
+    println!("u1: {:?}", u1);
+    println!("u3: {:?}", u3);
+    assert_eq!(u3.username, "u3");
+    assert_eq!(u3.email, u1.email);
+
+    // diff 只包含不同的字段
+    let d = UserPatch::diff(&u1, &u3);
+    assert_eq!(d, patch);
+    assert!(UserPatch::diff(&u1, &u1).is_empty());
+    let mut x = u1.clone();
+    d.apply(&mut x);
+    assert_eq!(x, u3);
+
+    // 合成: p.then(q) 等价于先 apply p 再 apply q
+    let p = UserPatch::new().active(false).sign_in_count(0);
+    let q = UserPatch::new()
+        .sign_in_count(7)
+        .email(Email::parse("u1@example.org").unwrap());
+    let (mut a, mut b) = (u1.clone(), u1.clone());
+    p.apply(&mut a);
+    q.apply(&mut a);
+    p.clone().then(q.clone()).apply(&mut b);
+    assert_eq!(a, b);
+    assert_eq!(b.sign_in_count, 7);
+    assert!(!b.active);
+    // 合成满足结合律, 空 patch 是单位元
+    let r = UserPatch::new().active(true);
+    assert_eq!(
+        p.clone().then(q.clone()).then(r.clone()),
+        p.clone().then(q.clone().then(r))
+    );
+    assert_eq!(UserPatch::new().then(p.clone()), p);
+
+    // JSON Merge Patch
+    let patch =
+        UserPatch::from_merge_patch(r#"{"email": "U1@Example.NET", "active": false}"#).unwrap();
+    assert_eq!(patch.email.as_ref().unwrap(), "U1@example.net");
+    assert_eq!(patch.username, None);
+    println!("{}", patch.to_merge_patch());
+    assert_eq!(
+        patch.to_merge_patch(),
+        r#"{"active":false,"email":"U1@example.net"}"#
+    );
+    assert_eq!(
+        UserPatch::from_merge_patch(&patch.to_merge_patch()).unwrap(),
+        patch
+    );
+    assert_eq!(UserPatch::new().to_merge_patch(), "{}");
+
+    let cases = [
+        ("[1, 2]", PatchError::NotAnObject),
+        (
+            r#"{"emial": "a@b.c"}"#,
+            PatchError::UnknownField(String::from("emial")),
+        ),
+        (r#"{"email": null}"#, PatchError::NotNullable("email")),
+        (
+            r#"{"active": "no"}"#,
+            PatchError::InvalidType {
+                field: "active",
+                expected: "a boolean",
+            },
+        ),
+        (
+            r#"{"sign_in_count": -1}"#,
+            PatchError::InvalidType {
+                field: "sign_in_count",
+                expected: "a non-negative integer",
+            },
+        ),
+        (
+            r#"{"username": "root"}"#,
+            PatchError::Username(username::UsernameError::Reserved(String::from("root"))),
+        ),
+    ];
+    for (json, expected) in cases {
+        let err = UserPatch::from_merge_patch(json).unwrap_err();
+        println!("{}: {}", json, err);
+        assert_eq!(err, expected);
+    }
+    assert!(matches!(
+        UserPatch::from_merge_patch("{"),
+        Err(PatchError::Json(_))
+    ));
+
+    // 与 UserStore 一起使用, 唯一性检查照常进行
+    let mut users = store::UserStore::in_memory();
+    let id = users.create(u1).unwrap();
+    let patch = UserPatch::from_merge_patch(r#"{"sign_in_count": 100}"#).unwrap();
+    let updated = users.update(id, |u| patch.apply(u)).unwrap();
+    assert_eq!(updated.sign_in_count, 100);
+}
+
+fn use_audit_log() {
+    use audit::{Actor, AuditLog, EventKind, InactivityPolicy};
+    use auth::Authenticator;
+    use clock::{Clock, ManualClock};
+    use std::time::{Duration, SystemTime};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let clock = ManualClock::new(start);
+    let mut users = store::UserStore::in_memory()
+        .with_clock(clock.clone())
+        .with_audit(AuditLog::new(clock.clone()));
+    let mut auth = Authenticator::with_clock(clock.clone())
+        .kdf_params(8, 1, 1)
+        .unwrap();
+
+    let admin = Actor::Admin(String::from("ops"));
+    let id1 = users.create_as(admin.clone(), u1_like()).unwrap();
+    let id2 = users
+        .create(User {
+            username: Username::parse("u2").unwrap(),
+            email: Email::parse("u2@example.com").unwrap(),
+            active: true,
+            sign_in_count: 0,
+        })
+        .unwrap();
+    auth.set_password(&mut users, id1, "hunter2hunter2")
+        .unwrap();
+
+    clock.advance(DAY);
+    auth.sign_in(&mut users, "u1", "hunter2hunter2").unwrap();
+    clock.advance(DAY);
+    users
+        .update_as(admin.clone(), id1, |u| {
+            u.email = Email::parse("u1@example.org").unwrap();
+            u.active = false;
+        })
+        .unwrap();
+    users
+        .update_as(admin.clone(), id1, |u| u.active = true)
+        .unwrap();
+
+    let audit = users.audit().unwrap();
+    for event in audit.events() {
+        println!("{:?}", event);
+    }
+    let kinds: Vec<&EventKind> = audit.for_user(id1).map(|e| &e.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            &EventKind::Created,
+            &EventKind::SignedIn,
+            &EventKind::EmailChanged {
+                from: String::from("u1@example.com"),
+                to: String::from("u1@example.org"),
+            },
+            &EventKind::Deactivated,
+            &EventKind::Activated,
+        ]
+    );
+    let signed_in = audit.for_user(id1).nth(1).unwrap();
+    assert_eq!(signed_in.actor, Actor::User(id1));
+    assert_eq!(signed_in.at, start + DAY);
+    assert_eq!(audit.for_user(id2).next().unwrap().actor, Actor::System);
+
+    // 按时间范围查询
+    assert!(!audit.is_empty());
+    assert_eq!(audit.len(), audit.events().len());
+    assert_eq!(audit.between(..start + DAY).len(), 2);
+    assert_eq!(audit.between(start + DAY..).len(), 4);
+    assert_eq!(audit.between(start + DAY..=start + DAY).len(), 1);
+    assert_eq!(audit.for_user_between(id1, start + DAY..).count(), 4);
+    assert_eq!(audit.for_user_between(id2, start + DAY..).count(), 0);
+    // 最后登录时间和创建时间保存在 store 里, 与 Authenticator 共用
+    assert_eq!(users.last_sign_in(id1), Some(start + DAY));
+    assert_eq!(users.last_sign_in(id2), None);
+    assert_eq!(users.account(id2).unwrap().created_at, Some(start));
+
+    // 30 天没有登录就停用. 第 30 天: u2 从创建起已经 30 天, u1 从上次登录起只有 29 天
+    let policy = InactivityPolicy::new(30);
+    clock.set(start + 30 * DAY);
+    assert_eq!(policy.run(&mut users).unwrap(), [id2]);
+    assert!(!users.get(id2).unwrap().active);
+    clock.advance(DAY);
+    assert_eq!(policy.run(&mut users).unwrap(), [id1]);
+    // 已经停用的不会再处理
+    assert!(policy.run(&mut users).unwrap().is_empty());
+
+    let audit = users.audit().unwrap();
+    let last = audit.events().last().unwrap();
+    assert_eq!(
+        (&last.actor, last.user, &last.kind, last.at),
+        (&Actor::System, id1, &EventKind::Deactivated, clock.now())
+    );
+    users.delete_as(admin, id2).unwrap();
+    assert_eq!(
+        users.audit().unwrap().for_user(id2).last().unwrap().kind,
+        EventKind::Deleted
+    );
+
+    // 重启之后没有审计记录, 策略照样按 store 里的时间生效
+    let path = std::env::temp_dir().join("use_struct_inactivity.jsonl");
+    let _ = std::fs::remove_file(&path);
+    let mut users = store::UserStore::open(&path)
+        .unwrap()
+        .with_clock(clock.clone());
+    let id = users.create(u1_like()).unwrap();
+    drop(users);
+    clock.advance(30 * DAY);
+    let mut users = store::UserStore::open(&path)
+        .unwrap()
+        .with_clock(clock.clone())
+        .with_audit(AuditLog::new(clock.clone()));
+    assert_eq!(policy.run(&mut users).unwrap(), [id]);
+    assert!(!users.get(id).unwrap().active);
+    // 天数很大也不会溢出
+    users.update(id, |u| u.active = true).unwrap();
+    assert!(InactivityPolicy::new(u64::MAX)
+        .run(&mut users)
+        .unwrap()
+        .is_empty());
+    let _ = std::fs::remove_file(&path);
+}
+
+fn use_csv() {
+    use csv::{CsvError, RowError};
+
+    let u2 = User {
+        username: Username::parse("u2").unwrap(),
+        email: Email::parse("o'brien+csv@example.com").unwrap(),
+        active: false,
+        sign_in_count: 0,
+    };
+    let exported = csv::export([&u1_like(), &u2]);
+    print!("{}", exported);
+    assert_eq!(
+        exported,
+        "username,email,active,sign_in_count\r\nu1,u1@example.com,true,42\r\nu2,o'brien+csv@example.com,false,0\r\n"
+    );
+    // 导出再导入, 得到相同的 User
+    let report = csv::import(&exported).unwrap();
+    assert!(report.is_ok());
+    let users: Vec<User> = report.imported.into_iter().map(|(_, u)| u).collect();
+    assert_eq!(users, [u1_like(), u2]);
+
+    // 引号和转义
+    let mut out = String::new();
+    csv::write_record(&mut out, &["a,b", "say \"hi\"", "two\nlines", "plain"]);
+    assert_eq!(out, "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",plain\r\n");
+    let parsed: Vec<_> = csv::records(&out).collect();
+    assert_eq!(
+        parsed,
+        [Ok((
+            1,
+            vec![
+                String::from("a,b"),
+                String::from("say \"hi\""),
+                String::from("two\nlines"),
+                String::from("plain"),
+            ]
+        ))]
+    );
+
+    // 列的顺序可以不同, 坏的行单独报告, 不影响其它行
+    let input = "\u{feff}Email, Username ,sign_in_count,active,note\n\
+                 u3@example.com,u3,7,yes,ok\n\
+                 u4@example.com,u4,,N,\"multi\nline note\"\n\
+                 \n\
+                 bad-email,u5,1,true,\n\
+                 u6@example.com,u6,-1,true,\n\
+                 u7@example.com,u7,1,maybe,\n\
+                 u8@example.com,u8,1\n\
+                 u9@example.com,u9,1,true,\"unterminated\" note\n\
+                 u10@example.com,admin,1,true,\n\
+                 u1@EXAMPLE.com,u11,0,1,\n";
+    let mut users = store::UserStore::in_memory();
+    users.create(u1_like()).unwrap();
+    let report = csv::import_into(&mut users, input).unwrap();
+    for e in &report.errors {
+        println!("{}", e);
+    }
+    let lines: Vec<usize> = report.imported.iter().map(|(line, _)| *line).collect();
+    assert_eq!(lines, [2, 3]);
+    let u4 = users.find_by_username("u4").unwrap().1;
+    assert_eq!((u4.active, u4.sign_in_count), (false, 0));
+    assert_eq!(users.find_by_username("u3").unwrap().1.sign_in_count, 7);
+
+    let summary: Vec<(usize, Option<&str>)> =
+        report.errors.iter().map(|e| (e.line, e.column)).collect();
+    assert_eq!(
+        summary,
+        [
+            (6, Some("email")),
+            (7, Some("sign_in_count")),
+            (8, Some("active")),
+            (9, None),
+            (10, None),
+            (11, Some("username")),
+            (12, Some("email")),
+        ]
+    );
+    assert_eq!(
+        report.errors[3],
+        RowError {
+            line: 9,
+            column: None,
+            reason: String::from("expected 5 fields, found 3"),
+        }
+    );
+    assert!(report.errors[4].reason.contains("after closing quote"));
+    assert!(report.errors[6].reason.contains("already registered"));
+
+    // 表头有问题时整个导入失败
+    assert_eq!(csv::import("").unwrap_err(), CsvError::Empty);
+    assert_eq!(
+        csv::import("username,email,active\n").unwrap_err(),
+        CsvError::MissingColumn("sign_in_count")
+    );
+    assert_eq!(
+        csv::import("username,email,active,sign_in_count,Email\n").unwrap_err(),
+        CsvError::DuplicateColumn(String::from("email"))
+    );
+    // 多出来的列可以重名, 例如电子表格导出时结尾的空列
+    let report =
+        csv::import("username,email,active,sign_in_count,,\nu3,u3@example.com,true,1,,\n").unwrap();
+    assert_eq!(report.imported.len(), 1);
+    assert!(report.errors.is_empty());
+    // 引号没有闭合, 错误报告在记录开始的那一行
+    let report =
+        csv::import("username,email,active,sign_in_count\nu3,\"u3@example.com,true,1\n").unwrap();
+    assert_eq!(report.errors[0].line, 2);
+    assert_eq!(report.errors[0].reason, "quoted field is not closed");
+}
+
 struct Color(f64, u32, u32);
 struct Point(f64, u32, u32);
 
@@ -104,6 +844,84 @@ fn use_debug_macro() {
     dbg!(&a);
 }
 
+fn traced_factorial(n: u32) -> u32 {
+    if trace!(n <= 1) {
+        trace!(1)
+    } else {
+        trace!(n * traced_factorial(n - 1))
+    }
+}
+
+fn use_trace_macro() {
+    //trace! 与 dbg! 一样获得参数的 ownership, 并返回该 ownership
+    //内层递归调用的记录会按深度缩进:
+    // [src/main.rs:110:8]   n <= 1 = false (bool, ThreadId(1) main, 129ns)
+    // [src/main.rs:110:8]     n <= 1 = false (bool, ThreadId(1) main, 72ns)
+    // [src/main.rs:110:8]       n <= 1 = false (bool, ThreadId(1) main, 49ns)
+    // [src/main.rs:110:8]         n <= 1 = true (bool, ThreadId(1) main, 50ns)
+    // [src/main.rs:111:9]         1 = 1 (u32, ThreadId(1) main, 157ns)
+    // [src/main.rs:113:9]       n * traced_factorial(n - 1) = 2 (u32, ThreadId(1) main, 171.111µs)
+    // [src/main.rs:113:9]     n * traced_factorial(n - 1) = 6 (u32, ThreadId(1) main, 349.848µs)
+    // [src/main.rs:113:9]   n * traced_factorial(n - 1) = 24 (u32, ThreadId(1) main, 531.005µs)
+    // [src/main.rs:129:5] traced_factorial(4) = 24 (u32, ThreadId(1) main, 751.233µs)
+    trace!(traced_factorial(4));
+
+    let a = AlwaysEqual;
+    let a = trace!(a);
+    let (x, y) = trace!(1 + 1, "two");
+    trace!();
+
+    //输出到内存, 方便断言
+    let (sink, records) = trace::Sink::memory();
+    trace::set_sink(sink).unwrap();
+    assert_eq!(traced_factorial(3), 6);
+    let handle = std::thread::Builder::new()
+        .name(String::from("worker"))
+        .spawn(|| trace!(String::from("from worker")))
+        .unwrap();
+    assert_eq!(handle.join().unwrap(), "from worker");
+
+    let records = records.lock().unwrap();
+    // n=3, n=2, n=1 三层, 每层两条记录, 加上 worker 的一条
+    assert_eq!(records.len(), 7);
+    let depths: Vec<usize> = records.iter().map(|r| r.depth).collect();
+    assert_eq!(depths, [0, 1, 2, 2, 1, 0, 0]);
+    assert_eq!(records[5].expr, "n * traced_factorial(n - 1)");
+    assert_eq!(records[5].value, "6");
+    assert_eq!(records[5].type_name, "u32");
+    assert!(records[6].thread.ends_with("worker"));
+    assert_eq!(records[6].value, "\"from worker\"");
+    drop(records);
+
+    //输出到 JSON Lines 文件
+    let path = std::env::temp_dir().join("use_struct_trace.jsonl");
+    let _ = std::fs::remove_file(&path);
+    trace::set_sink(trace::Sink::json_lines(&path).unwrap()).unwrap();
+    trace!(u1_like());
+    trace::flush().unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    println!("{}", content);
+    assert!(content.contains("\"expr\":\"u1_like()\""));
+    assert!(content.contains("\"type\":\"use_struct::User\""));
+
+    // 替换 sink 时旧的 sink 会被 flush
+    trace!(1 + 1);
+    let old = trace::set_sink(trace::Sink::Stderr).unwrap();
+    assert!(matches!(old, Some(trace::Sink::JsonLines(_))));
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("\"expr\":\"1 + 1\""));
+    println!("{:?} {} {}", a, x, y);
+}
+
+fn u1_like() -> User {
+    User {
+        username: Username::parse("u1").unwrap(),
+        email: Email::parse("u1@example.com").unwrap(),
+        active: true,
+        sign_in_count: 42,
+    }
+}
+
 #[derive(Debug)]
 struct Rectangle {
     width: u32,
@@ -159,6 +977,13 @@ impl Rectangle {
     }
 }
 
+impl Rectangle {
+    // Rectangle 没有位置, 放到 (x, y) 处得到 geometry::Rect, 其它几何运算都在 Rect 上
+    fn at(&self, x: u32, y: u32) -> geometry::Rect<u32> {
+        geometry::Rect::new(x, y, self.width, self.height)
+    }
+}
+
 fn use_associated_func() {
     //::语法用于关联函数和模块创建的命名空间
     let mut a = Rectangle::new(2, 3);
@@ -173,6 +998,236 @@ fn use_associated_func() {
     println!("a {:?}", a)
 }
 
+fn use_geometry() {
+    use geometry::{Point, Rect};
+
+    let a = Rectangle::new(30, 50).at(0, 0);
+    let b = Rect::new(10u32, 40, 40, 20);
+    println!("a: {}, b: {}", a, b);
+    assert_eq!((a.area(), a.perimeter()), (1500, 160));
+    assert_eq!(a.max(), Point::new(30, 50));
+    assert_eq!(a.center(), Point::new(15, 25));
+
+    // can_hold 只比较尺寸, contains_rect 还要看位置
+    let small = Rect::new(100u32, 100, 10, 40);
+    assert!(a.can_hold(&small));
+    assert!(!a.contains_rect(&small));
+    assert!(a.contains_rect(&Rect::new(5, 5, 10, 40)));
+    assert!(!a.can_hold(&b));
+
+    // 交集与并集
+    assert_eq!(a.intersection(&b), Some(Rect::new(10, 40, 20, 10)));
+    assert_eq!(a.union(&b), Rect::new(0, 0, 50, 60));
+    // 只接触边界不算相交
+    let right = Rect::new(30u32, 0, 10, 10);
+    assert!(!a.intersects(&right));
+    assert_eq!(a.intersection(&right), None);
+    assert_eq!(
+        Rect::bounding_box([&a, &b, &right]),
+        Some(Rect::new(0, 0, 50, 60))
+    );
+    assert_eq!(Rect::<u32>::bounding_box([]), None);
+
+    // 半开区间: 左上边界包含, 右下边界不包含
+    assert!(a.contains_point(Point::new(0, 0)));
+    assert!(a.contains_point(Point::new(29, 49)));
+    assert!(!a.contains_point(Point::new(30, 49)));
+
+    // 整数和浮点数共用一份实现, 负的宽高会被翻转
+    let r = Rect::new(0, 0, -4, 6);
+    assert_eq!((r.x(), r.width()), (-4, 4));
+    assert_eq!(
+        Rect::from_corners(Point::new(3, 1), Point::new(-1, 4)),
+        Rect::new(-1, 1, 4, 3)
+    );
+    assert_eq!(r.translate(10, -2), Rect::new(6, -2, 4, 6));
+    assert_eq!(r.min().translate(10, -2), Point::new(6, -2));
+    assert_eq!(Rect::square(1, 1, 2).scale(3), Rect::new(1, 1, 6, 6));
+    assert_eq!(r.scale_about_center(2), Rect::new(-6, -3, 8, 12));
+
+    let f = Rect::new(0.5, 0.5, 2.0, 1.0);
+    assert_eq!(f.area(), 2.0);
+    assert_eq!(f.scale_xy(0.5, 3.0), Rect::new(0.5, 0.5, 1.0, 3.0));
+    assert_eq!(f.scale_about_center(2.0), Rect::new(-0.5, 0.0, 4.0, 2.0));
+    assert!(f.contains_point(Point::new(2.4, 1.4)));
+    assert_eq!(r.cast::<f64>().union(&f), Rect::new(-4.0, 0.0, 6.5, 6.0));
+
+    // 点到矩形的距离
+    assert_eq!(a.distance_squared_to(Point::new(10, 10)), 0);
+    assert_eq!(a.distance_squared_to(Point::new(33, 54)), 25);
+    assert!(!Rect::new(0.0, 0.0, 1.0, 1.0).is_empty());
+    assert!(Rect::new(1, 1, 0, 5).is_empty());
+}
+
+// 简单的伪随机数 (xorshift), 演示和 benchmark 不需要引入 rand
+fn random_rects(n: usize, world: i32, max_side: i32, seed: u64) -> Vec<geometry::Rect<i32>> {
+    let mut state = seed;
+    let mut next = move |bound: i32| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound as u64) as i32
+    };
+    (0..n)
+        .map(|_| {
+            let (x, y) = (next(world), next(world));
+            geometry::Rect::new(x, y, 1 + next(max_side), 1 + next(max_side))
+        })
+        .collect()
+}
+
+fn use_rtree() {
+    use geometry::{Point, Rect};
+    use rtree::RTree;
+
+    let mut tree = RTree::new();
+    tree.insert(Rectangle::new(30, 50).at(0, 0).cast::<i64>(), "a");
+    tree.insert(Rect::new(100, 100, 10, 10), "b");
+    tree.insert(Rect::new(-20, 5, 10, 10), "c");
+    assert_eq!(tree.len(), 3);
+
+    let hits = tree.query(&Rect::new(-15, 0, 20, 10));
+    let mut names: Vec<&str> = hits.iter().map(|(_, v)| **v).collect();
+    names.sort();
+    assert_eq!(names, ["a", "c"]);
+    // 只接触边界不算相交
+    assert!(tree.query(&Rect::new(110, 110, 5, 5)).is_empty());
+    assert_eq!(*tree.nearest(Point::new(90, 90)).unwrap().1, "b");
+    assert_eq!(*tree.nearest(Point::new(-100, 0)).unwrap().1, "c");
+    assert_eq!(tree.remove(&Rect::new(100, 100, 10, 10), &"b"), Some("b"));
+    assert_eq!(tree.remove(&Rect::new(100, 100, 10, 10), &"b"), None);
+    assert_eq!(*tree.nearest(Point::new(90, 90)).unwrap().1, "a");
+
+    // 与线性扫描的结果比较
+    let rects = random_rects(5000, 10_000, 200, 42);
+    let mut tree: RTree<i32, usize> = RTree::new();
+    for (i, r) in rects.iter().enumerate() {
+        tree.insert(*r, i);
+    }
+    let bulk: RTree<i32, usize> = rects.iter().copied().zip(0..).collect();
+    println!("depth: insert {}, bulk {}", tree.depth(), bulk.depth());
+    assert_eq!((tree.len(), bulk.len()), (5000, 5000));
+    assert_eq!(tree.iter().count(), 5000);
+
+    let linear_query = |window: &Rect<i32>| {
+        let mut ids: Vec<usize> = (0..rects.len())
+            .filter(|&i| rects[i].intersects(window))
+            .collect();
+        ids.sort();
+        ids
+    };
+    let ids = |hits: Vec<(&Rect<i32>, &usize)>| {
+        let mut ids: Vec<usize> = hits.into_iter().map(|(_, &i)| i).collect();
+        ids.sort();
+        ids
+    };
+    for window in random_rects(100, 10_000, 1000, 7) {
+        let expected = linear_query(&window);
+        assert_eq!(ids(tree.query(&window)), expected);
+        assert_eq!(ids(bulk.query(&window)), expected);
+    }
+    for p in random_rects(100, 12_000, 1, 9).iter().map(|r| r.min()) {
+        let best = rects
+            .iter()
+            .map(|r| r.distance_squared_to(p))
+            .min()
+            .unwrap();
+        assert_eq!(tree.nearest(p).unwrap().0.distance_squared_to(p), best);
+        assert_eq!(bulk.nearest(p).unwrap().0.distance_squared_to(p), best);
+    }
+
+    // 删除一半, 剩下的仍然都能查到
+    for (i, r) in rects.iter().enumerate().step_by(2) {
+        assert_eq!(tree.remove(r, &i), Some(i));
+    }
+    assert_eq!(tree.len(), 2500);
+    let everything = Rect::new(-1000, -1000, 20_000, 20_000);
+    let remaining = ids(tree.query(&everything));
+    assert_eq!(remaining, (1..5000).step_by(2).collect::<Vec<_>>());
+    for (i, r) in rects.iter().enumerate().skip(1).step_by(2) {
+        assert_eq!(tree.remove(r, &i), Some(i));
+    }
+    assert!(tree.is_empty());
+    assert_eq!(tree.depth(), 1);
+    assert!(tree.nearest(Point::new(0, 0)).is_none());
+}
+
+fn bench_rtree() {
+    use geometry::Point;
+    use rtree::RTree;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    const N: usize = 100_000;
+    const QUERIES: usize = 1000;
+    let rects = random_rects(N, 100_000, 500, 42);
+    let windows = random_rects(QUERIES, 100_000, 2000, 7);
+    let points: Vec<Point<i32>> = random_rects(QUERIES, 100_000, 1, 9)
+        .iter()
+        .map(|r| r.min())
+        .collect();
+
+    let start = Instant::now();
+    let mut tree = RTree::new();
+    for (i, r) in rects.iter().enumerate() {
+        tree.insert(*r, i);
+    }
+    println!("insert {} rects:    {:?}", N, start.elapsed());
+    let start = Instant::now();
+    let bulk: RTree<i32, usize> = rects.iter().copied().zip(0..).collect();
+    println!("bulk load {} rects: {:?}", N, start.elapsed());
+
+    let start = Instant::now();
+    let mut linear_hits = 0;
+    for w in &windows {
+        linear_hits += black_box(rects.iter().filter(|r| r.intersects(w)).count());
+    }
+    println!("linear query x{}:   {:?}", QUERIES, start.elapsed());
+    for (name, tree) in [("insert", &tree), ("bulk", &bulk)] {
+        let start = Instant::now();
+        let mut hits = 0;
+        for w in &windows {
+            hits += black_box(tree.query(w).len());
+        }
+        println!("rtree query x{} ({}): {:?}", QUERIES, name, start.elapsed());
+        assert_eq!(hits, linear_hits);
+    }
+
+    let start = Instant::now();
+    for &p in &points {
+        black_box(rects.iter().map(|r| r.distance_squared_to(p)).min());
+    }
+    println!("linear nearest x{}: {:?}", QUERIES, start.elapsed());
+    let start = Instant::now();
+    for &p in &points {
+        black_box(bulk.nearest(p));
+    }
+    println!("rtree nearest x{}:  {:?}", QUERIES, start.elapsed());
+}
+
 fn main() {
-    use_associated_func();
+    // $ cargo run -- demo use_sign_in
+    // $ cargo run --release -- demo bench_rtree
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|s| s.as_str()) {
+        Some("demo") => match args.get(1).map(|s| s.as_str()) {
+            Some("use_associated_func") => use_associated_func(),
+            Some("use_trace_macro") => use_trace_macro(),
+            Some("use_user_store") => use_user_store(),
+            Some("use_validated_fields") => use_validated_fields(),
+            Some("use_sign_in") => use_sign_in(),
+            Some("use_user_patch") => use_user_patch(),
+            Some("use_audit_log") => use_audit_log(),
+            Some("use_csv") => use_csv(),
+            Some("use_geometry") => use_geometry(),
+            Some("use_rtree") => use_rtree(),
+            Some("bench_rtree") => bench_rtree(),
+            Some(other) => {
+                eprintln!("unknown demo: {}", other);
+                std::process::exit(2);
+            }
+            None => use_associated_func(),
+        },
+        _ => use_associated_func(),
+    }
 }
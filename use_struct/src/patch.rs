@@ -0,0 +1,206 @@
+// UserPatch: 对 User 的部分修改
+//
+// use_struct() 里的 User { username, ..u1 } 会把 u1 中没有实现 Copy 的字段 move 走, u1 之后就不能再用了.
+// UserPatch 的每个字段都是 Option, None 表示不修改; apply 通过 &mut User 原地修改, 不需要 move.
+//
+// diff(a, b) 得到把 a 变成 b 的最小 patch, 即 apply(diff(a, b)) 之后 a == b.
+// p.then(q) 把两个 patch 合成一个: 先 apply p 再 apply q, 与 apply p.then(q) 的结果相同,
+// 同一个字段两个 patch 都修改时后者覆盖前者.
+//
+// JSON Merge Patch (RFC 7396): admin API 收到的是
+//   {"email": "new@example.com", "active": false}
+// 这样的 JSON 对象, 出现的字段就是要修改的字段. RFC 中 null 表示删除字段,
+// 但 User 的字段都是必需的, 所以 null 会被拒绝; 未知的字段也会被拒绝, 避免拼写错误被悄悄忽略.
+// 不是对象的 patch 在 RFC 中表示整个替换, 这里也拒绝, 整个替换应该走 create.
+
+use crate::email::{Email, EmailError};
+use crate::username::{Username, UsernameError};
+use crate::User;
+use serde_json::{Map, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserPatch {
+    pub username: Option<Username>,
+    pub email: Option<Email>,
+    pub active: Option<bool>,
+    pub sign_in_count: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    Json(String),
+    NotAnObject,
+    UnknownField(String),
+    NotNullable(&'static str),
+    InvalidType {
+        field: &'static str,
+        expected: &'static str,
+    },
+    Username(UsernameError),
+    Email(EmailError),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Json(e) => write!(f, "invalid JSON: {}", e),
+            PatchError::NotAnObject => write!(f, "merge patch must be a JSON object"),
+            PatchError::UnknownField(name) => write!(f, "unknown field {:?}", name),
+            PatchError::NotNullable(field) => write!(f, "field {:?} cannot be removed", field),
+            PatchError::InvalidType { field, expected } => {
+                write!(f, "field {:?} must be {}", field, expected)
+            }
+            PatchError::Username(e) => write!(f, "field \"username\": {}", e),
+            PatchError::Email(e) => write!(f, "field \"email\": {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PatchError::Username(e) => Some(e),
+            PatchError::Email(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+fn field_str<'a>(field: &'static str, value: &'a Value) -> Result<&'a str, PatchError> {
+    value.as_str().ok_or(PatchError::InvalidType {
+        field,
+        expected: "a string",
+    })
+}
+
+impl UserPatch {
+    pub fn new() -> Self {
+        UserPatch::default()
+    }
+
+    pub fn username(mut self, username: Username) -> Self {
+        self.username = Some(username);
+        self
+    }
+
+    pub fn email(mut self, email: Email) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    pub fn active(mut self, active: bool) -> Self {
+        self.active = Some(active);
+        self
+    }
+
+    pub fn sign_in_count(mut self, count: u64) -> Self {
+        self.sign_in_count = Some(count);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == UserPatch::default()
+    }
+
+    // 只 clone 需要修改的字段
+    pub fn apply(&self, user: &mut User) {
+        if let Some(username) = &self.username {
+            user.username = username.clone();
+        }
+        if let Some(email) = &self.email {
+            user.email = email.clone();
+        }
+        if let Some(active) = self.active {
+            user.active = active;
+        }
+        if let Some(count) = self.sign_in_count {
+            user.sign_in_count = count;
+        }
+    }
+
+    // 把 from 变成 to 的 patch, 相同的字段为 None
+    pub fn diff(from: &User, to: &User) -> Self {
+        fn changed<T: PartialEq + Clone>(a: &T, b: &T) -> Option<T> {
+            (a != b).then(|| b.clone())
+        }
+        UserPatch {
+            username: changed(&from.username, &to.username),
+            email: changed(&from.email, &to.email),
+            active: changed(&from.active, &to.active),
+            sign_in_count: changed(&from.sign_in_count, &to.sign_in_count),
+        }
+    }
+
+    // 先 self 后 next
+    pub fn then(self, next: UserPatch) -> Self {
+        UserPatch {
+            username: next.username.or(self.username),
+            email: next.email.or(self.email),
+            active: next.active.or(self.active),
+            sign_in_count: next.sign_in_count.or(self.sign_in_count),
+        }
+    }
+
+    pub fn from_merge_patch(json: &str) -> Result<Self, PatchError> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| PatchError::Json(e.to_string()))?;
+        let Value::Object(map) = value else {
+            return Err(PatchError::NotAnObject);
+        };
+        let mut patch = UserPatch::new();
+        for (key, value) in &map {
+            let field = match key.as_str() {
+                "username" => "username",
+                "email" => "email",
+                "active" => "active",
+                "sign_in_count" => "sign_in_count",
+                _ => return Err(PatchError::UnknownField(key.clone())),
+            };
+            if value.is_null() {
+                return Err(PatchError::NotNullable(field));
+            }
+            match field {
+                "username" => {
+                    let s = field_str(field, value)?;
+                    patch.username = Some(Username::parse(s).map_err(PatchError::Username)?);
+                }
+                "email" => {
+                    let s = field_str(field, value)?;
+                    patch.email = Some(Email::parse(s).map_err(PatchError::Email)?);
+                }
+                "active" => {
+                    patch.active = Some(value.as_bool().ok_or(PatchError::InvalidType {
+                        field,
+                        expected: "a boolean",
+                    })?);
+                }
+                _ => {
+                    patch.sign_in_count = Some(value.as_u64().ok_or(PatchError::InvalidType {
+                        field,
+                        expected: "a non-negative integer",
+                    })?);
+                }
+            }
+        }
+        Ok(patch)
+    }
+
+    // 只包含要修改的字段; 空 patch 是 {}
+    pub fn to_merge_patch(&self) -> String {
+        let mut map = Map::new();
+        if let Some(username) = &self.username {
+            map.insert("username".into(), Value::from(username.as_str()));
+        }
+        if let Some(email) = &self.email {
+            map.insert("email".into(), Value::from(email.as_str()));
+        }
+        if let Some(active) = self.active {
+            map.insert("active".into(), Value::from(active));
+        }
+        if let Some(count) = self.sign_in_count {
+            map.insert("sign_in_count".into(), Value::from(count));
+        }
+        Value::Object(map).to_string()
+    }
+}
@@ -0,0 +1,381 @@
+// RTree: 矩形的空间索引
+//
+// 每个节点保存子节点 (或叶子中的条目) 的包围盒 (bounding box). 查询时包围盒与查询区域不相交的子树整个跳过,
+// 所以查询只访问 O(log n + 结果数) 个节点, 而线性扫描要检查全部 n 个矩形.
+//
+// 插入: 从根往下, 每层选择加入新矩形后周长增加最少的子节点; 节点超过 MAX_ENTRIES 时,
+//       沿包围盒较长的一边按中心排序, 从中间分成两半.
+// 删除: 找到条目所在的叶子删除; 节点少于 MIN_ENTRIES 时把它剩下的条目取出来重新插入,
+//       避免树里留下很多几乎空的节点.
+// 最近邻: 按 "点到包围盒的距离" 从近到远访问子树, 子树的距离已经超过当前最优值时剪枝.
+// 批量加载: STR (Sort-Tile-Recursive), 先按 x 排序切成竖条, 每条再按 y 排序切成叶子,
+//           逐层向上. 比逐个插入快, 节点也更满, 查询更快.
+//
+// 查询区域与矩形的相交判断与 Rect::intersects 一致: 需要有面积大于 0 的公共部分.
+
+use crate::geometry::{Coord, Point, Rect};
+use std::cmp::Ordering;
+
+pub const MAX_ENTRIES: usize = 16;
+pub const MIN_ENTRIES: usize = 4;
+
+enum Node<T, V> {
+    Leaf(Vec<(Rect<T>, V)>),
+    Inner(Vec<(Rect<T>, Node<T, V>)>),
+}
+
+pub struct RTree<T, V> {
+    root: Node<T, V>,
+    len: usize,
+}
+
+fn cmp<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+fn center_x<T: Coord>(r: &Rect<T>) -> T {
+    r.center().x
+}
+
+fn center_y<T: Coord>(r: &Rect<T>) -> T {
+    r.center().y
+}
+
+// 闭区间的重叠判断, 用于剪枝: 包围盒只是接触查询区域时, 里面的矩形也不可能与查询区域相交,
+// 但是包围盒本身可能是面积为 0 的, 所以不能用 Rect::intersects
+fn overlaps<T: Coord>(a: &Rect<T>, b: &Rect<T>) -> bool {
+    let (a_max, b_max) = (a.max(), b.max());
+    a.x() <= b_max.x && b.x() <= a_max.x && a.y() <= b_max.y && b.y() <= a_max.y
+}
+
+fn bbox_of<'a, T: Coord + 'a>(rects: impl IntoIterator<Item = &'a Rect<T>>) -> Rect<T> {
+    Rect::bounding_box(rects).unwrap_or(Rect::new(T::ZERO, T::ZERO, T::ZERO, T::ZERO))
+}
+
+impl<T: Coord, V> Node<T, V> {
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(entries) => entries.len(),
+            Node::Inner(children) => children.len(),
+        }
+    }
+
+    fn bbox(&self) -> Rect<T> {
+        match self {
+            Node::Leaf(entries) => bbox_of(entries.iter().map(|(r, _)| r)),
+            Node::Inner(children) => bbox_of(children.iter().map(|(r, _)| r)),
+        }
+    }
+
+    // 插入后如果分裂了, 返回新的兄弟节点
+    fn insert(&mut self, rect: Rect<T>, value: V) -> Option<(Rect<T>, Node<T, V>)> {
+        match self {
+            Node::Leaf(entries) => {
+                entries.push((rect, value));
+                (entries.len() > MAX_ENTRIES).then(|| {
+                    let other = split(entries, |(r, _)| *r);
+                    let node = Node::Leaf(other);
+                    (node.bbox(), node)
+                })
+            }
+            Node::Inner(children) => {
+                let i = choose_subtree(children, &rect);
+                let (bbox, child) = &mut children[i];
+                *bbox = bbox.union(&rect);
+                if let Some((sibling_bbox, sibling)) = child.insert(rect, value) {
+                    *bbox = child.bbox();
+                    children.push((sibling_bbox, sibling));
+                }
+                (children.len() > MAX_ENTRIES).then(|| {
+                    let other = split(children, |(r, _)| *r);
+                    let node = Node::Inner(other);
+                    (node.bbox(), node)
+                })
+            }
+        }
+    }
+
+    // 删除第一个满足 rect 相等且 pred 为真的条目. 删除后不足 MIN_ENTRIES 的子节点被拆掉,
+    // 其中的条目放进 orphans 等待重新插入
+    fn remove(
+        &mut self,
+        rect: &Rect<T>,
+        pred: &mut impl FnMut(&V) -> bool,
+        orphans: &mut Vec<(Rect<T>, V)>,
+    ) -> Option<V> {
+        match self {
+            Node::Leaf(entries) => {
+                let i = entries.iter().position(|(r, v)| r == rect && pred(v))?;
+                Some(entries.swap_remove(i).1)
+            }
+            Node::Inner(children) => {
+                for i in 0..children.len() {
+                    if !children[i].0.contains_rect(rect) {
+                        continue;
+                    }
+                    let Some(value) = children[i].1.remove(rect, pred, orphans) else {
+                        continue;
+                    };
+                    if children[i].1.len() < MIN_ENTRIES {
+                        let (_, child) = children.swap_remove(i);
+                        child.drain_into(orphans);
+                    } else {
+                        children[i].0 = children[i].1.bbox();
+                    }
+                    return Some(value);
+                }
+                None
+            }
+        }
+    }
+
+    fn drain_into(self, out: &mut Vec<(Rect<T>, V)>) {
+        match self {
+            Node::Leaf(entries) => out.extend(entries),
+            Node::Inner(children) => {
+                for (_, child) in children {
+                    child.drain_into(out);
+                }
+            }
+        }
+    }
+
+    fn query<'a>(&'a self, window: &Rect<T>, out: &mut Vec<(&'a Rect<T>, &'a V)>) {
+        match self {
+            Node::Leaf(entries) => out.extend(
+                entries
+                    .iter()
+                    .filter(|(r, _)| r.intersects(window))
+                    .map(|(r, v)| (r, v)),
+            ),
+            Node::Inner(children) => {
+                for (bbox, child) in children {
+                    if overlaps(bbox, window) {
+                        child.query(window, out);
+                    }
+                }
+            }
+        }
+    }
+
+    // best: 当前找到的最近条目和距离的平方
+    fn nearest<'a>(&'a self, p: Point<T>, best: &mut Option<(T, &'a Rect<T>, &'a V)>) {
+        match self {
+            Node::Leaf(entries) => {
+                for (r, v) in entries {
+                    let d = r.distance_squared_to(p);
+                    if best.as_ref().is_none_or(|(b, _, _)| d < *b) {
+                        *best = Some((d, r, v));
+                    }
+                }
+            }
+            Node::Inner(children) => {
+                let mut order: Vec<(T, &Node<T, V>)> = children
+                    .iter()
+                    .map(|(bbox, child)| (bbox.distance_squared_to(p), child))
+                    .collect();
+                order.sort_by(|a, b| cmp(&a.0, &b.0));
+                for (d, child) in order {
+                    if best.as_ref().is_some_and(|(b, _, _)| d >= *b) {
+                        break;
+                    }
+                    child.nearest(p, best);
+                }
+            }
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Inner(children) => 1 + children[0].1.depth(),
+        }
+    }
+}
+
+// 加入 rect 后周长增加最少的子节点, 相同时选周长较小的.
+// 不用面积: 整数坐标下包围盒的面积很容易溢出 (i32 的 100000 x 100000 就超了), 周长只是坐标的几倍
+fn choose_subtree<T: Coord, N>(children: &[(Rect<T>, N)], rect: &Rect<T>) -> usize {
+    let cost = |bbox: &Rect<T>| {
+        let perimeter = bbox.perimeter();
+        (bbox.union(rect).perimeter() - perimeter, perimeter)
+    };
+    let mut best = 0;
+    let mut best_cost = cost(&children[0].0);
+    for (i, (bbox, _)) in children.iter().enumerate().skip(1) {
+        let c = cost(bbox);
+        if cmp(&c.0, &best_cost.0).then(cmp(&c.1, &best_cost.1)) == Ordering::Less {
+            best = i;
+            best_cost = c;
+        }
+    }
+    best
+}
+
+// 沿包围盒较长的一边按中心排序, 后一半移出来返回
+fn split<T: Coord, E>(items: &mut Vec<E>, rect_of: impl Fn(&E) -> Rect<T>) -> Vec<E> {
+    let bbox = items
+        .iter()
+        .map(&rect_of)
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or(Rect::new(T::ZERO, T::ZERO, T::ZERO, T::ZERO));
+    if bbox.width() >= bbox.height() {
+        items.sort_by(|a, b| cmp(&center_x(&rect_of(a)), &center_x(&rect_of(b))));
+    } else {
+        items.sort_by(|a, b| cmp(&center_y(&rect_of(a)), &center_y(&rect_of(b))));
+    }
+    items.split_off(items.len() / 2)
+}
+
+// STR 的一层: 把 items 分组, 每组最多 MAX_ENTRIES 个, 空间上相邻的分在一组
+fn str_groups<T: Coord, E>(mut items: Vec<E>, rect_of: impl Fn(&E) -> Rect<T>) -> Vec<Vec<E>> {
+    let groups = items.len().div_ceil(MAX_ENTRIES);
+    let slices = (groups as f64).sqrt().ceil() as usize;
+    let per_slice = slices * MAX_ENTRIES;
+    items.sort_by(|a, b| cmp(&center_x(&rect_of(a)), &center_x(&rect_of(b))));
+    // 从后往前切, split_off 只移动切下来的部分
+    let mut out = Vec::with_capacity(groups);
+    while !items.is_empty() {
+        let start = (items.len() - 1) / per_slice * per_slice;
+        let mut slice = items.split_off(start);
+        slice.sort_by(|a, b| cmp(&center_y(&rect_of(a)), &center_y(&rect_of(b))));
+        while !slice.is_empty() {
+            let start = (slice.len() - 1) / MAX_ENTRIES * MAX_ENTRIES;
+            out.push(slice.split_off(start));
+        }
+    }
+    out
+}
+
+impl<T: Coord, V> RTree<T, V> {
+    pub fn new() -> Self {
+        RTree {
+            root: Node::Leaf(Vec::new()),
+            len: 0,
+        }
+    }
+
+    pub fn bulk_load(entries: Vec<(Rect<T>, V)>) -> Self {
+        let len = entries.len();
+        if len <= MAX_ENTRIES {
+            return RTree {
+                root: Node::Leaf(entries),
+                len,
+            };
+        }
+        let mut level: Vec<(Rect<T>, Node<T, V>)> = str_groups(entries, |(r, _)| *r)
+            .into_iter()
+            .map(|group| {
+                let node = Node::Leaf(group);
+                (node.bbox(), node)
+            })
+            .collect();
+        while level.len() > 1 {
+            level = str_groups(level, |(r, _)| *r)
+                .into_iter()
+                .map(|group| {
+                    let node = Node::Inner(group);
+                    (node.bbox(), node)
+                })
+                .collect();
+        }
+        RTree {
+            root: level.pop().unwrap().1,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // 根到叶子的层数
+    pub fn depth(&self) -> usize {
+        self.root.depth()
+    }
+
+    pub fn insert(&mut self, rect: Rect<T>, value: V) {
+        self.len += 1;
+        if let Some(sibling) = self.root.insert(rect, value) {
+            let old = std::mem::replace(&mut self.root, Node::Leaf(Vec::new()));
+            self.root = Node::Inner(vec![(old.bbox(), old), sibling]);
+        }
+    }
+
+    // 删除一个矩形为 rect 且 pred(value) 为真的条目
+    pub fn remove_with(&mut self, rect: &Rect<T>, mut pred: impl FnMut(&V) -> bool) -> Option<V> {
+        let mut orphans = Vec::new();
+        let value = self.root.remove(rect, &mut pred, &mut orphans)?;
+        self.len -= 1;
+        // 根只剩一个子节点时降低一层
+        loop {
+            match &mut self.root {
+                Node::Inner(children) if children.len() == 1 => {
+                    self.root = children.pop().unwrap().1;
+                }
+                Node::Inner(children) if children.is_empty() => {
+                    self.root = Node::Leaf(Vec::new());
+                }
+                _ => break,
+            }
+        }
+        self.len -= orphans.len();
+        for (r, v) in orphans {
+            self.insert(r, v);
+        }
+        Some(value)
+    }
+
+    pub fn remove(&mut self, rect: &Rect<T>, value: &V) -> Option<V>
+    where
+        V: PartialEq,
+    {
+        self.remove_with(rect, |v| v == value)
+    }
+
+    // 与 window 相交的所有条目
+    pub fn query(&self, window: &Rect<T>) -> Vec<(&Rect<T>, &V)> {
+        let mut out = Vec::new();
+        if overlaps(&self.root.bbox(), window) {
+            self.root.query(window, &mut out);
+        }
+        out
+    }
+
+    // 离 p 最近的条目, 距离相同时返回其中任意一个. p 在矩形内时距离为 0
+    pub fn nearest(&self, p: Point<T>) -> Option<(&Rect<T>, &V)> {
+        let mut best = None;
+        self.root.nearest(p, &mut best);
+        best.map(|(_, r, v)| (r, v))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Rect<T>, &V)> {
+        let mut stack = vec![&self.root];
+        let mut leaf: std::slice::Iter<(Rect<T>, V)> = [].iter();
+        std::iter::from_fn(move || loop {
+            if let Some((r, v)) = leaf.next() {
+                return Some((r, v));
+            }
+            match stack.pop()? {
+                Node::Leaf(entries) => leaf = entries.iter(),
+                Node::Inner(children) => stack.extend(children.iter().map(|(_, c)| c)),
+            }
+        })
+    }
+}
+
+impl<T: Coord, V> Default for RTree<T, V> {
+    fn default() -> Self {
+        RTree::new()
+    }
+}
+
+impl<T: Coord, V> FromIterator<(Rect<T>, V)> for RTree<T, V> {
+    fn from_iter<I: IntoIterator<Item = (Rect<T>, V)>>(iter: I) -> Self {
+        RTree::bulk_load(iter.into_iter().collect())
+    }
+}
@@ -0,0 +1,489 @@
+// UserStore: 管理 User 的增删改查
+//
+// 约束:
+//   username 唯一, Username 已经规范化成小写了
+//   email 唯一, 比较时不区分大小写 ("A@Example.com" 与 "a@example.com" 冲突)
+// 除了按 id 查找, 还维护了 username 和 email 两个索引, 查找都是 O(1).
+//
+// 持久化: JSON Lines 格式的追加日志, 每次修改追加一行:
+//   {"op":"put","id":1,"user":{"username":"u1",...},"account":{"password_hash":"$argon2id$..."}}
+//   {"op":"delete","id":1}
+// 打开时按顺序重放日志即可得到当前状态.
+// Account 是与 User 一起保存, 但不属于 User 的数据 (密码哈希, 创建时间, 最后登录时间),
+// 不会导出到 CSV, 也不能通过 UserPatch 修改. 没有 account 的旧日志行按空的 Account 处理.
+//
+// 崩溃安全: 每行写完后 sync_data 才更新内存, 所以内存里的状态一定已经落盘.
+// 如果写到一半崩溃, 最后一行是不完整的 (没有换行符), 下次打开时会被丢弃并截断;
+// 中间的行损坏则不是崩溃造成的, 报告 Corrupt 错误而不是悄悄丢数据.
+//
+// 压缩: 日志里被覆盖或删除的旧记录越来越多, compact() 把当前状态写到临时文件,
+// sync 后 rename 覆盖原文件. rename 是原子的, 任何时刻崩溃, 磁盘上要么是旧日志要么是新日志.
+
+use crate::audit::{Actor, AuditLog};
+use crate::clock::{Clock, SystemClock};
+use crate::email::Email;
+use crate::username::Username;
+use crate::User;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct UserId(pub u64);
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    DuplicateUsername(String),
+    DuplicateEmail(String),
+    NotFound(UserId),
+    Io(io::Error),
+    // 日志第 line 行 (从 1 开始) 无法解析
+    Corrupt { line: usize, message: String },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::DuplicateUsername(name) => {
+                write!(f, "username {:?} is already taken", name)
+            }
+            StoreError::DuplicateEmail(email) => {
+                write!(f, "email {:?} is already registered", email)
+            }
+            StoreError::NotFound(id) => write!(f, "user {} not found", id),
+            StoreError::Io(e) => write!(f, "io error: {}", e),
+            StoreError::Corrupt { line, message } => {
+                write!(f, "corrupt log at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    // Authenticator 生成的 PHC 字符串, None 表示还没有设置密码
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    // 旧日志里的用户没有创建时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_sign_in: Option<SystemTime>,
+}
+
+impl Account {
+    // 最后一次活动: 最后一次登录, 从未登录过的取创建时间
+    pub fn last_active(&self) -> Option<SystemTime> {
+        self.last_sign_in.or(self.created_at)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Entry {
+    Put {
+        id: UserId,
+        user: User,
+        #[serde(default)]
+        account: Account,
+    },
+    Delete {
+        id: UserId,
+    },
+}
+
+struct Log {
+    path: PathBuf,
+    file: File,
+    // 日志中的行数, 与 users.len() 的差就是可以被压缩掉的行数
+    lines: usize,
+}
+
+impl Log {
+    fn append(&mut self, entry: &Entry) -> Result<(), StoreError> {
+        let mut line = serde_json::to_vec(entry).expect("entry is always serializable");
+        line.push(b'\n');
+        // 一次 write_all, 避免 BufWriter 把一行拆成多次写
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        self.lines += 1;
+        Ok(())
+    }
+}
+
+pub struct UserStore {
+    users: BTreeMap<UserId, User>,
+    accounts: HashMap<UserId, Account>,
+    by_username: HashMap<Username, UserId>,
+    // key 是小写的 email
+    by_email: HashMap<String, UserId>,
+    next_id: u64,
+    // None 表示只在内存中
+    log: Option<Log>,
+    // None 表示不记录审计
+    audit: Option<AuditLog>,
+    // 用于 Account::created_at
+    clock: Box<dyn Clock>,
+}
+
+// Email 只规范化了 domain, local-part 保持原样, 这里再统一转成小写
+fn email_key(email: &Email) -> String {
+    email.as_str().to_lowercase()
+}
+
+impl UserStore {
+    pub fn in_memory() -> Self {
+        UserStore {
+            users: BTreeMap::new(),
+            accounts: HashMap::new(),
+            by_username: HashMap::new(),
+            by_email: HashMap::new(),
+            next_id: 1,
+            log: None,
+            audit: None,
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
+    // 之后的每次修改都记录到 audit 中, 见 audit.rs
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn audit(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    // 打开 (或创建) 日志文件并重放
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut store = UserStore::in_memory();
+        let mut lines = 0;
+        // 最后一个完整行之后的位置, 之后的内容是崩溃时写了一半的行
+        let mut valid_len = 0;
+        if path.exists() {
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut buf = String::new();
+            loop {
+                buf.clear();
+                if reader.read_line(&mut buf)? == 0 {
+                    break;
+                }
+                if !buf.ends_with('\n') {
+                    break;
+                }
+                let entry: Entry = serde_json::from_str(&buf).map_err(|e| StoreError::Corrupt {
+                    line: lines + 1,
+                    message: e.to_string(),
+                })?;
+                store.replay(entry);
+                lines += 1;
+                valid_len += buf.len() as u64;
+            }
+        }
+        let file = File::options().create(true).append(true).open(&path)?;
+        if file.metadata()?.len() > valid_len {
+            file.set_len(valid_len)?;
+            file.sync_data()?;
+        }
+        store.log = Some(Log { path, file, lines });
+        Ok(store)
+    }
+
+    fn replay(&mut self, entry: Entry) {
+        match entry {
+            Entry::Put { id, user, account } => {
+                self.unindex(id);
+                self.index(id, user, account);
+                self.next_id = self.next_id.max(id.0 + 1);
+            }
+            Entry::Delete { id } => {
+                self.unindex(id);
+            }
+        }
+    }
+
+    fn index(&mut self, id: UserId, user: User, account: Account) {
+        self.by_username.insert(user.username.clone(), id);
+        self.by_email.insert(email_key(&user.email), id);
+        self.users.insert(id, user);
+        self.accounts.insert(id, account);
+    }
+
+    fn unindex(&mut self, id: UserId) -> Option<(User, Account)> {
+        let user = self.users.remove(&id)?;
+        let account = self.accounts.remove(&id).unwrap_or_default();
+        self.by_username.remove(&user.username);
+        self.by_email.remove(&email_key(&user.email));
+        Some((user, account))
+    }
+
+    // 检查 user 的 username 和 email 是否与 id 以外的用户冲突
+    fn check_unique(&self, id: Option<UserId>, user: &User) -> Result<(), StoreError> {
+        if let Some(&other) = self.by_username.get(&user.username) {
+            if Some(other) != id {
+                return Err(StoreError::DuplicateUsername(user.username.to_string()));
+            }
+        }
+        if let Some(&other) = self.by_email.get(&email_key(&user.email)) {
+            if Some(other) != id {
+                return Err(StoreError::DuplicateEmail(user.email.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // 先写日志再改内存, 写日志失败时内存保持不变
+    fn write(&mut self, entry: &Entry) -> Result<(), StoreError> {
+        match self.log.as_mut() {
+            Some(log) => log.append(entry),
+            None => Ok(()),
+        }
+    }
+
+    // 不带 _as 的修改方法的操作者都是 Actor::System
+    pub fn create(&mut self, user: User) -> Result<UserId, StoreError> {
+        self.create_as(Actor::System, user)
+    }
+
+    pub fn create_as(&mut self, actor: Actor, user: User) -> Result<UserId, StoreError> {
+        self.check_unique(None, &user)?;
+        let id = UserId(self.next_id);
+        let entry = Entry::Put {
+            id,
+            user,
+            account: Account {
+                created_at: Some(self.clock.now()),
+                ..Account::default()
+            },
+        };
+        self.write(&entry)?;
+        let Entry::Put { user, account, .. } = entry else {
+            unreachable!()
+        };
+        self.next_id += 1;
+        self.index(id, user, account);
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record_created(&actor, id);
+        }
+        Ok(id)
+    }
+
+    pub fn get(&self, id: UserId) -> Option<&User> {
+        self.users.get(&id)
+    }
+
+    pub fn account(&self, id: UserId) -> Option<&Account> {
+        self.accounts.get(&id)
+    }
+
+    pub fn last_sign_in(&self, id: UserId) -> Option<SystemTime> {
+        self.accounts.get(&id)?.last_sign_in
+    }
+
+    // 由 Authenticator::set_password 调用, 与 User 的修改一样先写日志
+    pub fn set_password_hash(&mut self, id: UserId, hash: String) -> Result<(), StoreError> {
+        let user = self.users.get(&id).ok_or(StoreError::NotFound(id))?.clone();
+        let mut account = self.accounts.get(&id).cloned().unwrap_or_default();
+        account.password_hash = Some(hash);
+        self.write(&Entry::Put {
+            id,
+            user,
+            account: account.clone(),
+        })?;
+        self.accounts.insert(id, account);
+        Ok(())
+    }
+
+    // 参数会先按 Username / Email 的规则规范化, 不合法的输入直接返回 None
+    pub fn find_by_username(&self, username: &str) -> Option<(UserId, &User)> {
+        let &id = self.by_username.get(&Username::parse(username).ok()?)?;
+        Some((id, &self.users[&id]))
+    }
+
+    pub fn find_by_email(&self, email: &str) -> Option<(UserId, &User)> {
+        let &id = self.by_email.get(&email_key(&Email::parse(email).ok()?))?;
+        Some((id, &self.users[&id]))
+    }
+
+    // 在副本上执行 f, 通过唯一性检查并写入日志后才替换原来的记录.
+    // f 返回后如果检查失败, 原来的记录不受影响.
+    pub fn update(&mut self, id: UserId, f: impl FnOnce(&mut User)) -> Result<&User, StoreError> {
+        self.update_inner(Actor::System, id, f, None)
+    }
+
+    pub fn update_as(
+        &mut self,
+        actor: Actor,
+        id: UserId,
+        f: impl FnOnce(&mut User),
+    ) -> Result<&User, StoreError> {
+        self.update_inner(actor, id, f, None)
+    }
+
+    // 用户在 at 时刻登录成功: sign_in_count + 1, 记录最后登录时间, 审计中记录为 SignedIn
+    pub fn record_sign_in(&mut self, id: UserId, at: SystemTime) -> Result<&User, StoreError> {
+        self.update_inner(Actor::User(id), id, |u| u.sign_in_count += 1, Some(at))
+    }
+
+    // sign_in 不为 None 时是一次登录
+    fn update_inner(
+        &mut self,
+        actor: Actor,
+        id: UserId,
+        f: impl FnOnce(&mut User),
+        sign_in: Option<SystemTime>,
+    ) -> Result<&User, StoreError> {
+        let mut user = self.users.get(&id).ok_or(StoreError::NotFound(id))?.clone();
+        f(&mut user);
+        self.check_unique(Some(id), &user)?;
+        let mut account = self.accounts.get(&id).cloned().unwrap_or_default();
+        if sign_in.is_some() {
+            account.last_sign_in = sign_in;
+        }
+        let entry = Entry::Put { id, user, account };
+        self.write(&entry)?;
+        let Entry::Put { user, account, .. } = entry else {
+            unreachable!()
+        };
+        let (before, _) = self.unindex(id).expect("checked above");
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record_update(&actor, id, &before, &user, sign_in.is_some());
+        }
+        self.index(id, user, account);
+        Ok(&self.users[&id])
+    }
+
+    pub fn deactivate(&mut self, id: UserId) -> Result<&User, StoreError> {
+        self.deactivate_as(Actor::System, id)
+    }
+
+    pub fn deactivate_as(&mut self, actor: Actor, id: UserId) -> Result<&User, StoreError> {
+        self.update_as(actor, id, |u| u.active = false)
+    }
+
+    pub fn delete(&mut self, id: UserId) -> Result<User, StoreError> {
+        self.delete_as(Actor::System, id)
+    }
+
+    pub fn delete_as(&mut self, actor: Actor, id: UserId) -> Result<User, StoreError> {
+        if !self.users.contains_key(&id) {
+            return Err(StoreError::NotFound(id));
+        }
+        self.write(&Entry::Delete { id })?;
+        if let Some(audit) = self.audit.as_mut() {
+            audit.record_deleted(&actor, id);
+        }
+        Ok(self.unindex(id).expect("checked above").0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (UserId, &User)> {
+        self.users.iter().map(|(&id, user)| (id, user))
+    }
+
+    // 日志中可以被压缩掉的行数
+    pub fn garbage(&self) -> usize {
+        self.log
+            .as_ref()
+            .map_or(0, |log| log.lines - self.users.len())
+    }
+
+    // 用当前状态重写日志
+    pub fn compact(&mut self) -> Result<(), StoreError> {
+        let Some(log) = self.log.as_mut() else {
+            return Ok(());
+        };
+        let tmp = log.path.with_extension("jsonl.tmp");
+        let mut file = File::create(&tmp)?;
+        let mut out = Vec::new();
+        for (&id, user) in &self.users {
+            serde_json::to_writer(
+                &mut out,
+                &PutRef {
+                    op: "put",
+                    id,
+                    user,
+                    account: &self.accounts[&id],
+                },
+            )
+            .expect("user is always serializable");
+            out.push(b'\n');
+        }
+        file.write_all(&out)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &log.path)?;
+        // rename 本身记录在目录里, 目录也要 sync 才算落盘
+        let dir = match log.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+        log.file = File::options().append(true).open(&log.path)?;
+        log.lines = self.users.len();
+        Ok(())
+    }
+
+    // 可以被压缩掉的行数超过 min_garbage 并且超过一半时压缩
+    pub fn maybe_compact(&mut self, min_garbage: usize) -> Result<bool, StoreError> {
+        let garbage = self.garbage();
+        if garbage > min_garbage && garbage > self.users.len() {
+            self.compact()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+// 与 Entry::Put 的格式相同, 但不需要 clone User
+#[derive(Serialize)]
+struct PutRef<'a> {
+    op: &'static str,
+    id: UserId,
+    user: &'a User,
+    account: &'a Account,
+}
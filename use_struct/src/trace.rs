@@ -0,0 +1,236 @@
+// trace!: 信息更丰富的 dbg!
+//
+// dbg!(expr) 只会向 stderr 打印 [file:line:column] expr = value.
+// trace!(expr) 与 dbg! 一样获得参数的 ownership 并返回它, 另外还记录:
+// 表达式的类型, 当前线程, 表达式求值耗时, 以及嵌套深度.
+//
+// 嵌套深度: 在 trace!(n * factorial(n - 1)) 求值的过程中, factorial 里的 trace! 会被再次执行,
+// 内层的记录深度 +1, 打印时按深度缩进, 递归的调用结构就一目了然了.
+//
+// 输出位置 (sink) 可以配置: stderr (默认), JSON Lines 文件, 或者内存 (方便测试断言).
+
+use std::cell::Cell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+    pub expr: &'static str,
+    pub type_name: &'static str,
+    // 值的 {:#?} 输出, 与 dbg! 一致
+    pub value: String,
+    pub thread: String,
+    pub elapsed: Duration,
+    pub depth: usize,
+}
+
+pub enum Sink {
+    Stderr,
+    JsonLines(BufWriter<File>),
+    Memory(Arc<Mutex<Vec<Record>>>),
+}
+
+impl Sink {
+    pub fn json_lines<P: AsRef<Path>>(path: P) -> io::Result<Sink> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Sink::JsonLines(BufWriter::new(file)))
+    }
+
+    // 返回 sink 和一个可以读取记录的句柄
+    pub fn memory() -> (Sink, Arc<Mutex<Vec<Record>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        (Sink::Memory(Arc::clone(&records)), records)
+    }
+}
+
+// None 表示使用默认的 stderr
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+// 替换当前的 sink, 返回旧的 sink (如果设置过的话).
+// 旧的 sink 先 flush, 缓冲中的记录写不进去时返回 Err, 旧的 sink 随之丢弃, 新的 sink 仍然生效
+pub fn set_sink(sink: Sink) -> io::Result<Option<Sink>> {
+    let mut guard = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut old = guard.replace(sink);
+    drop(guard);
+    if let Some(Sink::JsonLines(w)) = old.as_mut() {
+        w.flush()?;
+    }
+    Ok(old)
+}
+
+pub fn flush() -> io::Result<()> {
+    let mut guard = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    match guard.as_mut() {
+        Some(Sink::JsonLines(w)) => w.flush(),
+        _ => Ok(()),
+    }
+}
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// 在 trace! 的表达式求值期间存活, 负责维护当前线程的嵌套深度.
+// 即使表达式 panic, drop 时也会把深度恢复.
+pub struct Scope {
+    depth: usize,
+}
+
+impl Scope {
+    pub fn enter() -> Scope {
+        let depth = DEPTH.with(|d| {
+            let depth = d.get();
+            d.set(depth + 1);
+            depth
+        });
+        Scope { depth }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        DEPTH.with(|d| d.set(self.depth));
+    }
+}
+
+pub fn type_name_of<T>(_: &T) -> &'static str {
+    std::any::type_name::<T>()
+}
+
+pub fn current_thread() -> String {
+    let t = thread::current();
+    match t.name() {
+        Some(name) => format!("{:?} {}", t.id(), name),
+        None => format!("{:?}", t.id()),
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // trace!() 与 dbg!() 一样只打印位置
+        if self.expr.is_empty() {
+            return write!(f, "[{}:{}:{}]", self.file, self.line, self.column);
+        }
+        write!(
+            f,
+            "[{}:{}:{}] {}{} = {} ({}, {}, {:?})",
+            self.file,
+            self.line,
+            self.column,
+            "  ".repeat(self.depth),
+            self.expr,
+            self.value,
+            self.type_name,
+            self.thread,
+            self.elapsed
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Record {
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"expr\":{},\"type\":{},\"value\":{},\"thread\":{},\"elapsed_ns\":{},\"depth\":{}}}",
+            json_string(self.file),
+            self.line,
+            self.column,
+            json_string(self.expr),
+            json_string(self.type_name),
+            json_string(&self.value),
+            json_string(&self.thread),
+            self.elapsed.as_nanos(),
+            self.depth
+        )
+    }
+}
+
+pub fn emit(record: Record) {
+    let mut guard = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    match guard.as_mut() {
+        None | Some(Sink::Stderr) => eprintln!("{}", record),
+        Some(Sink::JsonLines(w)) => {
+            // 与 eprintln! 不同, 写文件失败时不 panic, 只是丢掉这条记录
+            let _ = writeln!(w, "{}", record.to_json());
+        }
+        Some(Sink::Memory(records)) => records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(record),
+    }
+}
+
+// 用法与 dbg! 相同:
+// trace!(expr) 返回 expr 的值; trace!(a, b) 返回 (a, b); trace!() 只记录位置.
+#[macro_export]
+macro_rules! trace {
+    () => {
+        $crate::trace::emit($crate::trace::Record {
+            file: file!(),
+            line: line!(),
+            column: column!(),
+            expr: "",
+            type_name: "()",
+            value: String::from("()"),
+            thread: $crate::trace::current_thread(),
+            elapsed: std::time::Duration::ZERO,
+            depth: $crate::trace::Scope::enter().depth(),
+        })
+    };
+    ($val:expr $(,)?) => {{
+        let scope = $crate::trace::Scope::enter();
+        let start = std::time::Instant::now();
+        // 与 dbg! 一样用 match 而不是 let, 以保证临时值的生命周期正确
+        match $val {
+            tmp => {
+                let elapsed = start.elapsed();
+                let depth = scope.depth();
+                drop(scope);
+                $crate::trace::emit($crate::trace::Record {
+                    file: file!(),
+                    line: line!(),
+                    column: column!(),
+                    expr: stringify!($val),
+                    type_name: $crate::trace::type_name_of(&tmp),
+                    value: format!("{:#?}", &tmp),
+                    thread: $crate::trace::current_thread(),
+                    elapsed,
+                    depth,
+                });
+                tmp
+            }
+        }
+    }};
+    ($($val:expr),+ $(,)?) => {
+        ($($crate::trace!($val)),+,)
+    };
+}
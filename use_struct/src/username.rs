@@ -0,0 +1,182 @@
+// Username: 经过校验和规范化的用户名
+//
+// 规则:
+//   去掉首尾空白后转成小写, 所以 "U1" 和 "u1" 是同一个用户名
+//   长度 2 到 32 个字符
+//   只能包含 ASCII 小写字母, 数字, 以及分隔符 "_" "-" "."
+//   必须以字母开头, 不能以分隔符结尾, 分隔符不能连续
+//   不能是保留名 (RESERVED), 这些名字会和路由, 系统账号或者角色混淆
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+pub const MIN_LEN: usize = 2;
+pub const MAX_LEN: usize = 32;
+
+pub const RESERVED: &[&str] = &[
+    "abuse",
+    "admin",
+    "administrator",
+    "anonymous",
+    "api",
+    "help",
+    "hostmaster",
+    "login",
+    "logout",
+    "me",
+    "null",
+    "postmaster",
+    "root",
+    "settings",
+    "support",
+    "system",
+    "undefined",
+    "webmaster",
+    "www",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UsernameError {
+    TooShort { len: usize },
+    TooLong { len: usize },
+    // index 是规范化之后的字符位置
+    InvalidChar { ch: char, index: usize },
+    MustStartWithLetter { ch: char },
+    ConsecutiveSeparators { index: usize },
+    TrailingSeparator,
+    Reserved(String),
+}
+
+impl fmt::Display for UsernameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsernameError::TooShort { len } => {
+                write!(
+                    f,
+                    "username is {} characters, at least {} required",
+                    len, MIN_LEN
+                )
+            }
+            UsernameError::TooLong { len } => {
+                write!(
+                    f,
+                    "username is {} characters, at most {} allowed",
+                    len, MAX_LEN
+                )
+            }
+            UsernameError::InvalidChar { ch, index } => {
+                write!(
+                    f,
+                    "character {:?} at {} is not allowed in username",
+                    ch, index
+                )
+            }
+            UsernameError::MustStartWithLetter { ch } => {
+                write!(f, "username must start with a letter, not {:?}", ch)
+            }
+            UsernameError::ConsecutiveSeparators { index } => {
+                write!(f, "separators at {} must not be consecutive", index)
+            }
+            UsernameError::TrailingSeparator => write!(f, "username must not end with a separator"),
+            UsernameError::Reserved(name) => write!(f, "username {:?} is reserved", name),
+        }
+    }
+}
+
+impl std::error::Error for UsernameError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Username(String);
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '_' | '-' | '.')
+}
+
+impl Username {
+    pub fn parse(s: &str) -> Result<Username, UsernameError> {
+        let name = s.trim().to_lowercase();
+        let len = name.chars().count();
+        if len < MIN_LEN {
+            return Err(UsernameError::TooShort { len });
+        }
+        if len > MAX_LEN {
+            return Err(UsernameError::TooLong { len });
+        }
+        let mut prev_separator = false;
+        for (index, ch) in name.chars().enumerate() {
+            if is_separator(ch) {
+                if prev_separator {
+                    return Err(UsernameError::ConsecutiveSeparators { index });
+                }
+                prev_separator = true;
+            } else if ch.is_ascii_lowercase() || ch.is_ascii_digit() {
+                prev_separator = false;
+            } else {
+                return Err(UsernameError::InvalidChar { ch, index });
+            }
+        }
+        let first = name.chars().next().unwrap();
+        if !first.is_ascii_lowercase() {
+            return Err(UsernameError::MustStartWithLetter { ch: first });
+        }
+        if prev_separator {
+            return Err(UsernameError::TrailingSeparator);
+        }
+        if RESERVED.contains(&name.as_str()) {
+            return Err(UsernameError::Reserved(name));
+        }
+        Ok(Username(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Username {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Username {
+    type Err = UsernameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Username::parse(s)
+    }
+}
+
+impl TryFrom<String> for Username {
+    type Error = UsernameError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Username::parse(&s)
+    }
+}
+
+impl From<Username> for String {
+    fn from(name: Username) -> String {
+        name.0
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Username {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Username {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}